@@ -7,7 +7,7 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::{Mutex, RwLock};
 use tracing::warn;
-use walrus_rust::Walrus;
+use walrus_rust::{StorageBackend, Walrus};
 
 pub const DATA_NAMESPACE: &str = "data_plane";
 
@@ -27,12 +27,19 @@ impl Storage {
         std::fs::create_dir_all(&storage_path)?;
 
         // io_uring is unavailable in many containerized environments; allow opting into mmap.
-        if std::env::var("WALRUS_DISABLE_IO_URING").is_ok() {
-            walrus_rust::disable_fd_backend();
-        }
-        std::env::set_var("WALRUS_DATA_DIR", &storage_path);
+        let backend = if std::env::var("WALRUS_DISABLE_IO_URING").is_ok() {
+            StorageBackend::Mmap
+        } else {
+            StorageBackend::Fd
+        };
 
-        let engine = Arc::new(Walrus::new_for_key(DATA_NAMESPACE)?);
+        let engine = Arc::new(
+            Walrus::builder()
+                .data_dir(&storage_path)
+                .namespace(DATA_NAMESPACE)
+                .backend(backend)
+                .open()?,
+        );
 
         Ok(Self {
             engine,
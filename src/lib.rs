@@ -12,6 +12,8 @@
 //! - **Dual Storage Backends**: FD backend with pread/pwrite (default) or mmap backend
 //! - **Persistent Read Offsets**: Read positions survive process restarts
 //! - **Namespace Isolation**: Separate WAL instances with per-key directories
+//! - **Async Front-end**: `AsyncWalrus` for tokio services, behind the `async` feature; appends
+//!   and syncs await io_uring completions on Linux, reads run on tokio's blocking pool
 //!
 //! ## Quick Start
 //!
@@ -62,9 +64,15 @@
 //! ];
 //! wal.batch_append_for_topic("events", &batch)?;
 //!
+//! // Atomic batch across topics: every batch lands or none does
+//! let order: &[&[u8]] = &[b"order created"];
+//! let event: &[&[u8]] = &[b"order-created event"];
+//! let ranges = wal.batch_append_multi(&[("orders", order), ("outbox", event)])?;
+//! assert_eq!(ranges.len(), 2);
+//!
 //! // Batch read with byte limit (returns at least 1 entry if available)
 //! let max_bytes = 1024 * 1024; // 1MB
-//! let entries = wal.batch_read_for_topic("events", max_bytes, true)?;
+//! let entries = wal.batch_read_for_topic("events", max_bytes, true, None)?;
 //! for entry in entries {
 //!     println!("Read: {} bytes", entry.data.len());
 //! }
@@ -72,6 +80,180 @@
 //! # }
 //! ```
 //!
+//! ## Transactions
+//!
+//! A transaction spans any number of appends, to any topics, over several calls. Its entries
+//! are written right away but readers only see them once it commits; an abort, dropping the
+//! handle or a crash discards them. Readers wait in front of the first entry of a transaction
+//! in flight, so ordering within a topic is kept.
+//!
+//! ```rust,no_run
+//! use walrus_rust::Walrus;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let wal = Walrus::new()?;
+//!
+//! let mut tx = wal.begin_transaction();
+//! tx.append("orders", b"order created")?;
+//! tx.append("outbox", b"order-created event")?;
+//! tx.commit()?; // both become readable together
+//!
+//! let mut tx = wal.begin_transaction();
+//! tx.append("orders", b"never seen")?;
+//! tx.abort()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Waiting for New Entries
+//!
+//! Instead of polling a caught-up topic with sleeps, consumers can wait for the next append.
+//! The topic's writer wakes them as soon as it appends; the timeout bounds the wait:
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use walrus_rust::Walrus;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let wal = Walrus::new()?;
+//!
+//! // `None` if nothing was appended within a second
+//! if let Some(entry) = wal.read_next_blocking("events", true, Duration::from_secs(1))? {
+//!     println!("Read: {} bytes", entry.data.len());
+//! }
+//!
+//! // Empty if nothing was appended within a second
+//! let entries =
+//!     wal.batch_read_for_topic_blocking("events", 1024 * 1024, true, Duration::from_secs(1))?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Committing After Processing
+//!
+//! A checkpointing read consumes entries before the caller has processed them. For
+//! at-least-once delivery, [`Walrus::read_leased()`] returns a batch with a [`CommitToken`]
+//! instead, and [`Walrus::commit()`] persists the read position once processing succeeded.
+//! Entries whose lease was never committed are read again after a restart.
+//!
+//! ```rust,no_run
+//! use walrus_rust::Walrus;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let wal = Walrus::new()?;
+//!
+//! while let Some(lease) = wal.read_leased("events", 1024 * 1024)? {
+//!     for entry in &lease.entries {
+//!         println!("Processing entry {}", entry.seq);
+//!     }
+//!     wal.commit("events", lease.token)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Subscriptions
+//!
+//! [`Walrus::subscribe()`] turns the read loop into an iterator. It fetches batches through the
+//! topic's cursor, waits for appends when caught up, and checkpoints per a
+//! [`CheckpointPolicy`]: `Auto` acknowledges an entry once the next one is requested, `Manual`
+//! waits for [`Subscription::ack()`], and `EveryN(n)` acknowledges in groups of `n`.
+//! Acknowledgements are persisted following the instance's [`ReadConsistency`]; entries read
+//! but not persisted are delivered again after a restart.
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use walrus_rust::{CheckpointPolicy, SeekTarget, SubscribeOptions, Walrus};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let wal = Walrus::new()?;
+//!
+//! let options = SubscribeOptions::new()
+//!     .start(SeekTarget::Beginning)
+//!     .checkpoint(CheckpointPolicy::Manual)
+//!     .max_batch_bytes(256 * 1024)
+//!     .idle_timeout(Duration::from_secs(5));
+//! let mut sub = wal.subscribe("events", options)?;
+//! while let Some(entry) = sub.next() {
+//!     println!("Processed entry {}", entry?.seq);
+//!     sub.ack()?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Consumer Groups
+//!
+//! Each named consumer group reads a topic through its own persisted cursor, so every group
+//! sees every entry. The plain read methods use the default group.
+//!
+//! ```rust,no_run
+//! use walrus_rust::Walrus;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let wal = Walrus::new()?;
+//! wal.append_for_topic("orders", b"order #1")?;
+//!
+//! // Both groups receive the entry
+//! let billing = wal.read_next_as("billing", "orders", true)?;
+//! let audit = wal.read_next_as("audit", "orders", true)?;
+//! assert_eq!(billing.map(|e| e.seq), audit.map(|e| e.seq));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Deleting and Truncating Topics
+//!
+//! [`Walrus::delete_topic()`] discards a topic's entries together with the cursors of all its
+//! consumer groups. [`Walrus::truncate_topic_before()`] discards its oldest sealed blocks up to
+//! a [`SeekTarget`], moving lagging cursors forward. Both are durable once they return, and the
+//! files they free are removed by the background reclaimer as soon as nothing else lives in
+//! them; [`Walrus::reclaim_now()`] waits for that, and [`Walrus::reclaim_stats()`] reports
+//! how much space is waiting and how much was given back.
+//!
+//! A file is only reclaimed once all of its blocks are consumed, so one slow topic can keep
+//! files full of other topics' consumed blocks around. [`WalrusBuilder::compact_on_open`]
+//! copies the unconsumed blocks out of such files while opening, so they can be reclaimed.
+//!
+//! ```rust,no_run
+//! use walrus_rust::{SeekTarget, Walrus};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let wal = Walrus::new()?;
+//!
+//! // Keep roughly the entries from sequence number 1000 on
+//! let oldest = wal.truncate_topic_before("metrics", SeekTarget::Sequence(1000))?;
+//! println!("oldest kept entry: {}", oldest);
+//!
+//! wal.delete_topic("scratch")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A [`RetentionPolicy`] does the same on its own: once a topic is older, larger or longer
+//! than its limits, the background thread discards its oldest sealed blocks even if
+//! nobody read them.
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use walrus_rust::{RetentionPolicy, Walrus};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let wal = Walrus::builder()
+//!     .retention(RetentionPolicy::new().max_age(Duration::from_secs(24 * 3600)))
+//!     .open()?;
+//! wal.set_topic_retention("audit", RetentionPolicy::new().max_bytes(10 << 30))?;
+//!
+//! // Applies the policies right away instead of waiting for the background thread
+//! let discarded = wal.enforce_retention()?;
+//! println!("discarded {} entries", discarded);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Walrus::list_topics()`] and [`Walrus::topic_info()`] show what is stored: a topic's
+//! blocks, where its cursor stands and how many entries and bytes are left to read.
+//!
 //! ## Consistency Models
 //!
 //! Control the trade-off between durability and performance:
@@ -121,6 +303,102 @@
 //! # }
 //! ```
 //!
+//! Whatever the schedule, callers can wait for durability explicitly. Concurrent waiters
+//! share one fsync:
+//!
+//! ```rust,no_run
+//! use walrus_rust::Walrus;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let wal = Walrus::new()?;
+//!
+//! // Returns once this entry is on disk
+//! wal.append_for_topic_durable("payments", b"txn-42")?;
+//!
+//! // Barriers for everything written so far
+//! wal.append_for_topic("events", b"e1")?;
+//! wal.sync_topic("events")?;
+//! wal.sync_all()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! With many concurrent writers under `SyncEach`, [`WalrusBuilder::group_commit`] coalesces
+//! single-entry appends from all topics into one write submission and one fsync per file:
+//!
+//! ```rust,no_run
+//! use walrus_rust::{FsyncSchedule, Walrus};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let wal = Walrus::builder()
+//!     .fsync(FsyncSchedule::SyncEach)
+//!     .group_commit(true)
+//!     .open()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Builder
+//!
+//! [`Walrus::builder()`] configures an instance in one place. Every setting belongs to the
+//! instance, so instances in the same process can use different directories, backends and
+//! fsync policies:
+//!
+//! ```rust,no_run
+//! use walrus_rust::{FsyncSchedule, ReadConsistency, StorageBackend, Walrus};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let wal = Walrus::builder()
+//!     .data_dir("/var/lib/myapp/wal")
+//!     .namespace("orders")
+//!     .backend(StorageBackend::Fd)
+//!     .fsync(FsyncSchedule::SyncEach)
+//!     .consistency(ReadConsistency::StrictlyAtOnce)
+//!     .open()?;
+//!
+//! let scratch = Walrus::builder()
+//!     .data_dir("/tmp/scratch-wal")
+//!     .backend(StorageBackend::Mmap)
+//!     .fsync(FsyncSchedule::NoFsync)
+//!     .open()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The `with_*` constructors below are shorthands for common builder settings.
+//!
+//! ## Async
+//!
+//! With the `async` cargo feature, `AsyncWalrus` wraps an instance for tokio services.
+//! On Linux with the FD backend, appends and syncs go through an io_uring driver thread
+//! and the calling task awaits the completion instead of blocking a runtime thread. Reads,
+//! leased reads, commits and subscriptions are not async I/O: they run the blocking call
+//! on tokio's blocking pool, as appends and syncs do in the other configurations.
+//!
+//! ```rust,ignore
+//! use walrus_rust::{AsyncWalrus, SubscribeOptions, Walrus};
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let wal = AsyncWalrus::new(Walrus::builder().open()?);
+//!
+//! let seq = wal.append("orders", b"order-1").await?;
+//! let seqs = wal.batch_append("orders", &[b"order-2", b"order-3"]).await?;
+//! wal.sync("orders").await?;
+//!
+//! let entry = wal.read_next("orders", true).await?;
+//! let entries = wal.batch_read("orders", 1024 * 1024, true, None).await?;
+//!
+//! let mut sub = wal.subscribe("orders", SubscribeOptions::new()).await?;
+//! while let Some(entry) = sub.next().await {
+//!     println!("Processed entry {}", entry?.seq);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! While an async append to a topic is in flight, synchronous appends to the same topic
+//! wait for it to finish.
+//!
 //! ## Namespace Isolation
 //!
 //! Create isolated WAL instances with separate storage directories:
@@ -149,9 +427,61 @@
 //! # }
 //! ```
 //!
+//! ## Shutting Down
+//!
+//! Each instance runs a background thread for scheduled fsyncs and reclamation, and
+//! another that persists clean markers. [`Walrus::close()`] fsyncs what was appended,
+//! writes out the read offset index and the clean markers, lets the background thread
+//! finish its queued fsyncs and reclamations, and joins both threads. Dropping an instance
+//! does the same but ignores errors, so services and tests can open and close instances
+//! over the same directory as often as they like. Blocks are recorded in a manifest as
+//! they fill up and on shutdown, so reopening after a clean shutdown reads no entries;
+//! after a crash only the blocks that were still being written to are scanned:
+//!
+//! ```rust,no_run
+//! use walrus_rust::Walrus;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let wal = Walrus::new_for_key("jobs")?;
+//! wal.append_for_topic("pending", b"job-1")?;
+//! wal.close()?;
+//!
+//! let wal = Walrus::new_for_key("jobs")?;
+//! assert_eq!(wal.read_next("pending", true)?.unwrap().data, b"job-1");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Block Geometry
+//!
+//! Block size, blocks per file and the largest single allocation can be set per instance
+//! through [`Walrus::builder()`]. The geometry is recorded in every data file header, so
+//! files written with other settings are still recovered correctly.
+//!
+//! ```rust,no_run
+//! use walrus_rust::Walrus;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! // Small blocks for many low-volume topics
+//! let wal = Walrus::builder()
+//!     .namespace("small-messages")
+//!     .block_size(64 * 1024)
+//!     .blocks_per_file(1024)
+//!     .open()?;
+//!
+//! // Room for entries larger than 1GB
+//! let blobs = Walrus::builder()
+//!     .namespace("blobs")
+//!     .blocks_per_file(512)
+//!     .max_alloc(4 * 1024 * 1024 * 1024)
+//!     .open()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
 //! ## Storage Backends
 //!
-//! Walrus supports two storage backends that can be selected at runtime:
+//! Walrus supports two storage backends that can be selected per instance:
 //!
 //! ### FD Backend (File Descriptor) - Default
 //!
@@ -178,26 +508,33 @@
 //!
 //! - **Works on**: All platforms
 //! - **Best for**: Windows, or when FD backend is incompatible
-//! - **Default**: Disabled (use `disable_fd_backend()` to enable)
+//! - **Default**: Disabled (select with `StorageBackend::Mmap`)
 //!
 //! ### Selecting a Backend
 //!
 //! ```rust,no_run
-//! use walrus_rust::{enable_fd_backend, disable_fd_backend};
+//! use walrus_rust::{StorageBackend, Walrus, disable_fd_backend};
 //!
-//! // Use FD backend (default - uses io_uring for batches on Linux)
-//! enable_fd_backend();
+//! # fn main() -> std::io::Result<()> {
+//! // Per instance
+//! let wal = Walrus::builder().backend(StorageBackend::Mmap).open()?;
 //!
-//! // Use mmap backend (no io_uring, sequential batch operations)
+//! // Process-wide switch, read only by the legacy constructors
 //! disable_fd_backend();
+//! let wal = Walrus::new()?;
+//! # Ok(())
+//! # }
 //! ```
 //!
-//! **Important**: Backend selection must be done before creating any `Walrus` instances.
+//! An instance keeps the backend it was opened with. The builder defaults to the FD
+//! backend; the process-wide switch only affects instances opened afterwards through
+//! `Walrus::new` and the other legacy constructors.
 //!
 //! ## Environment Variables
 //!
-//! - `WALRUS_DATA_DIR`: Change storage location (default: `./wal_files`)
-//! - `WALRUS_INSTANCE_KEY`: Default namespace for all instances
+//! - `WALRUS_DATA_DIR`: Default storage location (default: `./wal_files`), overridden by
+//!   [`WalrusBuilder::data_dir()`]
+//! - `WALRUS_INSTANCE_KEY`: Default namespace for instances opened without one
 //! - `WALRUS_QUIET=1`: Suppress debug output
 //!
 //! ```bash
@@ -213,18 +550,23 @@
 //!
 //! ## Performance Characteristics
 //!
-//! - **Block size**: 10MB per block
+//! - **Block size**: 10MB per block by default (configurable via [`Walrus::builder()`])
 //! - **Batch limits**: Up to 2,000 entries or ~10GB payload per batch
 //! - **Default fsync interval**: 200ms
-//! - **File organization**: 100 blocks per file (1GB files)
+//! - **File organization**: 100 blocks per file (1GB files) by default, after a 4KB versioned
+//!   file header
 //!
 //! ## Types
 //!
 //! ```rust
-//! # use walrus_rust::Entry;
 //! // Entry returned by read operations
 //! pub struct Entry {
 //!     pub data: Vec<u8>,
+//!     pub seq: u64,      // per-topic sequence number assigned at append
+//!     pub block_id: u64, // block the entry was read from
+//!     pub offset: u64,   // offset of the entry within that block
+//!     pub timestamp_ms: u64, // append time in unix millis (0 for older records)
+//!     pub headers: Vec<(String, Vec<u8>)>, // key/value headers stored with the entry
 //! }
 //! ```
 //!
@@ -238,22 +580,74 @@
 //! - [`Walrus::new_for_key()`]: Create namespaced instance
 //! - [`Walrus::with_consistency_for_key()`]: Namespaced with consistency
 //! - [`Walrus::with_consistency_and_schedule_for_key()`]: Full namespaced configuration
+//! - [`Walrus::builder()`]: [`WalrusBuilder`] for data directory, namespace, backend,
+//!   consistency, fsync policy, block geometry and group commit
+//! - [`Walrus::close()`]: Flush everything and stop the background threads, reporting errors
 //!
 //! ### Write Operations
 //!
-//! - [`Walrus::append_for_topic()`]: Append single entry to topic
-//! - [`Walrus::batch_append_for_topic()`]: Atomic batch write (up to 2,000 entries)
+//! - [`Walrus::append_for_topic()`]: Append single entry to topic, returns its sequence number
+//! - [`Walrus::append_for_topic_with_headers()`]: Append a single entry with key/value headers
+//! - [`Walrus::batch_append_for_topic()`]: Atomic batch write (up to 2,000 entries), returns the
+//!   range of sequence numbers assigned to the batch
+//! - [`Walrus::batch_append_multi()`]: Atomic batch write across several topics, returns the
+//!   range assigned to each topic's batch
+//! - [`Walrus::begin_transaction()`]: Start a [`Transaction`] whose entries, across topics and
+//!   calls, become readable on commit and are discarded on abort
+//! - [`Walrus::append_for_topic_durable()`]: Append single entry and wait until it is fsynced
+//! - [`Walrus::sync_topic()`], [`Walrus::sync_all()`]: Wait until everything written so far to
+//!   a topic, or to any topic, is fsynced
+//!
+//! ### Async Operations (`async` feature)
+//!
+//! - `AsyncWalrus::append()`, `AsyncWalrus::batch_append()`: Async single and batch appends
+//! - `AsyncWalrus::sync()`: Resolves once everything written so far to a topic is fsynced
+//! - `AsyncWalrus::read_next()`, `AsyncWalrus::batch_read()`: Reads on tokio's blocking pool
+//! - `AsyncWalrus::read_next_wait()`, `AsyncWalrus::batch_read_wait()`: Reads that wait
+//!   up to a timeout for new entries without parking a thread while waiting
+//! - `AsyncWalrus::read_leased()`, `AsyncWalrus::commit()`: Leased reads and commits on
+//!   tokio's blocking pool
+//! - `AsyncWalrus::subscribe()`: `AsyncSubscription`, a `Stream` counterpart of [`Subscription`]
 //!
 //! ### Read Operations
 //!
 //! - [`Walrus::read_next()`]: Read next entry (checkpoint=true consumes, false peeks)
 //! - [`Walrus::batch_read_for_topic()`]: Read multiple entries up to byte limit
+//! - [`Walrus::read_next_blocking()`], [`Walrus::batch_read_for_topic_blocking()`]: Same as
+//!   above, but wait up to a timeout for new entries when the topic is caught up
+//! - [`Walrus::seek_topic()`]: Move a topic's persisted read position to the beginning, the
+//!   end, a sequence number, or the first entry appended at or after a timestamp
+//! - [`Walrus::read_next_as()`], [`Walrus::batch_read_for_topic_as()`],
+//!   [`Walrus::seek_topic_as()`]: Same as above through a named consumer group's own cursor
+//! - [`Walrus::read_leased()`], [`Walrus::read_leased_as()`]: Read a batch without consuming
+//!   it; [`Walrus::commit()`] consumes it once processed
+//! - [`Walrus::subscribe()`]: Iterate over a topic's entries, waiting for new ones, with
+//!   automatic, manual or every-N acknowledgement
+//! - [`Walrus::get_topic_entry_count_as()`]: Unread entries of a topic for a consumer group
+//!
+//! ### Topic Management
+//!
+//! - [`Walrus::truncate_topic_before()`]: Discard a topic's oldest blocks up to a position
+//! - [`Walrus::delete_topic()`]: Discard a topic with all its cursors and persisted state
+//! - [`Walrus::set_topic_retention()`]: Give a topic its own retention policy
+//! - [`Walrus::clear_topic_retention()`]: Put a topic back under the default policy
+//! - [`Walrus::enforce_retention()`]: Apply every retention policy now
+//! - [`Walrus::reclaim_now()`]: Delete fully consumed files right away
+//! - [`Walrus::reclaim_stats()`]: Space waiting to be reclaimed and reclaimed so far
+//! - [`Walrus::list_topics()`]: Topics with entries still on disk
+//! - [`Walrus::topic_info()`]: Blocks, reader position and backlog of a topic
+//! - [`Walrus::topic_info_as()`]: Same, for a consumer group's cursor
 
 #![recursion_limit = "256"]
 pub mod wal;
 pub use wal::{
-    Entry, FsyncSchedule, ReadConsistency, WalIndex, Walrus, disable_fd_backend, enable_fd_backend,
+    BlockInfo, CheckpointPolicy, CommitToken, Entry, FsyncSchedule, Lease, ReadConsistency,
+    ReaderPosition, ReclaimStats, RetentionPolicy, SeekTarget, StorageBackend, SubscribeOptions,
+    Subscription, TopicInfo, Transaction, WalIndex, Walrus, WalrusBuilder, disable_fd_backend,
+    enable_fd_backend,
 };
+#[cfg(feature = "async")]
+pub use wal::{AsyncSubscription, AsyncWalrus};
 
 pub fn topic_entry_count(wal: &Walrus, topic: &str) -> u64 {
     wal.get_topic_entry_count(topic)
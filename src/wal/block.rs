@@ -4,9 +4,133 @@ use rkyv::Deserialize as _;
 use rkyv_derive::{Archive, Deserialize, Serialize};
 use std::sync::Arc;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Entry {
     pub data: Vec<u8>,
+    /// Per-topic sequence number assigned at append time.
+    pub seq: u64,
+    /// Block the entry was read from.
+    pub block_id: u64,
+    /// Offset of the entry's metadata prefix within that block.
+    pub offset: u64,
+    /// Wall-clock append time in unix milliseconds; 0 for records written before
+    /// append times were stored.
+    pub timestamp_ms: u64,
+    /// Key/value headers stored alongside the payload, in the order they were appended.
+    pub headers: Vec<(String, Vec<u8>)>,
+}
+
+/// Upper bound on the encoded size of one entry's headers.
+pub(crate) const MAX_HEADERS_SIZE: usize = 64 * 1024;
+
+/// Encodes headers as `[count: u16]` followed by `[key_len: u16][key][value_len: u32][value]`
+/// per header. An empty list encodes to no bytes at all.
+pub(crate) fn encode_headers(headers: &[(&str, &[u8])]) -> std::io::Result<Vec<u8>> {
+    if headers.is_empty() {
+        return Ok(Vec::new());
+    }
+    let too_large = || {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("headers exceed {} bytes", MAX_HEADERS_SIZE),
+        )
+    };
+    let count = u16::try_from(headers.len()).map_err(|_| too_large())?;
+    let mut out = Vec::new();
+    out.extend_from_slice(&count.to_le_bytes());
+    for (key, value) in headers {
+        let key_len = u16::try_from(key.len()).map_err(|_| too_large())?;
+        let value_len = u32::try_from(value.len()).map_err(|_| too_large())?;
+        out.extend_from_slice(&key_len.to_le_bytes());
+        out.extend_from_slice(key.as_bytes());
+        out.extend_from_slice(&value_len.to_le_bytes());
+        out.extend_from_slice(value);
+        if out.len() > MAX_HEADERS_SIZE {
+            return Err(too_large());
+        }
+    }
+    Ok(out)
+}
+
+fn decode_headers(mut buf: &[u8]) -> std::io::Result<Vec<(String, Vec<u8>)>> {
+    if buf.is_empty() {
+        return Ok(Vec::new());
+    }
+    let malformed = || std::io::Error::new(std::io::ErrorKind::InvalidData, "malformed headers");
+    let mut take = |n: usize| -> std::io::Result<&[u8]> {
+        if buf.len() < n {
+            return Err(malformed());
+        }
+        let (head, rest) = buf.split_at(n);
+        buf = rest;
+        Ok(head)
+    };
+
+    let count = u16::from_le_bytes(take(2)?.try_into().unwrap());
+    let mut headers = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let key_len = u16::from_le_bytes(take(2)?.try_into().unwrap()) as usize;
+        let key = String::from_utf8(take(key_len)?.to_vec()).map_err(|_| malformed())?;
+        let value_len = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+        headers.push((key, take(value_len)?.to_vec()));
+    }
+    Ok(headers)
+}
+
+// Byte 1 of the metadata prefix used to be the high byte of the metadata length.
+// Metadata is capped at PREFIX_META_SIZE - 2 bytes, so it was always zero; it now
+// carries the record version. Records written before versioning decode as v0.
+const META_VERSION_V0: u8 = 0;
+pub(crate) const META_VERSION: u8 = 1;
+
+// Values of `Metadata::txn_kind`
+const TXN_KIND_NONE: u8 = 0;
+const TXN_KIND_DATA: u8 = 1;
+const TXN_KIND_COMMIT: u8 = 2;
+const TXN_KIND_ABORT: u8 = 3;
+
+/// Part a record plays in a transaction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum TxnMark {
+    /// Appended outside any transaction.
+    #[default]
+    None,
+    /// Entry appended through transaction `id`.
+    Data(u64),
+    /// Marker written once transaction `id` committed.
+    Commit(u64),
+    /// Marker written once transaction `id` aborted.
+    Abort(u64),
+}
+
+impl TxnMark {
+    /// Transaction id and kind, as stored in a record's metadata.
+    pub(crate) fn encode(self) -> (u64, u8) {
+        match self {
+            TxnMark::None => (0, TXN_KIND_NONE),
+            TxnMark::Data(id) => (id, TXN_KIND_DATA),
+            TxnMark::Commit(id) => (id, TXN_KIND_COMMIT),
+            TxnMark::Abort(id) => (id, TXN_KIND_ABORT),
+        }
+    }
+
+    pub(crate) fn decode(id: u64, kind: u8) -> Self {
+        match kind {
+            TXN_KIND_DATA => TxnMark::Data(id),
+            TXN_KIND_COMMIT => TxnMark::Commit(id),
+            TXN_KIND_ABORT => TxnMark::Abort(id),
+            _ => TxnMark::None,
+        }
+    }
+}
+
+#[derive(Archive, Deserialize, Serialize, Debug)]
+#[archive(check_bytes)]
+struct MetadataV0 {
+    read_size: usize,
+    owned_by: String,
+    next_block_start: u64,
+    checksum: u64,
 }
 
 #[derive(Archive, Deserialize, Serialize, Debug)]
@@ -16,6 +140,123 @@ pub(crate) struct Metadata {
     pub(crate) owned_by: String,
     pub(crate) next_block_start: u64,
     pub(crate) checksum: u64,
+    // v0 records carry no sequence number and decode with seq = 0
+    pub(crate) seq: u64,
+    // Wall-clock append time in unix millis; v0 records decode with 0
+    pub(crate) timestamp_ms: u64,
+    // Leading bytes of the record body holding encoded headers; `read_size` and
+    // `checksum` cover headers and payload together. v0 records have none.
+    pub(crate) headers_size: u32,
+    // Transaction the record belongs to and its part in it, see `TxnMark`; v0
+    // records decode as written outside any transaction
+    pub(crate) txn_id: u64,
+    pub(crate) txn_kind: u8,
+}
+
+impl Metadata {
+    pub(crate) fn txn(&self) -> TxnMark {
+        TxnMark::decode(self.txn_id, self.txn_kind)
+    }
+
+    /// Serializes into a full `PREFIX_META_SIZE` prefix: [len, version, rkyv bytes..., zero padding]
+    pub(crate) fn encode_prefix(&self) -> std::io::Result<Vec<u8>> {
+        let meta_bytes = rkyv::to_bytes::<_, 256>(self)
+            .map_err(|e| std::io::Error::other(format!("serialize metadata failed: {:?}", e)))?;
+        if meta_bytes.len() > PREFIX_META_SIZE - 2 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "metadata too large",
+            ));
+        }
+
+        let mut meta_buffer = vec![0u8; PREFIX_META_SIZE];
+        meta_buffer[0] = meta_bytes.len() as u8;
+        meta_buffer[1] = META_VERSION;
+        meta_buffer[2..2 + meta_bytes.len()].copy_from_slice(&meta_bytes);
+        Ok(meta_buffer)
+    }
+
+    /// Parses a metadata prefix; `prefix` must hold at least `PREFIX_META_SIZE` bytes.
+    pub(crate) fn decode_prefix(prefix: &[u8]) -> std::io::Result<Metadata> {
+        let meta_len = prefix[0] as usize;
+        let version = prefix[1];
+
+        if meta_len == 0 || meta_len > PREFIX_META_SIZE - 2 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("invalid metadata length: {}", meta_len),
+            ));
+        }
+
+        let mut aligned = rkyv::AlignedVec::with_capacity(meta_len);
+        aligned.extend_from_slice(&prefix[2..2 + meta_len]);
+
+        let deserialize_failed = || {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "failed to deserialize metadata",
+            )
+        };
+
+        // SAFETY: `aligned` contains bytes read from our own file format.
+        // We bounded `meta_len` to PREFIX_META_SIZE and copy into an `AlignedVec`,
+        // which satisfies alignment requirements of rkyv.
+        match version {
+            META_VERSION_V0 => {
+                let archived = unsafe { rkyv::archived_root::<MetadataV0>(&aligned[..]) };
+                let v0: MetadataV0 = archived
+                    .deserialize(&mut rkyv::Infallible)
+                    .map_err(|_| deserialize_failed())?;
+                Ok(Metadata {
+                    read_size: v0.read_size,
+                    owned_by: v0.owned_by,
+                    next_block_start: v0.next_block_start,
+                    checksum: v0.checksum,
+                    seq: 0,
+                    timestamp_ms: 0,
+                    headers_size: 0,
+                    txn_id: 0,
+                    txn_kind: TXN_KIND_NONE,
+                })
+            }
+            META_VERSION => {
+                let archived = unsafe { rkyv::archived_root::<Metadata>(&aligned[..]) };
+                archived
+                    .deserialize(&mut rkyv::Infallible)
+                    .map_err(|_| deserialize_failed())
+            }
+            other => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unsupported metadata version: {}", other),
+            )),
+        }
+    }
+
+    /// Builds the entry for a record body (headers followed by payload) read at
+    /// `offset` of block `block_id`. The caller has verified the checksum.
+    pub(crate) fn into_entry(
+        self,
+        mut body: Vec<u8>,
+        block_id: u64,
+        offset: u64,
+    ) -> std::io::Result<Entry> {
+        let split = self.headers_size as usize;
+        if split > body.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "headers larger than record",
+            ));
+        }
+        let data = body.split_off(split);
+        Ok(Entry {
+            data,
+            seq: self.seq,
+            block_id,
+            offset,
+            timestamp_ms: self.timestamp_ms,
+            headers: decode_headers(&body)?,
+        })
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -29,83 +270,78 @@ pub struct Block {
 }
 
 impl Block {
+    /// Writes one record: the metadata prefix, then `headers` (already encoded), then `data`.
     pub(crate) fn write(
         &self,
         in_block_offset: u64,
+        headers: &[u8],
         data: &[u8],
         owned_by: &str,
-        next_block_start: u64,
+        seq: u64,
+        timestamp_ms: u64,
     ) -> std::io::Result<()> {
-        debug_assert!(
-            in_block_offset + (data.len() as u64 + PREFIX_META_SIZE as u64) <= self.limit
-        );
-
-        let new_meta = Metadata {
-            read_size: data.len(),
-            owned_by: owned_by.to_string(),
-            next_block_start,
-            checksum: checksum64(data),
-        };
-
-        let meta_bytes = rkyv::to_bytes::<_, 256>(&new_meta).map_err(|e| {
-            std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("serialize metadata failed: {:?}", e),
-            )
-        })?;
-        if meta_bytes.len() > PREFIX_META_SIZE - 2 {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "metadata too large",
-            ));
-        }
-
-        let mut meta_buffer = vec![0u8; PREFIX_META_SIZE];
-        // Store actual length in first 2 bytes (little endian)
-        meta_buffer[0] = (meta_bytes.len() & 0xFF) as u8;
-        meta_buffer[1] = ((meta_bytes.len() >> 8) & 0xFF) as u8;
-        // Copy actual metadata starting at byte 2
-        meta_buffer[2..2 + meta_bytes.len()].copy_from_slice(&meta_bytes);
-
-        // Combine and write
-        let mut combined = Vec::with_capacity(PREFIX_META_SIZE + data.len());
-        combined.extend_from_slice(&meta_buffer);
-        combined.extend_from_slice(data);
+        let body_len = headers.len() + data.len();
+        debug_assert!(in_block_offset + (body_len as u64 + PREFIX_META_SIZE as u64) <= self.limit);
 
+        let combined =
+            self.encode_record(headers, data, owned_by, seq, timestamp_ms, TxnMark::None)?;
         let file_offset = self.offset + in_block_offset;
         self.mmap.write(file_offset as usize, &combined);
         Ok(())
     }
 
-    pub(crate) fn read(&self, in_block_offset: u64) -> std::io::Result<(Entry, usize)> {
+    /// Serializes one record (metadata prefix, headers, payload) for this block.
+    pub(crate) fn encode_record(
+        &self,
+        headers: &[u8],
+        data: &[u8],
+        owned_by: &str,
+        seq: u64,
+        timestamp_ms: u64,
+        txn: TxnMark,
+    ) -> std::io::Result<Vec<u8>> {
+        let body_len = headers.len() + data.len();
+        let mut combined = Vec::with_capacity(PREFIX_META_SIZE + body_len);
+        combined.resize(PREFIX_META_SIZE, 0);
+        combined.extend_from_slice(headers);
+        combined.extend_from_slice(data);
+
+        let (txn_id, txn_kind) = txn.encode();
+        let new_meta = Metadata {
+            read_size: body_len,
+            owned_by: owned_by.to_string(),
+            next_block_start: self.offset + self.limit,
+            checksum: checksum64(&combined[PREFIX_META_SIZE..]),
+            seq,
+            timestamp_ms,
+            headers_size: headers.len() as u32,
+            txn_id,
+            txn_kind,
+        };
+        combined[..PREFIX_META_SIZE].copy_from_slice(&new_meta.encode_prefix()?);
+        Ok(combined)
+    }
+
+    /// Decodes only the metadata prefix at `in_block_offset`, without touching the payload.
+    pub(crate) fn read_meta(&self, in_block_offset: u64) -> std::io::Result<Metadata> {
         let mut meta_buffer = vec![0; PREFIX_META_SIZE];
         let file_offset = self.offset + in_block_offset;
         self.mmap.read(file_offset as usize, &mut meta_buffer);
+        Metadata::decode_prefix(&meta_buffer)
+    }
 
-        // Read the actual metadata length from first 2 bytes
-        let meta_len = (meta_buffer[0] as usize) | ((meta_buffer[1] as usize) << 8);
-
-        if meta_len == 0 || meta_len > PREFIX_META_SIZE - 2 {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("invalid metadata length: {}", meta_len),
-            ));
-        }
-
-        // Deserialize only the actual metadata bytes (skip the 2-byte length prefix)
-        let mut aligned = rkyv::AlignedVec::with_capacity(meta_len);
-        aligned.extend_from_slice(&meta_buffer[2..2 + meta_len]);
+    pub(crate) fn read(&self, in_block_offset: u64) -> std::io::Result<(Entry, usize)> {
+        self.read_record(in_block_offset)
+            .map(|(entry, consumed, _)| (entry, consumed))
+    }
 
-        // SAFETY: `aligned` contains bytes we just read from our own file format.
-        // We bounded `meta_len` to PREFIX_META_SIZE and copy into an `AlignedVec`,
-        // which satisfies alignment requirements of rkyv.
-        let archived = unsafe { rkyv::archived_root::<Metadata>(&aligned[..]) };
-        let meta: Metadata = archived.deserialize(&mut rkyv::Infallible).map_err(|_| {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "failed to deserialize metadata",
-            )
-        })?;
+    /// Like [`Block::read`], also returning the record's part in a transaction.
+    pub(crate) fn read_record(
+        &self,
+        in_block_offset: u64,
+    ) -> std::io::Result<(Entry, usize, TxnMark)> {
+        let file_offset = self.offset + in_block_offset;
+        let meta = self.read_meta(in_block_offset)?;
         let actual_entry_size = meta.read_size;
 
         // Read the actual data
@@ -129,7 +365,9 @@ impl Block {
         }
 
         let consumed = PREFIX_META_SIZE + actual_entry_size;
-        Ok((Entry { data: ret_buffer }, consumed))
+        let txn = meta.txn();
+        let entry = meta.into_entry(ret_buffer, self.id, in_block_offset)?;
+        Ok((entry, consumed, txn))
     }
 
     pub(crate) fn zero_range(&self, in_block_offset: u64, size: u64) -> std::io::Result<()> {
@@ -2,19 +2,40 @@ use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::SystemTime;
 
-// Global flag to choose backend
-pub(crate) static USE_FD_BACKEND: AtomicBool = AtomicBool::new(true);
+// Backend of instances opened through the legacy `Walrus` constructors
+static USE_FD_BACKEND: AtomicBool = AtomicBool::new(true);
 
-// Public function to enable FD backend
+// Public function to enable FD backend for legacy-constructed instances opened afterwards
 pub fn enable_fd_backend() {
     USE_FD_BACKEND.store(true, Ordering::Relaxed);
 }
 
-// Public function to disable FD backend (use mmap instead)
+// Public function to disable FD backend (use mmap instead) for legacy-constructed instances
+// opened afterwards; `WalrusBuilder` ignores it
 pub fn disable_fd_backend() {
     USE_FD_BACKEND.store(false, Ordering::Relaxed);
 }
 
+/// How an instance reads and writes its data files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StorageBackend {
+    /// pread/pwrite on file descriptors, with io_uring batching on Linux
+    Fd,
+    /// Memory-mapped files
+    Mmap,
+}
+
+// Backend of instances opened through `WalrusBuilder` without one
+pub(crate) const DEFAULT_BACKEND: StorageBackend = StorageBackend::Fd;
+
+pub(crate) fn legacy_backend() -> StorageBackend {
+    if USE_FD_BACKEND.load(Ordering::Relaxed) {
+        StorageBackend::Fd
+    } else {
+        StorageBackend::Mmap
+    }
+}
+
 // Macro to conditionally print debug messages
 macro_rules! debug_print {
     ($($arg:tt)*) => {
@@ -33,14 +54,70 @@ pub enum FsyncSchedule {
     NoFsync,  // disable fsyncing entirely (maximum throughput, no durability)
 }
 
+// Default geometry; instances can override it through `WalrusBuilder`
 pub(crate) const DEFAULT_BLOCK_SIZE: u64 = 10 * 1024 * 1024; // 10mb
 pub(crate) const BLOCKS_PER_FILE: u64 = 100;
 pub(crate) const MAX_ALLOC: u64 = 1 * 1024 * 1024 * 1024; // 1 GiB cap per block
 // Expose so integration tests can match the on-disk layout when poking raw files.
 pub const PREFIX_META_SIZE: usize = 256;
-pub(crate) const MAX_FILE_SIZE: u64 = DEFAULT_BLOCK_SIZE * BLOCKS_PER_FILE;
+// Every data file starts with a header page; its first block begins right after it.
+pub const FILE_HEADER_SIZE: u64 = 4096;
 pub(crate) const MAX_BATCH_ENTRIES: usize = 2000;
 pub(crate) const MAX_BATCH_BYTES: u64 = 10 * 1024 * 1024 * 1024; // 10 GiB total payload limit
+// Blocks are page aligned, so sizes must be a multiple of this
+const BLOCK_SIZE_ALIGN: u64 = 4096;
+
+/// Block layout of the data files of one `Walrus` instance. Block size and blocks per
+/// file are recorded in every file header so recovery reads each file as it was written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Geometry {
+    pub(crate) block_size: u64,
+    pub(crate) blocks_per_file: u64,
+    // Largest single block handed out, for entries that do not fit a regular block
+    pub(crate) max_alloc: u64,
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        Self {
+            block_size: DEFAULT_BLOCK_SIZE,
+            blocks_per_file: BLOCKS_PER_FILE,
+            max_alloc: MAX_ALLOC,
+        }
+    }
+}
+
+impl Geometry {
+    /// Bytes of blocks per data file, not counting the file header.
+    pub(crate) fn file_size(&self) -> u64 {
+        self.block_size * self.blocks_per_file
+    }
+
+    pub(crate) fn validate(&self) -> std::io::Result<()> {
+        let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, msg);
+        if self.block_size == 0 || !self.block_size.is_multiple_of(BLOCK_SIZE_ALIGN) {
+            return Err(invalid(format!(
+                "block size {} must be a non-zero multiple of {}",
+                self.block_size, BLOCK_SIZE_ALIGN
+            )));
+        }
+        if self.blocks_per_file == 0 {
+            return Err(invalid("blocks per file must be at least 1".to_string()));
+        }
+        let Some(file_size) = self.block_size.checked_mul(self.blocks_per_file) else {
+            return Err(invalid(
+                "block size times blocks per file overflows".to_string(),
+            ));
+        };
+        if self.max_alloc < self.block_size || self.max_alloc > file_size {
+            return Err(invalid(format!(
+                "max allocation {} must be between the block size {} and the file size {}",
+                self.max_alloc, self.block_size, file_size
+            )));
+        }
+        Ok(())
+    }
+}
 
 static LAST_MILLIS: AtomicU64 = AtomicU64::new(0);
 
@@ -67,6 +144,13 @@ pub(crate) fn now_millis_str() -> String {
     }
 }
 
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis().try_into().unwrap_or(u64::MAX))
+        .unwrap_or(0)
+}
+
 pub(crate) fn checksum64(data: &[u8]) -> u64 {
     // FNV-1a 64-bit checksum
     const FNV_OFFSET: u64 = 0xcbf29ce484222325;
@@ -0,0 +1,87 @@
+use crate::wal::config::{FILE_HEADER_SIZE, Geometry, checksum64};
+
+const FILE_MAGIC: [u8; 8] = *b"WALRUSDF";
+// Version 1 introduced the header itself; headerless files are treated as version 0.
+pub(crate) const FORMAT_VERSION: u32 = 1;
+// magic + version + header size + block size + blocks per file + creation time + checksum
+const ENCODED_LEN: usize = 8 + 4 + 4 + 8 + 8 + 8 + 8;
+
+/// Header at the start of every data file. It occupies the first
+/// `FILE_HEADER_SIZE` bytes, so blocks start right after it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct FileHeader {
+    pub(crate) format_version: u32,
+    pub(crate) header_size: u32,
+    pub(crate) block_size: u64,
+    pub(crate) blocks_per_file: u64,
+    pub(crate) created_ms: u64,
+}
+
+impl FileHeader {
+    pub(crate) fn current(geometry: &Geometry, created_ms: u64) -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            header_size: FILE_HEADER_SIZE as u32,
+            block_size: geometry.block_size,
+            blocks_per_file: geometry.blocks_per_file,
+            created_ms,
+        }
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(FILE_HEADER_SIZE as usize);
+        out.extend_from_slice(&FILE_MAGIC);
+        out.extend_from_slice(&self.format_version.to_le_bytes());
+        out.extend_from_slice(&self.header_size.to_le_bytes());
+        out.extend_from_slice(&self.block_size.to_le_bytes());
+        out.extend_from_slice(&self.blocks_per_file.to_le_bytes());
+        out.extend_from_slice(&self.created_ms.to_le_bytes());
+        let checksum = checksum64(&out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+        out.resize(FILE_HEADER_SIZE as usize, 0);
+        out
+    }
+
+    /// Parses the first bytes of a data file. `Ok(None)` means the file predates
+    /// file headers; a header this build cannot read is an `InvalidData` error.
+    pub(crate) fn decode(bytes: &[u8]) -> std::io::Result<Option<FileHeader>> {
+        if bytes.len() < ENCODED_LEN || bytes[..8] != FILE_MAGIC {
+            return Ok(None);
+        }
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+
+        // The version decides the layout, so it is checked before the checksum
+        let format_version = u32_at(8);
+        if format_version != FORMAT_VERSION {
+            return Err(invalid(format!(
+                "file format version {} is not supported (newest supported: {})",
+                format_version, FORMAT_VERSION
+            )));
+        }
+        let header = FileHeader {
+            format_version,
+            header_size: u32_at(12),
+            block_size: u64_at(16),
+            blocks_per_file: u64_at(24),
+            created_ms: u64_at(32),
+        };
+        if checksum64(&bytes[..ENCODED_LEN - 8]) != u64_at(ENCODED_LEN - 8) {
+            return Err(invalid("file header checksum mismatch".to_string()));
+        }
+        if header.block_size == 0 || header.blocks_per_file == 0 {
+            return Err(invalid(format!(
+                "invalid file geometry: block size {}, blocks per file {}",
+                header.block_size, header.blocks_per_file
+            )));
+        }
+        if (header.header_size as usize) < ENCODED_LEN {
+            return Err(invalid(format!(
+                "invalid file header size {}",
+                header.header_size
+            )));
+        }
+        Ok(Some(header))
+    }
+}
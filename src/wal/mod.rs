@@ -1,12 +1,22 @@
 mod block;
 mod config;
+mod file_header;
 mod paths;
 mod runtime;
 mod storage;
 
 pub use block::Entry;
-pub use config::{FsyncSchedule, PREFIX_META_SIZE, disable_fd_backend, enable_fd_backend};
-pub use runtime::{ReadConsistency, WalIndex, Walrus};
+pub use config::{
+    FILE_HEADER_SIZE, FsyncSchedule, PREFIX_META_SIZE, StorageBackend, disable_fd_backend,
+    enable_fd_backend,
+};
+#[cfg(feature = "async")]
+pub use runtime::{AsyncSubscription, AsyncWalrus};
+pub use runtime::{
+    BlockInfo, BlockPos, CheckpointPolicy, CommitToken, Lease, ReadConsistency, ReaderPosition,
+    ReclaimStats, RetentionPolicy, SeekTarget, SubscribeOptions, Subscription, TopicInfo,
+    Transaction, WalIndex, Walrus, WalrusBuilder,
+};
 
 #[doc(hidden)]
 pub fn __set_thread_namespace_for_tests(key: &str) {
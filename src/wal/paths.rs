@@ -1,6 +1,10 @@
-use crate::wal::config::{MAX_FILE_SIZE, now_millis_str, sanitize_namespace, wal_data_dir};
+use crate::wal::config::{
+    FILE_HEADER_SIZE, Geometry, now_millis_str, sanitize_namespace, wal_data_dir,
+};
+use crate::wal::file_header::FileHeader;
 use std::cell::RefCell;
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
@@ -10,8 +14,16 @@ pub(crate) struct WalPathManager {
 
 impl WalPathManager {
     pub(crate) fn default() -> Self {
-        let mut root = wal_data_dir();
-        if let Some(key) = thread_namespace() {
+        Self::resolve(None, None)
+    }
+
+    /// Root directory for an instance. Settings left unset fall back to
+    /// `WALRUS_DATA_DIR` and the thread or `WALRUS_INSTANCE_KEY` namespace.
+    pub(crate) fn resolve(data_dir: Option<&Path>, namespace: Option<&str>) -> Self {
+        let mut root = data_dir.map(Path::to_path_buf).unwrap_or_else(wal_data_dir);
+        if let Some(key) = namespace {
+            root.push(sanitize_namespace(key));
+        } else if let Some(key) = thread_namespace() {
             root.push(sanitize_namespace(&key));
         } else if let Ok(key) = std::env::var("WALRUS_INSTANCE_KEY") {
             root.push(sanitize_namespace(&key));
@@ -19,12 +31,6 @@ impl WalPathManager {
         Self { root }
     }
 
-    pub(crate) fn for_key(key: &str) -> Self {
-        let mut root = wal_data_dir();
-        root.push(sanitize_namespace(key));
-        Self { root }
-    }
-
     pub(crate) fn ensure_root(&self) -> std::io::Result<()> {
         fs::create_dir_all(&self.root)
     }
@@ -33,12 +39,14 @@ impl WalPathManager {
         self.root.join(format!("{}_index.db", file_name))
     }
 
-    pub(crate) fn create_new_file(&self) -> std::io::Result<String> {
+    pub(crate) fn create_new_file(&self, geometry: &Geometry) -> std::io::Result<String> {
         self.ensure_root()?;
         let file_name = now_millis_str();
         let path = self.root.join(&file_name);
-        let f = std::fs::File::create(&path)?;
-        f.set_len(MAX_FILE_SIZE)?;
+        let mut f = std::fs::File::create(&path)?;
+        f.set_len(FILE_HEADER_SIZE + geometry.file_size())?;
+        let created_ms = file_name.parse().unwrap_or(0);
+        f.write_all(&FileHeader::current(geometry, created_ms).encode())?;
 
         // Sync file metadata (size, etc.) to disk
         f.sync_all()?;
@@ -1,44 +1,76 @@
 use crate::wal::block::Block;
-use crate::wal::config::{DEFAULT_BLOCK_SIZE, MAX_ALLOC, MAX_FILE_SIZE, debug_print};
+use crate::wal::config::{FILE_HEADER_SIZE, Geometry, debug_print};
 use crate::wal::paths::WalPathManager;
-use crate::wal::storage::{SharedMmap, SharedMmapKeeper};
+use crate::wal::storage::{SharedMmap, SharedMmapKeeper, StorageOptions};
 use std::cell::UnsafeCell;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
-use std::sync::{Arc, OnceLock, RwLock};
+use std::sync::{Arc, RwLock};
 
-use super::DELETION_TX;
+use super::manifest::BlockManifest;
+use super::reclaim::Reclaimer;
 
 pub(super) struct BlockAllocator {
     next_block: UnsafeCell<Block>,
     lock: AtomicBool,
     paths: Arc<WalPathManager>,
+    geometry: Geometry,
+    storage: StorageOptions,
+    tracker: Arc<StateTracker>,
+    manifest: BlockManifest,
 }
 
 impl BlockAllocator {
-    pub(super) fn new(paths: Arc<WalPathManager>) -> std::io::Result<Self> {
-        let file1 = paths.create_new_file()?;
-        let mmap: Arc<SharedMmap> = SharedMmapKeeper::get_mmap_arc(&file1)?;
+    pub(super) fn new(
+        paths: Arc<WalPathManager>,
+        geometry: Geometry,
+        storage: StorageOptions,
+        tracker: Arc<StateTracker>,
+    ) -> std::io::Result<Self> {
+        let manifest = BlockManifest::open(&paths)?;
+        let file1 = paths.create_new_file(&geometry)?;
+        let mmap: Arc<SharedMmap> = tracker.mmaps().get_mmap_arc(&file1, storage)?;
         debug_print!(
             "[alloc] init: created file={}, max_file_size={}B, block_size={}B",
             file1,
-            MAX_FILE_SIZE,
-            DEFAULT_BLOCK_SIZE
+            geometry.file_size(),
+            geometry.block_size
         );
         Ok(BlockAllocator {
             next_block: UnsafeCell::new(Block {
                 id: 1,
-                offset: 0,
-                limit: DEFAULT_BLOCK_SIZE,
+                offset: FILE_HEADER_SIZE,
+                limit: geometry.block_size,
                 file_path: file1,
                 mmap,
                 used: 0,
             }),
             lock: AtomicBool::new(false),
             paths,
+            geometry,
+            storage,
+            tracker,
+            manifest,
         })
     }
 
+    pub(super) fn geometry(&self) -> &Geometry {
+        &self.geometry
+    }
+
+    pub(super) fn storage(&self) -> StorageOptions {
+        self.storage
+    }
+
+    pub(super) fn tracker(&self) -> &Arc<StateTracker> {
+        &self.tracker
+    }
+
+    /// Sealed blocks of this instance's files, for recovery to skip reading.
+    pub(super) fn manifest(&self) -> &BlockManifest {
+        &self.manifest
+    }
+
     /// SAFETY: Caller must ensure the returned `Block` is treated as uniquely
     /// owned by a single writer until it is sealed. Internally, a spin lock
     /// ensures exclusive mutable access to `next_block` while computing the
@@ -50,23 +82,25 @@ impl BlockAllocator {
         // to `next_block` so creating a `&mut` from `UnsafeCell` is sound.
         let data = unsafe { &mut *self.next_block.get() };
         let prev_block_file_path = data.file_path.clone();
-        if data.offset >= MAX_FILE_SIZE {
+        if data.offset >= FILE_HEADER_SIZE + self.geometry.file_size() {
             // mark previous file as fully allocated before switching
-            FileStateTracker::set_fully_allocated(prev_block_file_path);
-            data.file_path = self.paths.create_new_file()?;
-            data.mmap = SharedMmapKeeper::get_mmap_arc(&data.file_path)?;
-            data.offset = 0;
+            self.tracker.set_fully_allocated(prev_block_file_path);
+            data.file_path = self.paths.create_new_file(&self.geometry)?;
+            data.mmap = self
+                .tracker
+                .mmaps()
+                .get_mmap_arc(&data.file_path, self.storage)?;
+            data.offset = FILE_HEADER_SIZE;
             data.used = 0;
             debug_print!("[alloc] rolled over to new file: {}", data.file_path);
         }
 
         // set the cur block as locked
-        BlockStateTracker::register_block(data.id as usize, &data.file_path);
-        FileStateTracker::register_file_if_absent(&data.file_path);
-        FileStateTracker::add_block_to_file_state(&data.file_path);
-        FileStateTracker::set_block_locked(data.id as usize);
+        self.tracker
+            .register_block(data.id as usize, &data.file_path);
+        self.tracker.set_block_locked(data.id as usize);
         let ret = data.clone();
-        data.offset += DEFAULT_BLOCK_SIZE;
+        data.offset += self.geometry.block_size;
         data.id += 1;
         self.unlock();
         debug_print!(
@@ -83,14 +117,18 @@ impl BlockAllocator {
     /// by one writer and not read concurrently while being written. The
     /// internal spin lock provides exclusive access to mutate allocator state.
     pub(super) unsafe fn alloc_block(&self, want_bytes: u64) -> std::io::Result<Block> {
-        if want_bytes == 0 || want_bytes > MAX_ALLOC {
+        let block_size = self.geometry.block_size;
+        if want_bytes == 0 || want_bytes > self.geometry.max_alloc {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidInput,
-                "invalid allocation size, a single entry can't be more than 1gb",
+                format!(
+                    "invalid allocation size, a single entry can't be more than {} bytes",
+                    self.geometry.max_alloc
+                ),
             ));
         }
-        let alloc_units = (want_bytes + DEFAULT_BLOCK_SIZE - 1) / DEFAULT_BLOCK_SIZE;
-        let alloc_size = alloc_units * DEFAULT_BLOCK_SIZE;
+        let alloc_units = want_bytes.div_ceil(block_size);
+        let alloc_size = alloc_units * block_size;
         debug_print!(
             "[alloc] alloc_block: want_bytes={}, units={}, size={}",
             want_bytes,
@@ -102,13 +140,16 @@ impl BlockAllocator {
         // SAFETY: Guarded by `self.lock()` above, providing exclusive access
         // to `next_block` so creating a `&mut` from `UnsafeCell` is sound.
         let data = unsafe { &mut *self.next_block.get() };
-        if data.offset + alloc_size > MAX_FILE_SIZE {
+        if data.offset + alloc_size > FILE_HEADER_SIZE + self.geometry.file_size() {
             let prev_block_file_path = data.file_path.clone();
-            data.file_path = self.paths.create_new_file()?;
-            data.mmap = SharedMmapKeeper::get_mmap_arc(&data.file_path)?;
-            data.offset = 0;
+            data.file_path = self.paths.create_new_file(&self.geometry)?;
+            data.mmap = self
+                .tracker
+                .mmaps()
+                .get_mmap_arc(&data.file_path, self.storage)?;
+            data.offset = FILE_HEADER_SIZE;
             // mark the previous file fully allocated now
-            FileStateTracker::set_fully_allocated(prev_block_file_path);
+            self.tracker.set_fully_allocated(prev_block_file_path);
             debug_print!(
                 "[alloc] file rollover for sized alloc -> {}",
                 data.file_path
@@ -123,10 +164,8 @@ impl BlockAllocator {
             used: 0,
         };
         // register the new block before handing it out
-        BlockStateTracker::register_block(ret.id as usize, &ret.file_path);
-        FileStateTracker::register_file_if_absent(&ret.file_path);
-        FileStateTracker::add_block_to_file_state(&ret.file_path);
-        FileStateTracker::set_block_locked(ret.id as usize);
+        self.tracker.register_block(ret.id as usize, &ret.file_path);
+        self.tracker.set_block_locked(ret.id as usize);
         data.offset += alloc_size;
         data.id += 1;
         self.unlock();
@@ -185,18 +224,105 @@ unsafe impl Sync for BlockAllocator {}
 // thread-affine resources; moving it to another thread is safe.
 unsafe impl Send for BlockAllocator {}
 
-pub(super) fn flush_check(file_path: String) {
-    // readiness check fast path; hook actual reclamation later
-    if let Some((locked, checkpointed, total, fully_allocated)) =
-        FileStateTracker::get_state_snapshot(&file_path)
-    {
-        let ready_to_delete = fully_allocated && locked == 0 && total > 0 && checkpointed >= total;
-        if ready_to_delete {
-            if let Some(tx) = DELETION_TX.get() {
-                let _ = tx.send(file_path);
+/// Block and file bookkeeping of one instance: which blocks are being written or are
+/// checkpointed, per data file, to tell when a file can be handed to its reclaimer.
+pub(super) struct StateTracker {
+    blocks: BlockStateTracker,
+    files: FileStateTracker,
+    mmaps: SharedMmapKeeper,
+    reclaimer: Reclaimer,
+}
+
+impl StateTracker {
+    pub(super) fn new(reclaimer: Reclaimer) -> Self {
+        Self {
+            blocks: BlockStateTracker::default(),
+            files: FileStateTracker::default(),
+            mmaps: SharedMmapKeeper::new(),
+            reclaimer,
+        }
+    }
+
+    pub(super) fn reclaimer(&self) -> &Reclaimer {
+        &self.reclaimer
+    }
+
+    /// The mappings of this instance's files.
+    pub(super) fn mmaps(&self) -> &SharedMmapKeeper {
+        &self.mmaps
+    }
+
+    pub(super) fn flush_check(&self, file_path: String) {
+        // readiness check fast path; hook actual reclamation later
+        if self.is_reclaimable(&file_path) {
+            self.reclaimer.candidate(file_path);
+        }
+    }
+
+    pub(super) fn is_reclaimable(&self, file_path: &str) -> bool {
+        match self.files.get_state_snapshot(file_path) {
+            Some((locked, checkpointed, total, fully_allocated)) => {
+                fully_allocated && locked == 0 && total > 0 && checkpointed >= total
             }
+            None => false,
         }
     }
+
+    pub(super) fn register_file_if_absent(&self, file_path: &str) {
+        self.files.register_file_if_absent(file_path);
+    }
+
+    /// Registers a block of `file_path` and counts it among the file's blocks.
+    pub(super) fn register_block(&self, block_id: usize, file_path: &str) {
+        self.blocks.register_block(block_id, file_path);
+        self.files.add_block_to_file_state(file_path);
+    }
+
+    pub(super) fn set_checkpointed_true(&self, block_id: usize) {
+        if let Some(path) = self.blocks.set_checkpointed(block_id, true) {
+            self.files.inc_checkpoint_for_file(&path);
+            self.flush_check(path);
+        }
+    }
+
+    /// Reverts a checkpoint when a reader seeks back into an already consumed block.
+    pub(super) fn set_checkpointed_false(&self, block_id: usize) {
+        if let Some(path) = self.blocks.set_checkpointed(block_id, false) {
+            self.files.dec_checkpoint_for_file(&path);
+        }
+    }
+
+    pub(super) fn set_fully_allocated(&self, file_path: String) {
+        self.files.set_fully_allocated(&file_path);
+        self.flush_check(file_path);
+    }
+
+    pub(super) fn set_block_locked(&self, block_id: usize) {
+        if let Some(path) = self.blocks.get_file_path_for_block(block_id) {
+            self.files.inc_locked(&path);
+        }
+    }
+
+    pub(super) fn set_block_unlocked(&self, block_id: usize) {
+        if let Some(path) = self.blocks.get_file_path_for_block(block_id) {
+            self.files.dec_locked(&path);
+            self.flush_check(path);
+        }
+    }
+
+    /// Forgets a deleted file and returns the ids of the blocks it held.
+    pub(super) fn forget_file(&self, file_path: &str) -> Vec<u64> {
+        self.files.forget_file(file_path);
+        self.blocks.forget_file(file_path)
+    }
+
+    pub(super) fn file_paths(&self) -> Vec<String> {
+        self.files.file_paths()
+    }
+
+    pub(super) fn get_state_snapshot(&self, file_path: &str) -> Option<(u16, u16, u16, bool)> {
+        self.files.get_state_snapshot(file_path)
+    }
 }
 
 struct BlockState {
@@ -204,17 +330,14 @@ struct BlockState {
     file_path: String,
 }
 
-pub(super) struct BlockStateTracker {}
+#[derive(Default)]
+struct BlockStateTracker {
+    map: RwLock<HashMap<usize, BlockState>>,
+}
 
 impl BlockStateTracker {
-    fn map() -> &'static RwLock<HashMap<usize, BlockState>> {
-        static MAP: OnceLock<RwLock<HashMap<usize, BlockState>>> = OnceLock::new();
-        MAP.get_or_init(|| RwLock::new(HashMap::new()))
-    }
-
-    pub(super) fn register_block(block_id: usize, file_path: &str) {
-        let map = Self::map();
-        if let Ok(mut w) = map.write() {
+    fn register_block(&self, block_id: usize, file_path: &str) {
+        if let Ok(mut w) = self.map.write() {
             w.entry(block_id).or_insert_with(|| BlockState {
                 is_checkpointed: AtomicBool::new(false),
                 file_path: file_path.to_string(),
@@ -222,31 +345,32 @@ impl BlockStateTracker {
         }
     }
 
-    pub(super) fn get_file_path_for_block(block_id: usize) -> Option<String> {
-        let map = Self::map();
-        let r = map.read().ok()?;
+    fn get_file_path_for_block(&self, block_id: usize) -> Option<String> {
+        let r = self.map.read().ok()?;
         r.get(&block_id).map(|b| b.file_path.clone())
     }
 
-    pub(super) fn set_checkpointed_true(block_id: usize) {
-        let path_opt = {
-            let map = Self::map();
-            if let Ok(r) = map.read() {
-                if let Some(b) = r.get(&block_id) {
-                    b.is_checkpointed.store(true, Ordering::Release);
-                    Some(b.file_path.clone())
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        };
+    // Returns the block's file when the flag changed, so re-checkpointing a block can't
+    // inflate the file counter
+    fn set_checkpointed(&self, block_id: usize, checkpointed: bool) -> Option<String> {
+        let r = self.map.read().ok()?;
+        let b = r.get(&block_id)?;
+        (b.is_checkpointed.swap(checkpointed, Ordering::AcqRel) != checkpointed)
+            .then(|| b.file_path.clone())
+    }
 
-        if let Some(path) = path_opt {
-            FileStateTracker::inc_checkpoint_for_file(&path);
-            flush_check(path);
+    fn forget_file(&self, file_path: &str) -> Vec<u64> {
+        let mut forgotten = Vec::new();
+        if let Ok(mut w) = self.map.write() {
+            w.retain(|id, b| {
+                let keep = b.file_path != file_path;
+                if !keep {
+                    forgotten.push(*id as u64);
+                }
+                keep
+            });
         }
+        forgotten
     }
 }
 
@@ -257,17 +381,17 @@ struct FileState {
     is_fully_allocated: AtomicBool,
 }
 
-pub(super) struct FileStateTracker {}
+#[derive(Default)]
+struct FileStateTracker {
+    map: RwLock<HashMap<String, FileState>>,
+}
 
 impl FileStateTracker {
-    fn map() -> &'static RwLock<HashMap<String, FileState>> {
-        static MAP: OnceLock<RwLock<HashMap<String, FileState>>> = OnceLock::new();
-        MAP.get_or_init(|| RwLock::new(HashMap::new()))
-    }
-
-    pub(super) fn register_file_if_absent(file_path: &str) {
-        let map = Self::map();
-        let mut w = map.write().expect("file state map write lock poisoned");
+    fn register_file_if_absent(&self, file_path: &str) {
+        let mut w = self
+            .map
+            .write()
+            .expect("file state map write lock poisoned");
         w.entry(file_path.to_string()).or_insert_with(|| FileState {
             locked_block_ctr: AtomicU16::new(0),
             checkpoint_block_ctr: AtomicU16::new(0),
@@ -276,62 +400,73 @@ impl FileStateTracker {
         });
     }
 
-    pub(super) fn add_block_to_file_state(file_path: &str) {
-        Self::register_file_if_absent(file_path);
-        let map = Self::map();
-        if let Ok(r) = map.read() {
-            if let Some(st) = r.get(file_path) {
-                st.total_blocks.fetch_add(1, Ordering::AcqRel);
-            }
+    fn add_block_to_file_state(&self, file_path: &str) {
+        self.register_file_if_absent(file_path);
+        if let Ok(r) = self.map.read()
+            && let Some(st) = r.get(file_path)
+        {
+            st.total_blocks.fetch_add(1, Ordering::AcqRel);
         }
     }
 
-    pub(super) fn set_fully_allocated(file_path: String) {
-        Self::register_file_if_absent(&file_path);
-        let map = Self::map();
-        if let Ok(r) = map.read() {
-            if let Some(st) = r.get(&file_path) {
-                st.is_fully_allocated.store(true, Ordering::Release);
-            }
+    fn set_fully_allocated(&self, file_path: &str) {
+        self.register_file_if_absent(file_path);
+        if let Ok(r) = self.map.read()
+            && let Some(st) = r.get(file_path)
+        {
+            st.is_fully_allocated.store(true, Ordering::Release);
         }
-        flush_check(file_path);
     }
 
-    pub(super) fn set_block_locked(block_id: usize) {
-        if let Some(path) = BlockStateTracker::get_file_path_for_block(block_id) {
-            let map = Self::map();
-            if let Ok(r) = map.read() {
-                if let Some(st) = r.get(&path) {
-                    st.locked_block_ctr.fetch_add(1, Ordering::AcqRel);
-                }
-            }
+    fn inc_locked(&self, file_path: &str) {
+        if let Ok(r) = self.map.read()
+            && let Some(st) = r.get(file_path)
+        {
+            st.locked_block_ctr.fetch_add(1, Ordering::AcqRel);
         }
     }
 
-    pub(super) fn set_block_unlocked(block_id: usize) {
-        if let Some(path) = BlockStateTracker::get_file_path_for_block(block_id) {
-            let map = Self::map();
-            if let Ok(r) = map.read() {
-                if let Some(st) = r.get(&path) {
-                    st.locked_block_ctr.fetch_sub(1, Ordering::AcqRel);
-                }
-            }
-            flush_check(path);
+    fn dec_locked(&self, file_path: &str) {
+        if let Ok(r) = self.map.read()
+            && let Some(st) = r.get(file_path)
+        {
+            st.locked_block_ctr.fetch_sub(1, Ordering::AcqRel);
         }
     }
 
-    pub(super) fn inc_checkpoint_for_file(file_path: &str) {
-        let map = Self::map();
-        if let Ok(r) = map.read() {
-            if let Some(st) = r.get(file_path) {
-                st.checkpoint_block_ctr.fetch_add(1, Ordering::AcqRel);
-            }
+    fn inc_checkpoint_for_file(&self, file_path: &str) {
+        if let Ok(r) = self.map.read()
+            && let Some(st) = r.get(file_path)
+        {
+            st.checkpoint_block_ctr.fetch_add(1, Ordering::AcqRel);
         }
     }
 
-    pub(super) fn get_state_snapshot(file_path: &str) -> Option<(u16, u16, u16, bool)> {
-        let map = Self::map();
-        let r = map.read().ok()?;
+    fn dec_checkpoint_for_file(&self, file_path: &str) {
+        if let Ok(r) = self.map.read()
+            && let Some(st) = r.get(file_path)
+        {
+            let _ =
+                st.checkpoint_block_ctr
+                    .fetch_update(Ordering::AcqRel, Ordering::Acquire, |v| v.checked_sub(1));
+        }
+    }
+
+    fn forget_file(&self, file_path: &str) {
+        if let Ok(mut w) = self.map.write() {
+            w.remove(file_path);
+        }
+    }
+
+    fn file_paths(&self) -> Vec<String> {
+        self.map
+            .read()
+            .map(|r| r.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn get_state_snapshot(&self, file_path: &str) -> Option<(u16, u16, u16, bool)> {
+        let r = self.map.read().ok()?;
         let st = r.get(file_path)?;
         let locked = st.locked_block_ctr.load(Ordering::Acquire);
         let checkpointed = st.checkpoint_block_ctr.load(Ordering::Acquire);
@@ -0,0 +1,465 @@
+use crate::wal::block::Entry;
+use crate::wal::config::debug_print;
+use std::collections::HashMap;
+use std::future::Future;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, ready};
+use std::time::Duration;
+
+use super::subscription::{SubscribeOptions, SubscriptionState};
+use super::topic_signal::TopicSignal;
+use super::walrus::Walrus;
+use super::walrus_commit::{CommitToken, Lease};
+use super::writer::validate_batch;
+
+#[cfg(target_os = "linux")]
+use super::io_driver::{IoDriver, IoJob, IoOp};
+#[cfg(target_os = "linux")]
+use crate::wal::config::{FsyncSchedule, StorageBackend};
+
+/// Async handle to a [`Walrus`] instance for tokio services.
+///
+/// On Linux with the FD backend, appends and syncs are submitted to a dedicated io_uring
+/// driver thread and the calling task awaits their completion, so no runtime thread is
+/// parked on disk I/O. Everything else runs the blocking call on tokio's blocking pool:
+/// syncs on the mmap backend, on other platforms or without io_uring, and reads on every
+/// backend. A read holds its cursor's lock from planning until the cursor moves past what
+/// it parsed, which cannot be held across an await; on the FD backend the blocking batch
+/// read still reads its ranges through io_uring.
+///
+/// While an async append to a topic is in flight, synchronous appends to that topic
+/// through [`AsyncWalrus::wal`] wait for it to finish. Async appends to one topic queue
+/// up behind each other, but fail with `WouldBlock` during a synchronous batch write to
+/// the topic, as synchronous appends do. An append whose future is dropped still completes.
+pub struct AsyncWalrus {
+    // Dropped first: joining the driver finishes the appends in flight
+    #[cfg(target_os = "linux")]
+    driver: Option<IoDriver>,
+    wal: Arc<Walrus>,
+    topic_locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl AsyncWalrus {
+    pub fn new(wal: Walrus) -> Self {
+        Self::from_arc(Arc::new(wal))
+    }
+
+    /// Wraps an instance that synchronous callers keep using too.
+    pub fn from_arc(wal: Arc<Walrus>) -> Self {
+        #[cfg(target_os = "linux")]
+        let driver = if wal.allocator.storage().backend == StorageBackend::Fd {
+            IoDriver::start()
+                .inspect_err(|e| {
+                    debug_print!("[async] io_uring unavailable; using blocking pool: {}", e)
+                })
+                .ok()
+        } else {
+            None
+        };
+        #[cfg(not(target_os = "linux"))]
+        debug_print!("[async] no io_uring on this platform; using blocking pool");
+        Self {
+            #[cfg(target_os = "linux")]
+            driver,
+            wal,
+            topic_locks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The underlying instance, for calls without an async counterpart.
+    pub fn wal(&self) -> &Arc<Walrus> {
+        &self.wal
+    }
+
+    /// Appends one entry and returns its per-topic sequence number. Like
+    /// [`Walrus::append_for_topic`], the entry is fsynced per the fsync schedule.
+    pub async fn append(&self, topic: &str, data: &[u8]) -> std::io::Result<u64> {
+        #[cfg(target_os = "linux")]
+        if let Some(driver) = &self.driver {
+            let seqs = self
+                .append_via_driver(driver, topic, &[data], false)
+                .await?;
+            return Ok(seqs.start);
+        }
+        let wal = self.wal.clone();
+        let topic = topic.to_string();
+        let data = data.to_vec();
+        blocking(move || wal.append_for_topic(&topic, &data)).await
+    }
+
+    /// Atomically appends a batch, like [`Walrus::batch_append_for_topic`], and returns
+    /// the sequence numbers assigned to it. The batch is fsynced before this returns.
+    pub async fn batch_append(&self, topic: &str, batch: &[&[u8]]) -> std::io::Result<Range<u64>> {
+        validate_batch(batch.iter().copied())?;
+
+        #[cfg(target_os = "linux")]
+        if let Some(driver) = &self.driver
+            && !batch.is_empty()
+        {
+            return self.append_via_driver(driver, topic, batch, true).await;
+        }
+        let wal = self.wal.clone();
+        let topic = topic.to_string();
+        let batch: Vec<Vec<u8>> = batch.iter().map(|data| data.to_vec()).collect();
+        blocking(move || {
+            let refs: Vec<&[u8]> = batch.iter().map(|data| data.as_slice()).collect();
+            wal.batch_append_for_topic(&topic, &refs)
+        })
+        .await
+    }
+
+    /// [`Walrus::read_next`] on tokio's blocking pool.
+    pub async fn read_next(&self, topic: &str, checkpoint: bool) -> std::io::Result<Option<Entry>> {
+        let wal = self.wal.clone();
+        let topic = topic.to_string();
+        blocking(move || wal.read_next(&topic, checkpoint)).await
+    }
+
+    /// [`Walrus::batch_read_for_topic`] on tokio's blocking pool.
+    pub async fn batch_read(
+        &self,
+        topic: &str,
+        max_bytes: usize,
+        checkpoint: bool,
+        start_offset: Option<u64>,
+    ) -> std::io::Result<Vec<Entry>> {
+        let wal = self.wal.clone();
+        let topic = topic.to_string();
+        blocking(move || wal.batch_read_for_topic(&topic, max_bytes, checkpoint, start_offset))
+            .await
+    }
+
+    /// Like [`AsyncWalrus::read_next`], but when the topic is caught up waits up to
+    /// `timeout` for a writer to append to it. Nothing is parked while waiting; the
+    /// writer wakes the task. Returns `None` if nothing arrived in time.
+    pub async fn read_next_wait(
+        &self,
+        topic: &str,
+        checkpoint: bool,
+        timeout: Duration,
+    ) -> std::io::Result<Option<Entry>> {
+        let signal = self.wal.reader.signals.for_topic(topic);
+        let deadline = tokio::time::Instant::now().checked_add(timeout);
+        loop {
+            // Taken before reading so an append racing with the read still wakes us
+            let seen = signal.generation();
+            if let Some(entry) = self.read_next(topic, checkpoint).await? {
+                return Ok(Some(entry));
+            }
+            if !changed(&signal, seen, deadline).await {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Like [`AsyncWalrus::batch_read`] from the topic's cursor, but when the topic is
+    /// caught up waits up to `timeout` for a writer to append to it. Returns an empty
+    /// batch if nothing arrived in time.
+    pub async fn batch_read_wait(
+        &self,
+        topic: &str,
+        max_bytes: usize,
+        checkpoint: bool,
+        timeout: Duration,
+    ) -> std::io::Result<Vec<Entry>> {
+        let signal = self.wal.reader.signals.for_topic(topic);
+        let deadline = tokio::time::Instant::now().checked_add(timeout);
+        loop {
+            let seen = signal.generation();
+            let entries = self.batch_read(topic, max_bytes, checkpoint, None).await?;
+            if !entries.is_empty() || !changed(&signal, seen, deadline).await {
+                return Ok(entries);
+            }
+        }
+    }
+
+    /// [`Walrus::read_leased`] on tokio's blocking pool: the entries stay unconsumed until
+    /// the lease's token is committed.
+    pub async fn read_leased(
+        &self,
+        topic: &str,
+        max_bytes: usize,
+    ) -> std::io::Result<Option<Lease>> {
+        let wal = self.wal.clone();
+        let topic = topic.to_string();
+        blocking(move || wal.read_leased(&topic, max_bytes)).await
+    }
+
+    /// [`Walrus::commit`] on tokio's blocking pool.
+    pub async fn commit(&self, topic: &str, token: CommitToken) -> std::io::Result<()> {
+        let wal = self.wal.clone();
+        let topic = topic.to_string();
+        blocking(move || wal.commit(&topic, token)).await
+    }
+
+    /// Async [`Walrus::sync_topic`]: resolves once every entry appended to `topic` so far
+    /// is fsynced.
+    pub async fn sync(&self, topic: &str) -> std::io::Result<()> {
+        #[cfg(target_os = "linux")]
+        if let Some(driver) = &self.driver {
+            let Some(writer) = self.wal.existing_writer(topic)? else {
+                // Nothing appended to this topic by this instance
+                return Ok(());
+            };
+            let (target, block) = writer.written_tail()?;
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            driver.submit(IoJob {
+                stages: vec![vec![IoOp::Fsync { mmap: block.mmap }]],
+                done: Box::new(move |result| {
+                    if result.is_ok() {
+                        writer.mark_synced(target);
+                    }
+                    let _ = tx.send(result);
+                }),
+            });
+            return rx.await.unwrap_or_else(|_| Err(driver_stopped()));
+        }
+        let wal = self.wal.clone();
+        let topic = topic.to_string();
+        blocking(move || wal.sync_topic(&topic)).await
+    }
+
+    /// Async [`Walrus::subscribe`]: the returned [`AsyncSubscription`] is a stream of the
+    /// topic's entries that waits for new ones without parking a thread. Opening it, its
+    /// reads and its acknowledgements run on tokio's blocking pool.
+    pub async fn subscribe(
+        &self,
+        topic: &str,
+        options: SubscribeOptions,
+    ) -> std::io::Result<AsyncSubscription> {
+        let wal = self.wal.clone();
+        let topic = topic.to_string();
+        let state = blocking(move || SubscriptionState::open(&wal, &topic, options)).await?;
+        Ok(AsyncSubscription {
+            wal: self.wal.clone(),
+            state,
+            persisting: None,
+            fetching: None,
+        })
+    }
+
+    /// Plans the entries against the topic's writer, writes them in one io_uring
+    /// submission, fsyncs what the entries need and publishes them from the driver thread.
+    #[cfg(target_os = "linux")]
+    async fn append_via_driver(
+        &self,
+        driver: &IoDriver,
+        topic: &str,
+        entries: &[&[u8]],
+        batch: bool,
+    ) -> std::io::Result<Range<u64>> {
+        let topic_lock = self.topic_lock(topic)?;
+        let guard = topic_lock.lock_owned().await;
+
+        self.wal.mark_topic_dirty(topic);
+        let writer = self.wal.get_or_create_writer(topic)?;
+        let mut append = writer.begin_async()?;
+        let mut seqs: Option<Range<u64>> = None;
+        for data in entries {
+            let seq = append.push(data, &[])?;
+            seqs = Some(seqs.map_or(seq..seq + 1, |range| range.start..seq + 1));
+        }
+        let Some(seqs) = seqs else {
+            return Err(std::io::Error::other("nothing to append"));
+        };
+
+        // Batches are always fsynced, like `Walrus::batch_append_for_topic`
+        let synced = batch || matches!(self.wal.fsync_schedule, FsyncSchedule::SyncEach);
+        let mut flushes = HashMap::new();
+        for sealed in append.sealed() {
+            flushes.insert(sealed.file_path.clone(), sealed.mmap.clone());
+        }
+        let mut writes = Vec::with_capacity(append.records().len());
+        for record in append.records() {
+            writes.push(IoOp::Write {
+                mmap: record.block.mmap.clone(),
+                offset: record.block.offset + record.offset,
+                bytes: record.bytes.clone(),
+            });
+            if synced {
+                flushes.insert(record.block.file_path.clone(), record.block.mmap.clone());
+            }
+        }
+        let fsyncs = flushes
+            .into_values()
+            .map(|mmap| IoOp::Fsync { mmap })
+            .collect();
+
+        let wal = self.wal.clone();
+        let topic = topic.to_string();
+        let published = seqs.clone();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        driver.submit(IoJob {
+            stages: vec![writes, fsyncs],
+            done: Box::new(move |result| {
+                // Runs even when the caller stopped waiting, so the topic stays consistent
+                let result = result.and_then(|()| append.commit(synced));
+                if result.is_ok() {
+                    wal.increment_topic_entry_count(&topic, published.end - published.start);
+                }
+                drop(guard);
+                let _ = tx.send(result);
+            }),
+        });
+        rx.await.unwrap_or_else(|_| Err(driver_stopped()))?;
+        Ok(seqs)
+    }
+
+    fn topic_lock(&self, topic: &str) -> std::io::Result<Arc<tokio::sync::Mutex<()>>> {
+        let mut locks = self
+            .topic_locks
+            .lock()
+            .map_err(|_| std::io::Error::other("topic locks poisoned"))?;
+        Ok(locks.entry(topic.to_string()).or_default().clone())
+    }
+}
+
+impl From<Walrus> for AsyncWalrus {
+    fn from(wal: Walrus) -> Self {
+        Self::new(wal)
+    }
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// Stream of a topic's entries, returned by [`AsyncWalrus::subscribe`]. It behaves like
+/// [`Subscription`](crate::Subscription) and implements [`futures_core::Stream`];
+/// [`AsyncSubscription::next`] reads one entry without any stream utilities.
+pub struct AsyncSubscription {
+    wal: Arc<Walrus>,
+    state: SubscriptionState,
+    // Index write of an acknowledgement; finished before the next one starts
+    persisting: Option<BoxFuture<std::io::Result<()>>>,
+    // Batch read, possibly waiting for the topic to change; kept across cancelled polls
+    fetching: Option<BoxFuture<std::io::Result<Option<Lease>>>>,
+}
+
+impl AsyncSubscription {
+    /// The next entry, or `None` once the idle timeout passes with the topic caught up.
+    pub async fn next(&mut self) -> Option<std::io::Result<Entry>> {
+        std::future::poll_fn(|cx| self.poll_entry(cx)).await
+    }
+
+    /// Acknowledges every entry returned so far, like [`Subscription::ack`](crate::Subscription::ack).
+    pub async fn ack(&mut self) -> std::io::Result<()> {
+        std::future::poll_fn(|cx| self.poll_persisted(cx)).await?;
+        if let Some(token) = self.state.ack() {
+            self.persisting = Some(self.persist(token));
+            std::future::poll_fn(|cx| self.poll_persisted(cx)).await?;
+        }
+        Ok(())
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.state.topic
+    }
+
+    fn poll_entry(&mut self, cx: &mut Context<'_>) -> Poll<Option<std::io::Result<Entry>>> {
+        loop {
+            if let Err(e) = ready!(self.poll_persisted(cx)) {
+                return Poll::Ready(Some(Err(e)));
+            }
+            if let Some(token) = self.state.auto_ack() {
+                self.persisting = Some(self.persist(token));
+                continue;
+            }
+            if let Some(entry) = self.state.pop() {
+                return Poll::Ready(Some(Ok(entry)));
+            }
+            let fetching = match &mut self.fetching {
+                Some(fetching) => fetching,
+                None => self.fetching.insert(self.fetch()),
+            };
+            let result = ready!(fetching.as_mut().poll(cx));
+            self.fetching = None;
+            match result {
+                Ok(Some(lease)) => self.state.fill(lease),
+                Ok(None) => return Poll::Ready(None),
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+        }
+    }
+
+    fn poll_persisted(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let Some(persisting) = &mut self.persisting else {
+            return Poll::Ready(Ok(()));
+        };
+        let result = ready!(persisting.as_mut().poll(cx));
+        self.persisting = None;
+        Poll::Ready(result)
+    }
+
+    fn persist(&self, token: CommitToken) -> BoxFuture<std::io::Result<()>> {
+        let wal = self.wal.clone();
+        Box::pin(blocking(move || wal.persist_cursor_position(&token)))
+    }
+
+    /// Reads the next batch, waiting for appends while the topic is caught up. Resolves
+    /// to `None` if the idle timeout passes first.
+    fn fetch(&self) -> BoxFuture<std::io::Result<Option<Lease>>> {
+        let wal = self.wal.clone();
+        let group = self.state.group.clone();
+        let topic = self.state.topic.clone();
+        let max_bytes = self.state.max_batch_bytes;
+        let signal = self.state.signal.clone();
+        let deadline = self
+            .state
+            .idle_timeout
+            .and_then(|timeout| tokio::time::Instant::now().checked_add(timeout));
+        Box::pin(async move {
+            loop {
+                let seen = signal.generation();
+                let (wal, group, topic) = (wal.clone(), group.clone(), topic.clone());
+                let lease =
+                    blocking(move || wal.lease_for(group.as_deref(), &topic, max_bytes)).await?;
+                if lease.is_some() {
+                    return Ok(lease);
+                }
+                if !changed(&signal, seen, deadline).await {
+                    return Ok(None);
+                }
+            }
+        })
+    }
+}
+
+impl futures_core::Stream for AsyncSubscription {
+    type Item = std::io::Result<Entry>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_entry(cx)
+    }
+}
+
+/// Resolves to true once `signal` moves past `seen`, or to false if `deadline` passes first.
+async fn changed(signal: &TopicSignal, seen: u64, deadline: Option<tokio::time::Instant>) -> bool {
+    let change = std::future::poll_fn(|cx| signal.poll_past(seen, cx));
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, change).await.is_ok(),
+        None => {
+            change.await;
+            true
+        }
+    }
+}
+
+// Reads and cursor writes hold locks that cannot span an await, so they stay blocking
+async fn blocking<T, F>(f: F) -> std::io::Result<T>
+where
+    F: FnOnce() -> std::io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await.unwrap_or_else(|e| {
+        Err(std::io::Error::other(format!(
+            "blocking task failed: {}",
+            e
+        )))
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn driver_stopped() -> std::io::Error {
+    std::io::Error::other("io driver stopped")
+}
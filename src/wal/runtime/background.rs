@@ -1,30 +1,32 @@
 use crate::wal::config::{FsyncSchedule, debug_print};
-use crate::wal::storage::{StorageImpl, open_storage_for_path};
+use crate::wal::storage::{StorageImpl, StorageOptions, open_storage_for_path};
 use std::collections::{HashMap, HashSet};
-use std::fs;
 use std::path::Path;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::mpsc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use super::DELETION_TX;
+use super::allocator::StateTracker;
+use super::reclaim::{Maintenance, ReclaimMsg};
 
 #[cfg(target_os = "linux")]
-use crate::wal::config::USE_FD_BACKEND;
+use crate::wal::config::StorageBackend;
 #[cfg(target_os = "linux")]
 use std::os::unix::io::AsRawFd;
 
 #[cfg(target_os = "linux")]
 use io_uring;
 
-pub(super) fn start_background_workers(fsync_schedule: FsyncSchedule) -> Arc<mpsc::Sender<String>> {
+pub(super) fn start_background_workers(
+    fsync_schedule: FsyncSchedule,
+    storage: StorageOptions,
+    tracker: Arc<StateTracker>,
+    del_rx: mpsc::Receiver<ReclaimMsg>,
+) -> (Arc<mpsc::Sender<String>>, thread::JoinHandle<()>) {
     let (tx, rx) = mpsc::channel::<String>();
     let tx_arc = Arc::new(tx);
-    let (del_tx, del_rx) = mpsc::channel::<String>();
-    let del_tx_arc = Arc::new(del_tx);
-    let _ = DELETION_TX.set(del_tx_arc.clone());
     let pool: HashMap<String, StorageImpl> = HashMap::new();
     let tick = Arc::new(AtomicU64::new(0));
     let sleep_millis = match fsync_schedule {
@@ -33,17 +35,42 @@ pub(super) fn start_background_workers(fsync_schedule: FsyncSchedule) -> Arc<mps
         FsyncSchedule::NoFsync => 10000, // Even less frequent cleanup when no fsyncing
     };
 
-    thread::spawn(move || {
+    let handle = thread::spawn(move || {
         let mut pool = pool;
         let tick = tick;
         let del_rx = del_rx;
-        let mut delete_pending = HashSet::new();
+        let mut maintenance = Maintenance::new();
 
         #[cfg(target_os = "linux")]
         let mut ring = io_uring::IoUring::new(2048).expect("Failed to create io_uring");
 
         loop {
-            thread::sleep(Duration::from_millis(sleep_millis));
+            // Sleep until the next flush, reclaiming files and applying retention as
+            // soon as they are due
+            let deadline = Instant::now() + Duration::from_millis(sleep_millis);
+            while !maintenance.stopping
+                && let Some(left) = deadline.checked_duration_since(Instant::now())
+            {
+                // Age limits are checked between flushes too, as these can be far apart
+                match del_rx.recv_timeout(left.min(maintenance.until_age_sweep())) {
+                    Ok(msg) => {
+                        // Take everything queued meanwhile, so a burst costs one pass. A
+                        // stop flushes right away instead of at the deadline.
+                        maintenance.take(msg);
+                        while let Ok(msg) = del_rx.try_recv() {
+                            maintenance.take(msg);
+                        }
+                        maintenance.run(&del_rx, &mut pool, &tracker);
+                    }
+                    Err(mpsc::RecvTimeoutError::Timeout) => {}
+                    // Nothing can queue files any more; keep flushing
+                    Err(mpsc::RecvTimeoutError::Disconnected) => {
+                        thread::sleep(left);
+                        break;
+                    }
+                }
+                maintenance.sweep_aged();
+            }
 
             // Phase 1: Collect unique paths to flush
             let mut unique = HashSet::new();
@@ -64,7 +91,7 @@ pub(super) fn start_background_workers(fsync_schedule: FsyncSchedule) -> Arc<mps
                 }
 
                 if !pool.contains_key(path) {
-                    match open_storage_for_path(path) {
+                    match open_storage_for_path(path, storage) {
                         Ok(storage) => {
                             pool.insert(path.clone(), storage);
                         }
@@ -78,7 +105,7 @@ pub(super) fn start_background_workers(fsync_schedule: FsyncSchedule) -> Arc<mps
             // Phase 3: Flush operations
             #[cfg(target_os = "linux")]
             {
-                if USE_FD_BACKEND.load(Ordering::Relaxed) {
+                if storage.backend == StorageBackend::Fd {
                     // FD backend: Use io_uring for batched fsync
                     let mut fsync_batch = Vec::new();
 
@@ -164,36 +191,23 @@ pub(super) fn start_background_workers(fsync_schedule: FsyncSchedule) -> Arc<mps
                 }
             }
 
-            // Phase 4: Handle deletion requests
-            while let Ok(path) = del_rx.try_recv() {
-                debug_print!("[reclaim] deletion requested: {}", path);
-                delete_pending.insert(path);
+            // The fsyncs queued before the stop are done; dropping the pool closes its files
+            if maintenance.stopping {
+                break;
             }
 
-            // Phase 5: Periodic cleanup
+            // Phase 4: Periodic cleanup
             let n = tick.fetch_add(1, Ordering::Relaxed) + 1;
-            if n >= 1000 {
-                // WARN: we clean up once every 1000 times the fsync runs
-                if tick
+            if n >= 1000
+                && tick
                     .compare_exchange(n, 0, Ordering::AcqRel, Ordering::Relaxed)
                     .is_ok()
-                {
-                    let mut empty: HashMap<String, StorageImpl> = HashMap::new();
-                    std::mem::swap(&mut pool, &mut empty); // reset map every hour to avoid unconstrained overflow
-
-                    // Perform batched deletions now that mmaps/fds are dropped
-                    for path in delete_pending.drain() {
-                        match fs::remove_file(&path) {
-                            Ok(_) => debug_print!("[reclaim] deleted file {}", path),
-                            Err(e) => {
-                                debug_print!("[reclaim] delete failed for {}: {}", path, e)
-                            }
-                        }
-                    }
-                }
+            {
+                // Reset the pool every 1000 flushes to avoid unconstrained growth
+                pool.clear();
             }
         }
     });
 
-    tx_arc
+    (tx_arc, handle)
 }
@@ -0,0 +1,165 @@
+use crate::wal::config::{DEFAULT_BACKEND, FsyncSchedule, Geometry, MAX_ALLOC, StorageBackend};
+use crate::wal::paths::WalPathManager;
+use crate::wal::storage::StorageOptions;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::retention::RetentionPolicy;
+use super::walrus::{ReadConsistency, Walrus};
+
+/// Builds a [`Walrus`] instance. Every setting is owned by the instance it opens,
+/// so instances in one process can use different directories, backends and fsync
+/// policies.
+///
+/// Block size and blocks per file are written into every new data file's header,
+/// so reopening with different values is safe: existing files keep the layout
+/// they were written with and only new files use the new one.
+#[derive(Clone, Debug)]
+pub struct WalrusBuilder {
+    data_dir: Option<PathBuf>,
+    namespace: Option<String>,
+    backend: StorageBackend,
+    consistency: ReadConsistency,
+    fsync_schedule: FsyncSchedule,
+    block_size: u64,
+    blocks_per_file: u64,
+    max_alloc: Option<u64>,
+    group_commit: bool,
+    retention: Option<RetentionPolicy>,
+    compact_on_open: Option<f64>,
+}
+
+impl Default for WalrusBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WalrusBuilder {
+    pub fn new() -> Self {
+        let geometry = Geometry::default();
+        Self {
+            data_dir: None,
+            namespace: None,
+            backend: DEFAULT_BACKEND,
+            consistency: ReadConsistency::StrictlyAtOnce,
+            fsync_schedule: FsyncSchedule::Milliseconds(200),
+            block_size: geometry.block_size,
+            blocks_per_file: geometry.blocks_per_file,
+            max_alloc: None,
+            group_commit: false,
+            retention: None,
+            compact_on_open: None,
+        }
+    }
+
+    /// Directory holding the instance's files. Defaults to `WALRUS_DATA_DIR`,
+    /// or `./wal_files` when that is unset.
+    pub fn data_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.data_dir = Some(dir.into());
+        self
+    }
+
+    /// Keeps the instance's files in their own subdirectory of the data directory,
+    /// like [`Walrus::new_for_key`]. Defaults to `WALRUS_INSTANCE_KEY` when set.
+    pub fn namespace(mut self, key: &str) -> Self {
+        self.namespace = Some(key.to_string());
+        self
+    }
+
+    /// Storage backend for the data files. Defaults to [`StorageBackend::Fd`]; the
+    /// process-wide [`disable_fd_backend`](crate::wal::disable_fd_backend) switch only
+    /// applies to the legacy constructors on [`Walrus`].
+    pub fn backend(mut self, backend: StorageBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn consistency(mut self, mode: ReadConsistency) -> Self {
+        self.consistency = mode;
+        self
+    }
+
+    /// Defaults to [`FsyncSchedule::Milliseconds(200)`](FsyncSchedule::Milliseconds).
+    pub fn fsync(mut self, schedule: FsyncSchedule) -> Self {
+        self.fsync_schedule = schedule;
+        self
+    }
+
+    /// Size of a regular block in bytes; must be a non-zero multiple of 4096.
+    /// Defaults to 10 MiB.
+    pub fn block_size(mut self, bytes: u64) -> Self {
+        self.block_size = bytes;
+        self
+    }
+
+    /// Number of blocks per data file. Defaults to 100.
+    pub fn blocks_per_file(mut self, blocks: u64) -> Self {
+        self.blocks_per_file = blocks;
+        self
+    }
+
+    /// Largest single entry or batch block that can be allocated. Must lie between
+    /// the block size and the file size; defaults to 1 GiB, capped at the file size.
+    pub fn max_alloc(mut self, bytes: u64) -> Self {
+        self.max_alloc = Some(bytes);
+        self
+    }
+
+    /// Coalesces concurrent single-entry appends, across topics, into one write
+    /// submission and one fsync per file instead of one of each per entry. Mostly
+    /// useful with [`FsyncSchedule::SyncEach`] under many concurrent writers. Off by default.
+    pub fn group_commit(mut self, enabled: bool) -> Self {
+        self.group_commit = enabled;
+        self
+    }
+
+    /// Retention policy of every topic without one of its own, set with
+    /// [`Walrus::set_topic_retention`]. Topics keep everything by default.
+    pub fn retention(mut self, policy: RetentionPolicy) -> Self {
+        self.retention = Some(policy);
+        self
+    }
+
+    /// Compacts existing data files while opening: a file in which unconsumed blocks
+    /// make up at most `max_live_ratio` of the blocks, between 0.0 and 1.0, has those
+    /// copied into new files and is then reclaimed, so one slow topic no longer pins
+    /// files full of consumed blocks. Consumed entries of the topics involved can no
+    /// longer be sought back to. Off by default.
+    pub fn compact_on_open(mut self, max_live_ratio: f64) -> Self {
+        self.compact_on_open = Some(max_live_ratio);
+        self
+    }
+
+    /// Validates the settings and opens the instance, recovering existing files.
+    pub fn open(self) -> std::io::Result<Walrus> {
+        let file_size = self.block_size.saturating_mul(self.blocks_per_file);
+        let geometry = Geometry {
+            block_size: self.block_size,
+            blocks_per_file: self.blocks_per_file,
+            max_alloc: self.max_alloc.unwrap_or(MAX_ALLOC.min(file_size)),
+        };
+        geometry.validate()?;
+        if let Some(ratio) = self.compact_on_open
+            && !(0.0..=1.0).contains(&ratio)
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "compaction live ratio must be between 0.0 and 1.0",
+            ));
+        }
+
+        let paths = WalPathManager::resolve(self.data_dir.as_deref(), self.namespace.as_deref());
+        let storage = StorageOptions::new(self.backend, self.fsync_schedule, self.group_commit);
+        Walrus::with_paths(
+            Arc::new(paths),
+            self.consistency,
+            self.fsync_schedule,
+            geometry,
+            storage,
+            self.group_commit,
+            self.retention,
+            self.compact_on_open,
+        )
+    }
+}
@@ -0,0 +1,253 @@
+use super::Walrus;
+use super::reader::{TAIL_FLAG, split_cursor_key};
+use super::walrus_multi::BatchIntent;
+use super::walrus_seek::SeekTarget;
+use crate::wal::block::Block;
+use crate::wal::config::{PREFIX_META_SIZE, debug_print};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::Path;
+
+// A live block with the newly allocated block it is copied to
+struct MovedBlock {
+    topic: String,
+    original: Block,
+    copy: Block,
+}
+
+impl Walrus {
+    /// Copies the unconsumed blocks out of the recovered data files in which they make
+    /// up at most `max_live_ratio` of the blocks, and returns how many files that
+    /// emptied. Runs at open, before anything is read or appended. A topic with a block
+    /// in one of those files gets all of its unconsumed blocks copied, in chain order,
+    /// into newly allocated blocks; the old files are then left to the reclaimer.
+    ///
+    /// An intent file lists the copies while they are written and then the originals
+    /// while those are zeroed, so recovery finds one or the other but never both.
+    pub(super) fn compact_files(&self, max_live_ratio: f64) -> io::Result<usize> {
+        let chains = self.default_chains()?;
+        let mut live: HashMap<&str, usize> = HashMap::new();
+        for (topic, chain) in &chains {
+            for block in chain.iter().skip(self.reader.consumed_blocks(topic)) {
+                *live.entry(block.file_path.as_str()).or_default() += 1;
+            }
+        }
+
+        // Files without live blocks are left to the reclaimer
+        let tracker = self.allocator.tracker();
+        let candidates: HashSet<String> = tracker
+            .file_paths()
+            .into_iter()
+            .filter(|path| {
+                let (Some(&kept), Some((locked, _, total, fully_allocated))) =
+                    (live.get(path.as_str()), tracker.get_state_snapshot(path))
+                else {
+                    return false;
+                };
+                Path::new(path).parent() == Some(self.paths.root())
+                    && fully_allocated
+                    && locked == 0
+                    && kept < total as usize
+                    && kept as f64 <= max_live_ratio * total as f64
+            })
+            .collect();
+        if candidates.is_empty() {
+            return Ok(0);
+        }
+
+        // Copies land behind every existing block, so a topic moves as a whole
+        let mut moving: Vec<(&String, &[Block])> = Vec::new();
+        for (topic, chain) in &chains {
+            let consumed = self.reader.consumed_blocks(topic).min(chain.len());
+            let blocks = &chain[consumed..];
+            if blocks.iter().any(|b| candidates.contains(&b.file_path)) {
+                moving.push((topic, blocks));
+            }
+        }
+        let vacated: HashSet<&str> = moving
+            .iter()
+            .flat_map(|(_, blocks)| blocks.iter().map(|b| b.file_path.as_str()))
+            .collect();
+
+        // Recovery hands out block ids in file order, which changes once the vacated
+        // files are gone, and finds chains without the consumed blocks they held
+        self.pin_tail_positions(&chains)?;
+        for (topic, chain) in &chains {
+            let consumed = self.reader.consumed_blocks(topic).min(chain.len());
+            if !chain[..consumed]
+                .iter()
+                .any(|b| vacated.contains(b.file_path.as_str()))
+            {
+                continue;
+            }
+            let last = chain[consumed - 1].id;
+            let Some(floor) = self
+                .reader
+                .seek_index
+                .summary(topic, last)
+                .map(|s| s.next_seq)
+            else {
+                continue;
+            };
+            self.truncate_topic_before(topic, SeekTarget::Sequence(floor))?;
+        }
+
+        let mut moved = Vec::new();
+        for (topic, blocks) in moving {
+            for block in blocks {
+                // SAFETY: the copy is only written here, before any reader can see it
+                let mut copy = unsafe { self.allocator.alloc_block(block.used)? };
+                copy.used = block.used;
+                moved.push(MovedBlock {
+                    topic: topic.clone(),
+                    original: block.clone(),
+                    copy,
+                });
+            }
+        }
+        let headers = |pick: fn(&MovedBlock) -> &Block| -> Vec<(String, u64)> {
+            moved
+                .iter()
+                .map(|m| {
+                    let block = pick(m);
+                    (block.file_path.clone(), block.offset)
+                })
+                .collect()
+        };
+
+        let intent = BatchIntent::for_headers(self, &headers(|m| &m.copy))?;
+        let mut buf = Vec::new();
+        for m in &moved {
+            buf.resize(m.original.used as usize, 0);
+            m.original.mmap.read(m.original.offset as usize, &mut buf);
+            m.copy.mmap.write(m.copy.offset as usize, &buf);
+        }
+        flush_files(moved.iter().map(|m| &m.copy))?;
+
+        // From here on recovery keeps the copies
+        intent.replace(&headers(|m| &m.original))?;
+        let manifest = self.allocator.manifest();
+        for path in &vacated {
+            manifest.forget_file(path)?;
+        }
+        for m in &moved {
+            m.original
+                .mmap
+                .write(m.original.offset as usize, &[0u8; PREFIX_META_SIZE]);
+        }
+        flush_files(moved.iter().map(|m| &m.original))?;
+        intent.finish()?;
+
+        self.switch_to_copies(&moved)?;
+        debug_print!(
+            "[compact] moved {} live blocks out of {} files",
+            moved.len(),
+            vacated.len()
+        );
+        Ok(vacated.len())
+    }
+
+    // The chain of every topic, as held by its default-group cursor
+    fn default_chains(&self) -> io::Result<HashMap<String, Vec<Block>>> {
+        let map = self
+            .reader
+            .data
+            .read()
+            .map_err(|_| io::Error::other("reader map read lock poisoned"))?;
+        let mut chains = HashMap::new();
+        for (key, info_arc) in map.iter() {
+            if split_cursor_key(key).0.is_some() {
+                continue;
+            }
+            let info = info_arc
+                .read()
+                .map_err(|_| io::Error::other("col info read lock poisoned"))?;
+            chains.insert(key.clone(), info.chain.clone());
+        }
+        Ok(chains)
+    }
+
+    fn pin_tail_positions(&self, chains: &HashMap<String, Vec<Block>>) -> io::Result<()> {
+        let mut idx = self
+            .read_offset_index
+            .write()
+            .map_err(|_| io::Error::other("read offset index lock poisoned"))?;
+        let mut updates = Vec::new();
+        for key in idx.keys() {
+            let (group, topic) = split_cursor_key(key);
+            let Some(pos) = idx.get(key) else {
+                continue;
+            };
+            if group == Some("") || pos.cur_block_idx & TAIL_FLAG == 0 {
+                continue;
+            }
+            let tail_id = pos.cur_block_idx & !TAIL_FLAG;
+            if let Some(at) = chains
+                .get(topic)
+                .and_then(|chain| chain.iter().position(|b| b.id == tail_id))
+            {
+                updates.push((key.to_string(), at as u64, pos.cur_block_offset));
+            }
+        }
+        if !updates.is_empty() {
+            idx.update(&[], updates)?;
+        }
+        Ok(())
+    }
+
+    // Points chains, summaries and block state at the copies, and checkpoints the
+    // originals so their files get reclaimed
+    fn switch_to_copies(&self, moved: &[MovedBlock]) -> io::Result<()> {
+        let ids: HashMap<u64, u64> = moved.iter().map(|m| (m.original.id, m.copy.id)).collect();
+        let copies: HashMap<u64, &Block> = moved.iter().map(|m| (m.original.id, &m.copy)).collect();
+        let topics: HashSet<&str> = moved.iter().map(|m| m.topic.as_str()).collect();
+
+        let map = self
+            .reader
+            .data
+            .read()
+            .map_err(|_| io::Error::other("reader map read lock poisoned"))?;
+        for (key, info_arc) in map.iter() {
+            if !topics.contains(split_cursor_key(key).1) {
+                continue;
+            }
+            let mut info = info_arc
+                .write()
+                .map_err(|_| io::Error::other("col info write lock poisoned"))?;
+            for block in info.chain.iter_mut() {
+                if let Some(&copy) = copies.get(&block.id) {
+                    *block = copy.clone();
+                }
+            }
+            if let Some(&id) = ids.get(&info.tail_block_id) {
+                info.tail_block_id = id;
+            }
+        }
+        drop(map);
+
+        for topic in &topics {
+            self.reader.seek_index.relocate(topic, &ids);
+        }
+        let tracker = self.allocator.tracker();
+        for m in moved {
+            if let Some(summary) = self.reader.seek_index.summary(&m.topic, m.copy.id) {
+                self.allocator
+                    .manifest()
+                    .record(&m.copy, &m.topic, &summary);
+            }
+            tracker.set_block_unlocked(m.copy.id as usize);
+            tracker.set_checkpointed_true(m.original.id as usize);
+        }
+        Ok(())
+    }
+}
+
+fn flush_files<'a>(blocks: impl Iterator<Item = &'a Block>) -> io::Result<()> {
+    let mut flushed = HashSet::new();
+    for block in blocks {
+        if flushed.insert(block.file_path.as_str()) {
+            block.mmap.flush()?;
+        }
+    }
+    Ok(())
+}
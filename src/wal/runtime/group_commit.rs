@@ -0,0 +1,306 @@
+use super::writer::{StagedRecord, StagedWrites, Writer};
+use crate::wal::config::{FsyncSchedule, MAX_BATCH_ENTRIES, StorageBackend, debug_print};
+use crate::wal::storage::SharedMmap;
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+
+/// Coalesces concurrent appends, to any topic, into one write submission and at most
+/// one fsync per touched file.
+///
+/// There is no background thread: the first appender that finds no group in flight
+/// becomes the leader, writes everything queued so far on behalf of the others and
+/// wakes them with their results. Appenders arriving meanwhile form the next group.
+pub(super) struct GroupCommit {
+    state: Mutex<GroupState>,
+    done: Condvar,
+    // Only the leader writes, so the ring is never contended
+    ring: WriteRing,
+    fsync_schedule: FsyncSchedule,
+}
+
+struct GroupState {
+    queue: Vec<PendingAppend>,
+    leader_active: bool,
+    next_ticket: u64,
+    results: HashMap<u64, std::io::Result<u64>>,
+}
+
+struct PendingAppend {
+    ticket: u64,
+    writer: Arc<Writer>,
+    data: Vec<u8>,
+    headers: Vec<u8>,
+}
+
+impl GroupCommit {
+    pub(super) fn new(backend: StorageBackend, fsync_schedule: FsyncSchedule) -> Self {
+        Self {
+            state: Mutex::new(GroupState {
+                queue: Vec::new(),
+                leader_active: false,
+                next_ticket: 0,
+                results: HashMap::new(),
+            }),
+            done: Condvar::new(),
+            ring: WriteRing::new(backend),
+            fsync_schedule,
+        }
+    }
+
+    /// Queues one record for `writer` and returns its sequence number once the group
+    /// carrying it is written (and fsynced under `FsyncSchedule::SyncEach`).
+    pub(super) fn append(
+        &self,
+        writer: Arc<Writer>,
+        data: &[u8],
+        headers: &[u8],
+    ) -> std::io::Result<u64> {
+        let poisoned = || std::io::Error::other("group commit lock poisoned");
+        let mut state = self.state.lock().map_err(|_| poisoned())?;
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.queue.push(PendingAppend {
+            ticket,
+            writer,
+            data: data.to_vec(),
+            headers: headers.to_vec(),
+        });
+
+        loop {
+            if let Some(result) = state.results.remove(&ticket) {
+                return result;
+            }
+            if !state.leader_active {
+                state.leader_active = true;
+                let take = state.queue.len().min(MAX_BATCH_ENTRIES);
+                let group: Vec<PendingAppend> = state.queue.drain(..take).collect();
+                drop(state);
+
+                let results = self.commit_group(group);
+
+                state = self.state.lock().map_err(|_| poisoned())?;
+                state.leader_active = false;
+                state.results.extend(results);
+                self.done.notify_all();
+                continue;
+            }
+            state = self.done.wait(state).map_err(|_| poisoned())?;
+        }
+    }
+
+    fn commit_group(&self, group: Vec<PendingAppend>) -> Vec<(u64, std::io::Result<u64>)> {
+        // Keep arrival order within each topic
+        let mut by_writer: Vec<(Arc<Writer>, Vec<usize>)> = Vec::new();
+        let mut slots: HashMap<*const Writer, usize> = HashMap::new();
+        for (idx, pending) in group.iter().enumerate() {
+            let slot = *slots
+                .entry(Arc::as_ptr(&pending.writer))
+                .or_insert_with(|| {
+                    by_writer.push((pending.writer.clone(), Vec::new()));
+                    by_writer.len() - 1
+                });
+            by_writer[slot].1.push(idx);
+        }
+        // Writers are locked in address order, like multi-topic batches, so the two
+        // can't deadlock
+        by_writer.sort_by_key(|(writer, _)| Arc::as_ptr(writer));
+
+        let mut results = Vec::with_capacity(group.len());
+        let mut staged: Vec<StagedWrites<'_>> = Vec::with_capacity(by_writer.len());
+        for (writer, indices) in &by_writer {
+            match writer.stage() {
+                Ok(mut writes) => {
+                    for &idx in indices {
+                        let pending = &group[idx];
+                        results
+                            .push((pending.ticket, writes.push(&pending.data, &pending.headers)));
+                    }
+                    staged.push(writes);
+                }
+                Err(e) => {
+                    for &idx in indices {
+                        results.push((group[idx].ticket, Err(copy_error(&e))));
+                    }
+                }
+            }
+        }
+
+        let sync = matches!(self.fsync_schedule, FsyncSchedule::SyncEach);
+        let records: Vec<&StagedRecord> = staged.iter().flat_map(|s| s.records()).collect();
+        let outcome = self.ring.write(&records).and_then(|()| {
+            let mut files: HashMap<&str, &Arc<SharedMmap>> = HashMap::new();
+            for writes in &staged {
+                for sealed in writes.sealed() {
+                    files.insert(&sealed.file_path, &sealed.mmap);
+                }
+            }
+            if sync {
+                for record in &records {
+                    files.insert(&record.block.file_path, &record.block.mmap);
+                }
+            }
+            for mmap in files.values() {
+                mmap.flush()?;
+            }
+            Ok(())
+        });
+        debug_print!(
+            "[group] wrote {} records for {} topics, ok={}",
+            records.len(),
+            staged.len(),
+            outcome.is_ok()
+        );
+        drop(records);
+
+        match outcome {
+            Ok(()) => {
+                for writes in staged {
+                    writes.commit(sync);
+                }
+            }
+            Err(e) => {
+                for writes in staged {
+                    writes.abort();
+                }
+                for (_, result) in results.iter_mut() {
+                    if result.is_ok() {
+                        *result = Err(copy_error(&e));
+                    }
+                }
+            }
+        }
+        results
+    }
+}
+
+/// Entries in a [`WriteRing`]'s submission queue; larger writes go in several rounds.
+#[cfg(target_os = "linux")]
+const RING_ENTRIES: u32 = 256;
+
+/// The io_uring an instance writes staged records through on the FD backend, set up
+/// on first use and kept for the life of the instance.
+pub(super) struct WriteRing {
+    backend: StorageBackend,
+    #[cfg(target_os = "linux")]
+    ring: Mutex<RingState>,
+}
+
+#[cfg(target_os = "linux")]
+enum RingState {
+    Unopened,
+    Open(Box<io_uring::IoUring>),
+    Unavailable,
+}
+
+impl WriteRing {
+    pub(super) fn new(backend: StorageBackend) -> Self {
+        Self {
+            backend,
+            #[cfg(target_os = "linux")]
+            ring: Mutex::new(RingState::Unopened),
+        }
+    }
+
+    /// Writes staged records in io_uring submissions on the FD backend, or one by one
+    /// through the mmap otherwise. A caller that finds the ring in use by another
+    /// batch writes one by one rather than waiting for it.
+    pub(super) fn write(&self, records: &[&StagedRecord]) -> std::io::Result<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        #[cfg(target_os = "linux")]
+        if self.backend == StorageBackend::Fd
+            && let Ok(mut state) = self.ring.try_lock()
+        {
+            if let RingState::Unopened = *state {
+                *state = match io_uring::IoUring::new(RING_ENTRIES) {
+                    Ok(ring) => RingState::Open(Box::new(ring)),
+                    Err(e) => {
+                        debug_print!("[group] io_uring unavailable; falling back: {}", e);
+                        RingState::Unavailable
+                    }
+                };
+            }
+            if let RingState::Open(ring) = &mut *state {
+                let outcome = records
+                    .chunks(RING_ENTRIES as usize)
+                    .try_for_each(|chunk| submit_via_io_uring(ring, chunk));
+                if outcome.is_err() {
+                    // A failed round can leave entries queued; the next write opens a new ring
+                    *state = RingState::Unopened;
+                }
+                return outcome;
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = self.backend;
+        for record in records {
+            let file_offset = record.block.offset + record.offset;
+            record.block.mmap.write(file_offset as usize, &record.bytes);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn submit_via_io_uring(
+    ring: &mut io_uring::IoUring,
+    records: &[&StagedRecord],
+) -> std::io::Result<()> {
+    for (idx, record) in records.iter().enumerate() {
+        let Some(fd_backend) = record.block.mmap.storage().as_fd() else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "io_uring group commit requires FD backend",
+            ));
+        };
+        let fd = io_uring::types::Fd(fd_backend.file().as_raw_fd());
+        let write_op =
+            io_uring::opcode::Write::new(fd, record.bytes.as_ptr(), record.bytes.len() as u32)
+                .offset(record.block.offset + record.offset)
+                .build()
+                .user_data(idx as u64);
+        // SAFETY: `record.bytes` outlives the submission; we wait for every completion
+        // below before returning.
+        unsafe {
+            ring.submission()
+                .push(&write_op)
+                .map_err(|e| std::io::Error::other(format!("io_uring push failed: {}", e)))?;
+        }
+    }
+
+    ring.submit_and_wait(records.len())?;
+    let mut completed = 0;
+    for cqe in ring.completion() {
+        completed += 1;
+        let expected = records
+            .get(cqe.user_data() as usize)
+            .map(|r| r.bytes.len())
+            .unwrap_or(0);
+        let result = cqe.result();
+        if result < 0 {
+            return Err(std::io::Error::from_raw_os_error(-result));
+        }
+        if result as usize != expected {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::WriteZero,
+                format!("short write: wrote {} bytes, expected {}", result, expected),
+            ));
+        }
+    }
+    if completed != records.len() {
+        return Err(std::io::Error::other(format!(
+            "io_uring returned {} of {} completions",
+            completed,
+            records.len()
+        )));
+    }
+    Ok(())
+}
+
+fn copy_error(e: &std::io::Error) -> std::io::Error {
+    std::io::Error::new(e.kind(), e.to_string())
+}
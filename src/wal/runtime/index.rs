@@ -1,7 +1,20 @@
+use crate::wal::config::checksum64;
 use crate::wal::paths::WalPathManager;
 use rkyv::{Archive, Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+// Start of an index file in the log format; older files are a single rkyv map
+const LOG_MAGIC: &[u8; 8] = b"WALIDX01";
+// Record framing: payload length, then the checksum of the payload
+const RECORD_HEADER: usize = 4 + 8;
+const OP_SET: u8 = 1;
+const OP_REMOVE: u8 = 2;
+// The file is rewritten once it holds this many records, or more per live key
+const COMPACT_MIN_RECORDS: usize = 1024;
+const COMPACT_RECORDS_PER_KEY: usize = 4;
 
 #[derive(Archive, Deserialize, Serialize, Debug, Clone)]
 pub struct BlockPos {
@@ -9,9 +22,21 @@ pub struct BlockPos {
     pub cur_block_offset: u64,
 }
 
+/// Persisted positions by key, kept in an append-only file.
+///
+/// Every change appends one checksummed record and syncs it, so a checkpoint costs
+/// a small write whatever the number of keys. A record covers all the keys of one
+/// call and is replayed whole or not at all; a torn record at the end of the file
+/// is ignored. Once records pile up, the file is rewritten as a single record of
+/// the live positions and swapped in with a rename.
 pub struct WalIndex {
     store: HashMap<String, BlockPos>,
     path: String,
+    // Appends go here; None until the file is rewritten in the log format, or after
+    // an append failed part way
+    log: Option<File>,
+    // Records in the file, the rewritten snapshot included
+    records: usize,
 }
 
 impl WalIndex {
@@ -23,62 +48,252 @@ impl WalIndex {
     pub(super) fn new_in(paths: &WalPathManager, file_name: &str) -> std::io::Result<Self> {
         paths.ensure_root()?;
         let path = paths.index_path(file_name);
-        let store = path
-            .exists()
-            .then(|| fs::read(&path).ok())
-            .flatten()
-            .and_then(|bytes| {
-                if bytes.is_empty() {
-                    return None;
-                }
-                // SAFETY: `bytes` comes from our persisted index file which we control;
-                // we only proceed when the file is non-empty and rkyv can interpret it.
-                let archived = unsafe { rkyv::archived_root::<HashMap<String, BlockPos>>(&bytes) };
-                archived.deserialize(&mut rkyv::Infallible).ok()
-            })
-            .unwrap_or_default();
+        let bytes = if path.exists() {
+            fs::read(&path)?
+        } else {
+            Vec::new()
+        };
+
+        let mut store = HashMap::new();
+        let mut records = 0;
+        let mut log = None;
+        if let Some(body) = bytes.strip_prefix(LOG_MAGIC.as_slice()) {
+            let valid = replay(body, &mut store, &mut records);
+            // Appending behind a torn record would hide everything after it
+            if valid == body.len() {
+                log = Some(OpenOptions::new().append(true).open(&path)?);
+            }
+        } else if !bytes.is_empty() {
+            // SAFETY: `bytes` comes from our persisted index file which we control;
+            // we only proceed when the file is non-empty and rkyv can interpret it.
+            let archived = unsafe { rkyv::archived_root::<HashMap<String, BlockPos>>(&bytes) };
+            store = archived
+                .deserialize(&mut rkyv::Infallible)
+                .unwrap_or_default();
+        }
 
         Ok(Self {
             store,
             path: path.to_string_lossy().into_owned(),
+            log,
+            records,
         })
     }
 
     pub fn set(&mut self, key: String, idx: u64, offset: u64) -> std::io::Result<()> {
-        self.store.insert(
-            key,
-            BlockPos {
-                cur_block_idx: idx,
-                cur_block_offset: offset,
-            },
-        );
-        self.persist()
+        let pos = BlockPos {
+            cur_block_idx: idx,
+            cur_block_offset: offset,
+        };
+        let mut payload = Vec::new();
+        encode_set(&mut payload, &key, &pos);
+        self.store.insert(key, pos);
+        self.append(&payload)
     }
 
     pub fn get(&self, key: &str) -> Option<&BlockPos> {
         self.store.get(key)
     }
 
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.store.keys().map(String::as_str)
+    }
+
     pub fn remove(&mut self, key: &str) -> std::io::Result<Option<BlockPos>> {
         let result = self.store.remove(key);
         if result.is_some() {
-            self.persist()?;
+            let mut payload = Vec::new();
+            encode_remove(&mut payload, key);
+            self.append(&payload)?;
         }
         Ok(result)
     }
 
-    fn persist(&self) -> std::io::Result<()> {
+    /// Removes `removals` and then sets `updates` as `(key, idx, offset)`, with a single
+    /// record in the index file so a crash sees either all of it or none.
+    pub fn update(
+        &mut self,
+        removals: &[String],
+        updates: Vec<(String, u64, u64)>,
+    ) -> std::io::Result<()> {
+        let mut payload = Vec::new();
+        for key in removals {
+            self.store.remove(key);
+            encode_remove(&mut payload, key);
+        }
+        for (key, idx, offset) in updates {
+            let pos = BlockPos {
+                cur_block_idx: idx,
+                cur_block_offset: offset,
+            };
+            encode_set(&mut payload, &key, &pos);
+            self.store.insert(key, pos);
+        }
+        self.append(&payload)
+    }
+
+    /// Rewrites the index file from the positions in memory, e.g. after a failed
+    /// `set` whose error the caller dropped.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.compact()
+    }
+
+    fn append(&mut self, payload: &[u8]) -> std::io::Result<()> {
+        let limit = COMPACT_MIN_RECORDS.max(self.store.len() * COMPACT_RECORDS_PER_KEY);
+        let Some(log) = self.log.as_mut().filter(|_| self.records < limit) else {
+            // The rewrite carries the change just made in memory
+            return self.compact();
+        };
+        let result = log.write_all(&frame(payload)).and_then(|_| log.sync_data());
+        match result {
+            Ok(()) => self.records += 1,
+            // The next change rewrites the file rather than append after a partial record
+            Err(_) => self.log = None,
+        }
+        result
+    }
+
+    fn compact(&mut self) -> std::io::Result<()> {
+        self.log = None;
+        let mut payload = Vec::new();
+        for (key, pos) in &self.store {
+            encode_set(&mut payload, key, pos);
+        }
+        let mut bytes = LOG_MAGIC.to_vec();
+        bytes.extend_from_slice(&frame(&payload));
+
         let tmp_path = format!("{}.tmp", self.path);
-        let bytes = rkyv::to_bytes::<_, 256>(&self.store).map_err(|e| {
-            std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("index serialize failed: {:?}", e),
-            )
-        })?;
-
-        fs::write(&tmp_path, &bytes)?;
-        fs::File::open(&tmp_path)?.sync_all()?;
-        fs::rename(&tmp_path, &self.path)?;
+        replace_file(Path::new(&tmp_path), Path::new(&self.path), &bytes)?;
+        self.log = Some(OpenOptions::new().append(true).open(&self.path)?);
+        self.records = 1;
         Ok(())
     }
 }
+
+/// Writes `bytes` to `tmp_path` and renames it over `path`, syncing the file and then
+/// its directory so that a crash leaves either the old or the new contents.
+pub(super) fn replace_file(tmp_path: &Path, path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    fs::write(tmp_path, bytes)?;
+    File::open(tmp_path)?.sync_all()?;
+    fs::rename(tmp_path, path)?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+/// Frames `payload` as one record: its length, then its checksum.
+pub(super) fn frame(payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(RECORD_HEADER + payload.len());
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(&checksum64(payload).to_le_bytes());
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Payloads of the records framed in `body`, and the length of the valid prefix; the
+/// scan stops at the first record that is cut short or fails its checksum.
+pub(super) fn unframe(body: &[u8]) -> (Vec<&[u8]>, usize) {
+    let mut payloads = Vec::new();
+    let mut at = 0;
+    while body.len() - at >= RECORD_HEADER {
+        let len = u32::from_le_bytes(body[at..at + 4].try_into().unwrap()) as usize;
+        let checksum = u64::from_le_bytes(body[at + 4..at + RECORD_HEADER].try_into().unwrap());
+        let start = at + RECORD_HEADER;
+        let Some(payload) = body.get(start..start + len) else {
+            break;
+        };
+        if checksum64(payload) != checksum {
+            break;
+        }
+        payloads.push(payload);
+        at = start + len;
+    }
+    (payloads, at)
+}
+
+// Reads the little-endian fields of a framed payload off its front
+pub(super) struct Fields<'a>(pub(super) &'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let (head, rest) = self.0.split_at_checked(n)?;
+        self.0 = rest;
+        Some(head)
+    }
+
+    pub(super) fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    pub(super) fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    pub(super) fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    pub(super) fn str(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+}
+
+fn encode_key(out: &mut Vec<u8>, op: u8, key: &str) {
+    out.push(op);
+    out.extend_from_slice(&(key.len() as u32).to_le_bytes());
+    out.extend_from_slice(key.as_bytes());
+}
+
+fn encode_set(out: &mut Vec<u8>, key: &str, pos: &BlockPos) {
+    encode_key(out, OP_SET, key);
+    out.extend_from_slice(&pos.cur_block_idx.to_le_bytes());
+    out.extend_from_slice(&pos.cur_block_offset.to_le_bytes());
+}
+
+fn encode_remove(out: &mut Vec<u8>, key: &str) {
+    encode_key(out, OP_REMOVE, key);
+}
+
+/// Applies the records of `body` in order, and returns the length of the valid
+/// prefix; replay stops at the first record that cannot be applied.
+fn replay(body: &[u8], store: &mut HashMap<String, BlockPos>, records: &mut usize) -> usize {
+    let (payloads, mut valid) = unframe(body);
+    let mut at = 0;
+    for payload in payloads {
+        let Some(ops) = decode_ops(payload) else {
+            valid = at;
+            break;
+        };
+        for (key, pos) in ops {
+            match pos {
+                Some(pos) => store.insert(key, pos),
+                None => store.remove(&key),
+            };
+        }
+        *records += 1;
+        at += RECORD_HEADER + payload.len();
+    }
+    valid
+}
+
+// The changes of one record; a None position removes the key
+fn decode_ops(payload: &[u8]) -> Option<Vec<(String, Option<BlockPos>)>> {
+    let mut fields = Fields(payload);
+    let mut ops = Vec::new();
+    while let Some(op) = fields.u8() {
+        let key = fields.str()?;
+        let pos = match op {
+            OP_SET => Some(BlockPos {
+                cur_block_idx: fields.u64()?,
+                cur_block_offset: fields.u64()?,
+            }),
+            OP_REMOVE => None,
+            _ => return None,
+        };
+        ops.push((key, pos));
+    }
+    Some(ops)
+}
@@ -0,0 +1,292 @@
+use crate::wal::config::debug_print;
+use crate::wal::storage::SharedMmap;
+use std::collections::HashMap;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::{Arc, mpsc};
+use std::thread::JoinHandle;
+
+// Ring size; stages larger than this are submitted in several rounds
+const RING_ENTRIES: u32 = 256;
+// user_data of the eventfd read that wakes the driver for new jobs
+const WAKE_TOKEN: u64 = u64::MAX;
+// user_data is the job id shifted past the op index
+const OP_INDEX_BITS: u32 = 24;
+
+/// One io_uring operation. It owns the file handle and the buffer until the kernel
+/// completes it.
+pub(super) enum IoOp {
+    Write {
+        mmap: Arc<SharedMmap>,
+        offset: u64,
+        bytes: Vec<u8>,
+    },
+    Fsync {
+        mmap: Arc<SharedMmap>,
+    },
+}
+
+/// Operations submitted in stages: every op of a stage completes before the next stage
+/// is submitted and the first failure ends the job. `done` runs on the driver thread.
+pub(super) struct IoJob {
+    pub(super) stages: Vec<Vec<IoOp>>,
+    pub(super) done: Box<dyn FnOnce(std::io::Result<()>) + Send>,
+}
+
+/// A thread that owns an io_uring instance and completes jobs as their CQEs arrive, so
+/// async callers wait on a channel instead of parking a thread per operation.
+pub(super) struct IoDriver {
+    jobs: Option<mpsc::Sender<IoJob>>,
+    wake: Arc<OwnedFd>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl IoDriver {
+    pub(super) fn start() -> std::io::Result<Self> {
+        let ring = io_uring::IoUring::new(RING_ENTRIES)?;
+        // SAFETY: eventfd has no memory preconditions; the result is checked below.
+        let raw = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+        if raw < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        // SAFETY: `raw` is a freshly created descriptor owned by nothing else.
+        let wake = Arc::new(unsafe { OwnedFd::from_raw_fd(raw) });
+        let (tx, rx) = mpsc::channel();
+        let thread_wake = wake.clone();
+        let thread = std::thread::Builder::new()
+            .name("walrus-io-driver".to_string())
+            .spawn(move || run(ring, rx, thread_wake))?;
+        Ok(Self {
+            jobs: Some(tx),
+            wake,
+            thread: Some(thread),
+        })
+    }
+
+    pub(super) fn submit(&self, job: IoJob) {
+        let sent = match &self.jobs {
+            Some(jobs) => jobs.send(job).map_err(|e| e.0),
+            None => Err(job),
+        };
+        match sent {
+            Ok(()) => self.notify(),
+            Err(job) => (job.done)(Err(std::io::Error::other("io driver stopped"))),
+        }
+    }
+
+    fn notify(&self) {
+        let one: u64 = 1;
+        // SAFETY: writes 8 bytes from a live u64 to an eventfd we own.
+        unsafe {
+            libc::write(
+                self.wake.as_raw_fd(),
+                &one as *const u64 as *const libc::c_void,
+                8,
+            );
+        }
+    }
+}
+
+impl Drop for IoDriver {
+    fn drop(&mut self) {
+        // The driver finishes the jobs in flight, then exits once it sees the channel closed
+        self.jobs.take();
+        self.notify();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct InFlight {
+    // Later stages, in reverse so the next one pops off the end
+    remaining: Vec<Vec<IoOp>>,
+    current: Vec<IoOp>,
+    pending: usize,
+    error: Option<std::io::Error>,
+    done: Box<dyn FnOnce(std::io::Result<()>) + Send>,
+}
+
+fn run(ring: io_uring::IoUring, rx: mpsc::Receiver<IoJob>, wake: Arc<OwnedFd>) {
+    // Declared before the ring so it outlives a wakeup read still armed at exit
+    let mut wake_buf = [0u8; 8];
+    let mut ring = ring;
+    let mut jobs: HashMap<u64, InFlight> = HashMap::new();
+    let mut next_id: u64 = 0;
+    let mut closed = false;
+
+    if let Err(e) = arm_wake(&mut ring, &wake, &mut wake_buf) {
+        debug_print!("[io-driver] failed to arm wakeup: {}", e);
+        fail_pending(&rx, &e);
+        return;
+    }
+
+    loop {
+        while !closed {
+            match rx.try_recv() {
+                Ok(job) => {
+                    let id = next_id;
+                    next_id = (next_id + 1) % (WAKE_TOKEN >> OP_INDEX_BITS);
+                    let mut stages = job.stages;
+                    stages.reverse();
+                    let mut flight = InFlight {
+                        remaining: stages,
+                        current: Vec::new(),
+                        pending: 0,
+                        error: None,
+                        done: job.done,
+                    };
+                    if advance(&mut ring, id, &mut flight) {
+                        (flight.done)(flight.error.map_or(Ok(()), Err));
+                    } else {
+                        jobs.insert(id, flight);
+                    }
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => closed = true,
+            }
+        }
+        if closed && jobs.is_empty() {
+            return;
+        }
+
+        if let Err(e) = ring.submit_and_wait(1) {
+            if e.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            debug_print!("[io-driver] submit failed: {}", e);
+            for (_, flight) in jobs.drain() {
+                (flight.done)(Err(std::io::Error::new(e.kind(), e.to_string())));
+            }
+            fail_pending(&rx, &e);
+            return;
+        }
+
+        let completions: Vec<(u64, i32)> = ring
+            .completion()
+            .map(|cqe| (cqe.user_data(), cqe.result()))
+            .collect();
+        for (user_data, result) in completions {
+            if user_data == WAKE_TOKEN {
+                if !closed && let Err(e) = arm_wake(&mut ring, &wake, &mut wake_buf) {
+                    debug_print!("[io-driver] failed to re-arm wakeup: {}", e);
+                }
+                continue;
+            }
+            let id = user_data >> OP_INDEX_BITS;
+            let idx = (user_data & ((1 << OP_INDEX_BITS) - 1)) as usize;
+            let Some(flight) = jobs.get_mut(&id) else {
+                continue;
+            };
+            flight.pending -= 1;
+            if flight.error.is_none() {
+                flight.error = check_completion(&flight.current[idx], result).err();
+            }
+            if flight.pending > 0 {
+                continue;
+            }
+            let finished = flight.error.is_some() || advance(&mut ring, id, flight);
+            if finished && let Some(flight) = jobs.remove(&id) {
+                (flight.done)(flight.error.map_or(Ok(()), Err));
+            }
+        }
+    }
+}
+
+/// Submits the job's next non-empty stage. Returns true once no stage is left or the
+/// stage could not be submitted.
+fn advance(ring: &mut io_uring::IoUring, id: u64, flight: &mut InFlight) -> bool {
+    while let Some(stage) = flight.remaining.pop() {
+        if stage.is_empty() {
+            continue;
+        }
+        flight.current = stage;
+        for (idx, op) in flight.current.iter().enumerate() {
+            let entry = match prepare(op) {
+                Ok(entry) => entry.user_data((id << OP_INDEX_BITS) | idx as u64),
+                Err(e) => {
+                    // Ops already queued still complete and are counted in `pending`
+                    flight.error = Some(e);
+                    return flight.pending == 0;
+                }
+            };
+            if let Err(e) = push(ring, &entry) {
+                flight.error = Some(e);
+                return flight.pending == 0;
+            }
+            flight.pending += 1;
+        }
+        return false;
+    }
+    true
+}
+
+fn prepare(op: &IoOp) -> std::io::Result<io_uring::squeue::Entry> {
+    let (IoOp::Write { mmap, .. } | IoOp::Fsync { mmap }) = op;
+    let Some(fd_backend) = mmap.storage().as_fd() else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "async io requires FD backend",
+        ));
+    };
+    let fd = io_uring::types::Fd(fd_backend.file().as_raw_fd());
+    Ok(match op {
+        IoOp::Write { offset, bytes, .. } => {
+            io_uring::opcode::Write::new(fd, bytes.as_ptr(), bytes.len() as u32)
+                .offset(*offset)
+                .build()
+        }
+        IoOp::Fsync { .. } => io_uring::opcode::Fsync::new(fd).build(),
+    })
+}
+
+fn push(ring: &mut io_uring::IoUring, entry: &io_uring::squeue::Entry) -> std::io::Result<()> {
+    loop {
+        // SAFETY: the buffer and descriptor an entry points to are owned by the job,
+        // which stays in the driver's table until all of its completions arrive.
+        if unsafe { ring.submission().push(entry) }.is_ok() {
+            return Ok(());
+        }
+        // Queue full: hand what is queued to the kernel to make room
+        ring.submit()?;
+    }
+}
+
+fn arm_wake(
+    ring: &mut io_uring::IoUring,
+    wake: &OwnedFd,
+    buf: &mut [u8; 8],
+) -> std::io::Result<()> {
+    let entry = io_uring::opcode::Read::new(
+        io_uring::types::Fd(wake.as_raw_fd()),
+        buf.as_mut_ptr(),
+        buf.len() as u32,
+    )
+    .build()
+    .user_data(WAKE_TOKEN);
+    push(ring, &entry)
+}
+
+fn check_completion(op: &IoOp, result: i32) -> std::io::Result<()> {
+    if result < 0 {
+        return Err(std::io::Error::from_raw_os_error(-result));
+    }
+    if let IoOp::Write { bytes, .. } = op
+        && result as usize != bytes.len()
+    {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::WriteZero,
+            format!(
+                "short write: wrote {} bytes, expected {}",
+                result,
+                bytes.len()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn fail_pending(rx: &mpsc::Receiver<IoJob>, e: &std::io::Error) {
+    while let Ok(job) = rx.try_recv() {
+        (job.done)(Err(std::io::Error::new(e.kind(), e.to_string())));
+    }
+}
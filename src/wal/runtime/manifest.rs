@@ -0,0 +1,299 @@
+use super::index::{Fields, frame, replace_file, unframe};
+use super::seek_index::{BlockSummary, SeekPoint};
+use crate::wal::block::{Block, Metadata, TxnMark};
+use crate::wal::config::{PREFIX_META_SIZE, debug_print};
+use crate::wal::paths::WalPathManager;
+use crate::wal::storage::SharedMmap;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Start of a manifest file
+const MANIFEST_MAGIC: &[u8; 8] = b"WALMAN01";
+const OP_BLOCK: u8 = 1;
+const OP_FORGET_FILE: u8 = 2;
+
+/// What recovery needs of a sealed block without reading its records.
+#[derive(Clone, Debug)]
+pub(super) struct SealedBlock {
+    pub(super) topic: String,
+    pub(super) used: u64,
+    // Recovery numbers blocks anew, so the summary's block id is not kept
+    pub(super) summary: BlockSummary,
+}
+
+impl SealedBlock {
+    /// Cheap check that the block at `offset` is still the one recorded: it starts with
+    /// a record of the same topic and nothing was written past the recorded end.
+    pub(super) fn fits(&self, mmap: &SharedMmap, offset: u64, block_size: u64) -> bool {
+        if self.used == 0 || self.used > block_size {
+            return false;
+        }
+        let mut meta_buf = vec![0u8; PREFIX_META_SIZE];
+        mmap.read(offset as usize, &mut meta_buf);
+        if !Metadata::decode_prefix(&meta_buf).is_ok_and(|md| md.owned_by == self.topic) {
+            return false;
+        }
+        if self.used + PREFIX_META_SIZE as u64 > block_size {
+            return true;
+        }
+        let mut next_len = [0u8; 1];
+        mmap.read((offset + self.used) as usize, &mut next_len);
+        next_len[0] == 0
+    }
+}
+
+/// Sealed blocks by data file name and block offset, kept in an append-only file so
+/// that recovery only has to read the records of blocks missing from it.
+///
+/// A block is recorded once it is sealed and flushed, and the blocks writers still
+/// hold are recorded on clean shutdown. Records are framed like the read offset
+/// index, unsynced except for those forgetting a file: losing the last ones only
+/// costs a scan. Recovery rewrites the file with the blocks it found, which drops
+/// the blocks of reclaimed files.
+pub(super) struct BlockManifest {
+    path: PathBuf,
+    // Named like an index file so that recovery never takes a leftover for a data file
+    tmp_path: PathBuf,
+    // Appends go here; None when the file is missing or torn, or an append failed
+    log: Mutex<Option<File>>,
+}
+
+impl BlockManifest {
+    pub(super) fn open(paths: &WalPathManager) -> io::Result<Self> {
+        paths.ensure_root()?;
+        let path = paths.index_path("block_manifest");
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        // Appending behind a torn record would hide everything after it
+        let log = match bytes.strip_prefix(MANIFEST_MAGIC.as_slice()) {
+            Some(body) if unframe(body).1 == body.len() => {
+                Some(OpenOptions::new().append(true).open(&path)?)
+            }
+            _ => None,
+        };
+        Ok(Self {
+            path,
+            tmp_path: paths.index_path("block_manifest_tmp"),
+            log: Mutex::new(log),
+        })
+    }
+
+    /// Blocks recorded so far, by data file name and block offset.
+    pub(super) fn load(&self) -> HashMap<(String, u64), SealedBlock> {
+        let mut blocks = HashMap::new();
+        let Ok(bytes) = fs::read(&self.path) else {
+            return blocks;
+        };
+        let Some(body) = bytes.strip_prefix(MANIFEST_MAGIC.as_slice()) else {
+            return blocks;
+        };
+        for payload in unframe(body).0 {
+            match decode(payload) {
+                Some(Op::Block(file_name, offset, block)) => {
+                    blocks.insert((file_name, offset), block);
+                }
+                Some(Op::ForgetFile(file_name)) => blocks.retain(|(f, _), _| *f != file_name),
+                None => break,
+            }
+        }
+        blocks
+    }
+
+    /// Records a sealed block of `topic` with the summary of its entries. Failures are
+    /// only logged: recovery scans the blocks it does not find here.
+    pub(super) fn record(&self, block: &Block, topic: &str, summary: &BlockSummary) {
+        let mut payload = Vec::new();
+        encode_block(
+            &mut payload,
+            file_name(&block.file_path),
+            block.offset,
+            topic,
+            block.used,
+            summary,
+        );
+        let Ok(mut log) = self.log.lock() else {
+            return;
+        };
+        let Some(file) = log.as_mut() else {
+            return;
+        };
+        if let Err(e) = file.write_all(&frame(&payload)) {
+            debug_print!("[manifest] append failed: {}", e);
+            *log = None;
+        }
+    }
+
+    /// Drops the blocks of a data file about to be rewritten in place. Synced before it
+    /// returns; when that fails, the whole manifest is removed instead.
+    pub(super) fn forget_file(&self, file_path: &str) -> io::Result<()> {
+        let mut payload = vec![OP_FORGET_FILE];
+        put_str(&mut payload, file_name(file_path));
+        let mut log = self
+            .log
+            .lock()
+            .map_err(|_| io::Error::other("manifest lock poisoned"))?;
+        if let Some(file) = log.as_mut() {
+            let result = file
+                .write_all(&frame(&payload))
+                .and_then(|_| file.sync_data());
+            if result.is_ok() {
+                return Ok(());
+            }
+            *log = None;
+        }
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Rewrites the file with exactly `blocks`, as `(file name, offset, block)`.
+    pub(super) fn reset(&self, blocks: &[(String, u64, SealedBlock)]) -> io::Result<()> {
+        let mut log = self
+            .log
+            .lock()
+            .map_err(|_| io::Error::other("manifest lock poisoned"))?;
+        *log = None;
+        let mut bytes = MANIFEST_MAGIC.to_vec();
+        let mut payload = Vec::new();
+        for (file_name, offset, block) in blocks {
+            payload.clear();
+            encode_block(
+                &mut payload,
+                file_name,
+                *offset,
+                &block.topic,
+                block.used,
+                &block.summary,
+            );
+            bytes.extend_from_slice(&frame(&payload));
+        }
+
+        replace_file(&self.tmp_path, &self.path, &bytes)?;
+        *log = Some(OpenOptions::new().append(true).open(&self.path)?);
+        Ok(())
+    }
+
+    /// Syncs the records appended so far.
+    pub(super) fn sync(&self) -> io::Result<()> {
+        let log = self
+            .log
+            .lock()
+            .map_err(|_| io::Error::other("manifest lock poisoned"))?;
+        match log.as_ref() {
+            Some(file) => file.sync_data(),
+            None => Ok(()),
+        }
+    }
+}
+
+fn file_name(file_path: &str) -> &str {
+    Path::new(file_path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(file_path)
+}
+
+enum Op {
+    Block(String, u64, SealedBlock),
+    ForgetFile(String),
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn encode_block(
+    out: &mut Vec<u8>,
+    file_name: &str,
+    offset: u64,
+    topic: &str,
+    used: u64,
+    summary: &BlockSummary,
+) {
+    out.push(OP_BLOCK);
+    put_str(out, file_name);
+    out.extend_from_slice(&offset.to_le_bytes());
+    put_str(out, topic);
+    for value in [
+        used,
+        summary.first_seq,
+        summary.next_seq,
+        summary.first_ts,
+        summary.last_ts,
+    ] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.extend_from_slice(&(summary.points.len() as u32).to_le_bytes());
+    for point in &summary.points {
+        for value in [point.seq, point.offset, point.timestamp_ms] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    out.extend_from_slice(&(summary.txns.len() as u32).to_le_bytes());
+    for txn in &summary.txns {
+        let (id, kind) = txn.encode();
+        out.push(kind);
+        out.extend_from_slice(&id.to_le_bytes());
+    }
+}
+
+fn decode(payload: &[u8]) -> Option<Op> {
+    let mut fields = Fields(payload);
+    let op = fields.u8()?;
+    let file_name = fields.str()?;
+    match op {
+        OP_FORGET_FILE => return Some(Op::ForgetFile(file_name)),
+        OP_BLOCK => {}
+        _ => return None,
+    }
+    let offset = fields.u64()?;
+    let topic = fields.str()?;
+    let used = fields.u64()?;
+    let (first_seq, next_seq) = (fields.u64()?, fields.u64()?);
+    let (first_ts, last_ts) = (fields.u64()?, fields.u64()?);
+    let points = (0..fields.u32()?)
+        .map(|_| {
+            Some(SeekPoint {
+                seq: fields.u64()?,
+                offset: fields.u64()?,
+                timestamp_ms: fields.u64()?,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let txns = (0..fields.u32()?)
+        .map(|_| {
+            let kind = fields.u8()?;
+            Some(TxnMark::decode(fields.u64()?, kind))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    // A block without entries or seek points is never recorded
+    if next_seq <= first_seq || points.is_empty() {
+        return None;
+    }
+    let summary = BlockSummary {
+        block_id: 0,
+        first_seq,
+        next_seq,
+        first_ts,
+        last_ts,
+        points,
+        txns,
+    };
+    Some(Op::Block(
+        file_name,
+        offset,
+        SealedBlock {
+            topic,
+            used,
+            summary,
+        },
+    ))
+}
@@ -1,18 +1,44 @@
-use std::sync::mpsc;
-use std::sync::{Arc, OnceLock};
-
 mod allocator;
+#[cfg(feature = "async")]
+mod async_walrus;
 mod background;
+mod builder;
+mod compaction;
+mod group_commit;
 mod index;
+#[cfg(all(feature = "async", target_os = "linux"))]
+mod io_driver;
+mod manifest;
 mod reader;
+mod reclaim;
+mod recovery;
+mod retention;
+mod seek_index;
+mod subscription;
 mod topic_clean;
+mod topic_info;
+mod topic_signal;
+mod transaction;
 mod walrus;
+mod walrus_close;
+mod walrus_commit;
+mod walrus_multi;
 mod walrus_read;
+mod walrus_seek;
+mod walrus_truncate;
 mod walrus_write;
 mod writer;
 
 #[allow(unused_imports)]
 pub use index::{BlockPos, WalIndex};
+#[cfg(feature = "async")]
+pub use async_walrus::{AsyncSubscription, AsyncWalrus};
+pub use builder::WalrusBuilder;
+pub use reclaim::ReclaimStats;
+pub use retention::RetentionPolicy;
+pub use subscription::{CheckpointPolicy, SubscribeOptions, Subscription};
+pub use topic_info::{BlockInfo, ReaderPosition, TopicInfo};
+pub use transaction::Transaction;
 pub use walrus::{ReadConsistency, Walrus};
-
-pub(super) static DELETION_TX: OnceLock<Arc<mpsc::Sender<String>>> = OnceLock::new();
+pub use walrus_commit::{CommitToken, Lease};
+pub use walrus_seek::SeekTarget;
@@ -1,8 +1,53 @@
+use super::allocator::StateTracker;
+use super::retention::Retention;
+use super::seek_index::SeekIndex;
+use super::topic_signal::TopicSignals;
 use crate::wal::block::Block;
 use crate::wal::config::debug_print;
 use std::collections::HashMap;
 use std::io;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
+
+// Separates group and topic in cursor keys; rejected in group names
+pub(super) const GROUP_SEPARATOR: char = '\0';
+
+// Set on a cursor's block index while it points into a topic's active tail block
+pub(super) const TAIL_FLAG: u64 = 1u64 << 63;
+
+/// Key of a consumer cursor in the reader map and the read offset index. The
+/// default group keeps using the bare topic name, so existing indexes still load.
+pub(super) fn cursor_key(group: Option<&str>, topic: &str) -> String {
+    match group {
+        Some(group) => format!("{}{}{}", group, GROUP_SEPARATOR, topic),
+        None => topic.to_string(),
+    }
+}
+
+/// Inverse of [`cursor_key`]: returns the group (None for the default group) and topic.
+pub(super) fn split_cursor_key(key: &str) -> (Option<&str>, &str) {
+    match key.split_once(GROUP_SEPARATOR) {
+        Some((group, topic)) => (Some(group), topic),
+        None => (None, key),
+    }
+}
+
+/// Index key holding a topic's truncation floor, `(floor seq, dropped blocks)`: entries
+/// below the floor are gone, and the first `dropped` blocks of the topic's chain are the
+/// discarded ones still counted by persisted cursor positions. Group names can't be
+/// empty, so it never clashes with a cursor key.
+pub(super) fn floor_key(topic: &str) -> String {
+    cursor_key(Some(""), topic)
+}
+
+pub(super) fn validate_group(group: &str) -> io::Result<()> {
+    if group.is_empty() || group.contains(GROUP_SEPARATOR) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "consumer group names must be non-empty and must not contain NUL",
+        ));
+    }
+    Ok(())
+}
 
 #[derive(Debug)]
 pub(super) struct ColReaderInfo {
@@ -18,82 +63,291 @@ pub(super) struct ColReaderInfo {
     pub(super) hydrated_from_index: bool,
 }
 
+impl ColReaderInfo {
+    fn new(chain: Vec<Block>) -> Self {
+        Self {
+            chain,
+            cur_block_idx: 0,
+            cur_block_offset: 0,
+            reads_since_persist: 0,
+            tail_block_id: 0,
+            tail_offset: 0,
+            hydrated_from_index: false,
+        }
+    }
+
+    fn push_block(&mut self, block: Block) {
+        self.chain.push(block.clone());
+        // If we were reading this as the active tail, carry over progress to sealed chain
+        let new_idx = self.chain.len().saturating_sub(1);
+        if self.tail_block_id == block.id {
+            self.cur_block_idx = new_idx;
+            self.cur_block_offset = self.tail_offset.min(block.used);
+        }
+    }
+}
+
+// How far each registered cursor of a topic got, in fully consumed chain blocks
+#[derive(Default)]
+struct TopicProgress {
+    passed: HashMap<String, usize>,
+    watermark: usize,
+    // Leading chain blocks discarded by truncation; no cursor goes back before them
+    dropped: usize,
+}
+
 pub(super) struct Reader {
+    // Keyed by `cursor_key`. Every topic with sealed blocks has a default-group entry,
+    // which is the source of the chain copied into newly created group cursors.
     pub(super) data: RwLock<HashMap<String, Arc<RwLock<ColReaderInfo>>>>,
+    pub(super) seek_index: SeekIndex,
+    // Wakes blocking reads when a topic gets new entries
+    pub(super) signals: TopicSignals,
+    // Sequence number each cursor key was last committed up to, so stale tokens are ignored
+    pub(super) committed: Mutex<HashMap<String, u64>>,
+    // Retention policies, told about every sealed block
+    pub(super) retention: Retention,
+    progress: Mutex<HashMap<String, TopicProgress>>,
+    // Told when blocks become checkpointed or stop being
+    tracker: Arc<StateTracker>,
 }
 
 impl Reader {
-    pub(super) fn new() -> Self {
+    pub(super) fn new(tracker: Arc<StateTracker>) -> Self {
         Self {
             data: RwLock::new(HashMap::new()),
+            seek_index: SeekIndex::new(),
+            signals: TopicSignals::new(),
+            committed: Mutex::new(HashMap::new()),
+            retention: Retention::new(),
+            progress: Mutex::new(HashMap::new()),
+            tracker,
         }
     }
 
-    pub(super) fn append_block_to_chain(&self, col: &str, block: Block) -> io::Result<()> {
-        // fast path: try read-lock map and use per-column lock
+    /// Returns the cursor state of `group` on `col`, creating it on first use. A new
+    /// named group starts at the oldest block of the topic.
+    pub(super) fn col_info(
+        &self,
+        col: &str,
+        group: Option<&str>,
+    ) -> io::Result<Arc<RwLock<ColReaderInfo>>> {
+        let key = cursor_key(group, col);
         if let Some(info_arc) = {
+            let map = self
+                .data
+                .read()
+                .map_err(|_| io::Error::other("reader map read lock poisoned"))?;
+            map.get(&key).cloned()
+        } {
+            return Ok(info_arc);
+        }
+        // Holding the map write lock keeps chain appends out while the chain is copied
+        let mut map = self
+            .data
+            .write()
+            .map_err(|_| io::Error::other("reader map write lock poisoned"))?;
+        if let Some(info_arc) = map.get(&key) {
+            return Ok(info_arc.clone());
+        }
+        let chain = match (group, map.get(col)) {
+            (Some(_), Some(default_arc)) => default_arc
+                .read()
+                .map_err(|_| io::Error::other("col info read lock poisoned"))?
+                .chain
+                .clone(),
+            _ => Vec::new(),
+        };
+        let mut info = ColReaderInfo::new(chain);
+        // Truncated blocks may still be in the chain; a new cursor starts after them
+        info.cur_block_idx = self.dropped_blocks(col).min(info.chain.len());
+        let info_arc = Arc::new(RwLock::new(info));
+        map.insert(key, info_arc.clone());
+        Ok(info_arc)
+    }
+
+    pub(super) fn append_block_to_chain(&self, col: &str, block: Block) -> io::Result<()> {
+        // fast path: the topic already has cursors, a read lock on the map is enough
+        {
             let map = self.data.read().map_err(|_| {
                 io::Error::new(io::ErrorKind::Other, "reader map read lock poisoned")
             })?;
-            map.get(col).cloned()
-        } {
-            let mut info = info_arc.write().map_err(|_| {
-                io::Error::new(io::ErrorKind::Other, "col info write lock poisoned")
-            })?;
-            let before = info.chain.len();
-            info.chain.push(block.clone());
-            // If we were reading this as the active tail, carry over progress to sealed chain
-            let new_idx = info.chain.len().saturating_sub(1);
-            if info.tail_block_id == block.id {
-                info.cur_block_idx = new_idx;
-                info.cur_block_offset = info.tail_offset.min(block.used);
+            if map.contains_key(col) {
+                let pushed = Self::push_to_cursors(&map, col, &block)?;
+                debug_print!(
+                    "[reader] chain append(fast): col={}, block_id={}, cursors={}",
+                    col,
+                    block.id,
+                    pushed
+                );
+                self.block_sealed(col);
+                return Ok(());
             }
-            debug_print!(
-                "[reader] chain append(fast): col={}, block_id={}, chain_len {}->{}",
-                col,
-                block.id,
-                before,
-                before + 1
-            );
-            return Ok(());
         }
 
         // slow path
-        let info_arc = {
-            let mut map = self.data.write().map_err(|_| {
-                io::Error::new(io::ErrorKind::Other, "reader map write lock poisoned")
-            })?;
-            map.entry(col.to_string())
-                .or_insert_with(|| {
-                    Arc::new(RwLock::new(ColReaderInfo {
-                        chain: Vec::new(),
-                        cur_block_idx: 0,
-                        cur_block_offset: 0,
-                        reads_since_persist: 0,
-                        tail_block_id: 0,
-                        tail_offset: 0,
-                        hydrated_from_index: false,
-                    }))
-                })
-                .clone()
-        };
-        let mut info = info_arc
+        let mut map = self
+            .data
             .write()
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "col info write lock poisoned"))?;
-        info.chain.push(block.clone());
-        // If we were reading this as the active tail, carry over progress to sealed chain
-        let new_idx = info.chain.len().saturating_sub(1);
-        if info.tail_block_id == block.id {
-            info.cur_block_idx = new_idx;
-            info.cur_block_offset = info.tail_offset.min(block.used);
-        }
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "reader map write lock poisoned"))?;
+        map.entry(col.to_string())
+            .or_insert_with(|| Arc::new(RwLock::new(ColReaderInfo::new(Vec::new()))));
+        let pushed = Self::push_to_cursors(&map, col, &block)?;
         debug_print!(
-            "[reader] chain append(slow/new): col={}, block_id={}, chain_len {}->{}",
+            "[reader] chain append(slow/new): col={}, block_id={}, cursors={}",
             col,
             block.id,
-            0,
-            1
+            pushed
         );
+        self.block_sealed(col);
         Ok(())
     }
+
+    // Wakes the background thread when the topic's retention policy became due
+    fn block_sealed(&self, col: &str) {
+        if self.retention.block_sealed(col) {
+            self.tracker.reclaimer().retention_due();
+        }
+    }
+
+    // Appends `block` to the chain of every cursor (default and named groups) on `col`
+    fn push_to_cursors(
+        map: &HashMap<String, Arc<RwLock<ColReaderInfo>>>,
+        col: &str,
+        block: &Block,
+    ) -> io::Result<usize> {
+        let mut pushed = 0;
+        for (key, info_arc) in map.iter() {
+            if split_cursor_key(key).1 != col {
+                continue;
+            }
+            let mut info = info_arc.write().map_err(|_| {
+                io::Error::new(io::ErrorKind::Other, "col info write lock poisoned")
+            })?;
+            info.push_block(block.clone());
+            pushed += 1;
+        }
+        Ok(pushed)
+    }
+
+    /// Records that the cursor `key` has fully consumed the first `passed` blocks of
+    /// `chain`, registering it for `col` if needed. Blocks are checkpointed only once
+    /// every registered cursor is past them, and un-checkpointed if one moves back.
+    pub(super) fn set_blocks_passed(&self, col: &str, key: &str, passed: usize, chain: &[Block]) {
+        let Ok(mut progress) = self.progress.lock() else {
+            return;
+        };
+        let topic = progress.entry(col.to_string()).or_default();
+        topic
+            .passed
+            .insert(key.to_string(), passed.max(topic.dropped));
+        let watermark = topic.passed.values().copied().min().unwrap_or(0);
+        let old = topic.watermark;
+        topic.watermark = watermark;
+
+        if watermark > old {
+            for block in chain.iter().take(watermark).skip(old) {
+                self.tracker.set_checkpointed_true(block.id as usize);
+            }
+        } else if watermark < old {
+            for block in chain.iter().take(old).skip(watermark) {
+                self.tracker.set_checkpointed_false(block.id as usize);
+            }
+        }
+    }
+
+    /// Blocks at the front of `col`'s chains that every registered cursor has fully
+    /// consumed, which are the checkpointed ones.
+    pub(super) fn consumed_blocks(&self, col: &str) -> usize {
+        self.progress
+            .lock()
+            .ok()
+            .and_then(|p| p.get(col).map(|t| t.watermark))
+            .unwrap_or(0)
+    }
+
+    /// Blocks at the front of `col`'s chains discarded by truncation.
+    pub(super) fn dropped_blocks(&self, col: &str) -> usize {
+        self.progress
+            .lock()
+            .ok()
+            .and_then(|p| p.get(col).map(|t| t.dropped))
+            .unwrap_or(0)
+    }
+
+    /// Discards the first `dropped` blocks of `chain` for every cursor on `col`: cursors
+    /// behind them move to the first kept block, and they are checkpointed whether or not
+    /// a cursor has registered yet. Returns the keys of the cursors that moved.
+    pub(super) fn discard_blocks(&self, col: &str, dropped: usize, chain: &[Block]) -> Vec<String> {
+        let mut moved = Vec::new();
+        if let Ok(map) = self.data.read() {
+            for (key, info_arc) in map.iter() {
+                if split_cursor_key(key).1 != col {
+                    continue;
+                }
+                let Ok(mut info) = info_arc.write() else {
+                    continue;
+                };
+                if info.cur_block_idx < dropped {
+                    info.cur_block_idx = dropped;
+                    info.cur_block_offset = 0;
+                    info.tail_block_id = 0;
+                    info.tail_offset = 0;
+                    moved.push(key.clone());
+                }
+            }
+        }
+
+        let Ok(mut progress) = self.progress.lock() else {
+            return moved;
+        };
+        let topic = progress.entry(col.to_string()).or_default();
+        topic.dropped = topic.dropped.max(dropped);
+        for passed in topic.passed.values_mut() {
+            *passed = (*passed).max(topic.dropped);
+        }
+        let old = topic.watermark;
+        topic.watermark = old.max(topic.dropped);
+        for block in chain.iter().take(topic.watermark).skip(old) {
+            self.tracker.set_checkpointed_true(block.id as usize);
+        }
+        moved
+    }
+
+    /// Drops every cursor on `col` along with its progress and committed positions, and
+    /// returns the blocks their chains held. A reader still holding one of the cursors
+    /// finds its chain empty.
+    pub(super) fn forget_topic(&self, col: &str) -> Vec<Block> {
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut keys = Vec::new();
+        if let Ok(mut map) = self.data.write() {
+            map.retain(|key, info_arc| {
+                if split_cursor_key(key).1 != col {
+                    return true;
+                }
+                if let Ok(mut info) = info_arc.write() {
+                    for block in info.chain.drain(..) {
+                        if !blocks.iter().any(|b| b.id == block.id) {
+                            blocks.push(block);
+                        }
+                    }
+                    info.cur_block_idx = 0;
+                    info.cur_block_offset = 0;
+                    info.tail_block_id = 0;
+                    info.tail_offset = 0;
+                }
+                keys.push(key.clone());
+                false
+            });
+        }
+        if let Ok(mut progress) = self.progress.lock() {
+            progress.remove(col);
+        }
+        if let Ok(mut committed) = self.committed.lock() {
+            for key in &keys {
+                committed.remove(key);
+            }
+        }
+        blocks
+    }
 }
@@ -0,0 +1,302 @@
+use super::Walrus;
+use super::allocator::StateTracker;
+use super::reader::floor_key;
+use crate::wal::config::debug_print;
+use crate::wal::storage::StorageImpl;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+// How often the background thread looks for blocks past a topic's age limit
+const AGE_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Space reclamation figures returned by [`Walrus::reclaim_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReclaimStats {
+    /// Data files of the instance whose blocks are all checkpointed but that are still
+    /// on disk, and their size in bytes.
+    pub reclaimable_files: u64,
+    pub reclaimable_bytes: u64,
+    /// Data files deleted by the instance's background reclaimer since it was opened,
+    /// and their size in bytes.
+    pub reclaimed_files: u64,
+    pub reclaimed_bytes: u64,
+}
+
+pub(super) enum ReclaimMsg {
+    /// A file that may have just become reclaimable
+    Candidate(String),
+    /// A topic sealed a block and its retention policy is due
+    RetentionDue,
+    /// The instance whose retention the thread applies, once it is open
+    Attach(Box<Walrus>),
+    /// Reclaim the queued files right away, then acknowledge
+    Now(mpsc::Sender<()>),
+    /// Reclaim the queued files and run the pending fsyncs, then exit
+    Stop,
+}
+
+#[derive(Default)]
+struct ReclaimCounters {
+    files: AtomicU64,
+    bytes: AtomicU64,
+}
+
+/// Handle on an instance's background reclaimer: queues files and reads its counters.
+#[derive(Clone)]
+pub(super) struct Reclaimer {
+    tx: mpsc::Sender<ReclaimMsg>,
+    counters: Arc<ReclaimCounters>,
+}
+
+impl Reclaimer {
+    /// A reclaimer along with the receiving end the background thread drains.
+    pub(super) fn channel() -> (Self, mpsc::Receiver<ReclaimMsg>) {
+        let (tx, rx) = mpsc::channel();
+        let counters = Arc::new(ReclaimCounters::default());
+        (Self { tx, counters }, rx)
+    }
+
+    pub(super) fn candidate(&self, file_path: String) {
+        let _ = self.tx.send(ReclaimMsg::Candidate(file_path));
+    }
+
+    /// Wakes the background thread to apply the retention policies that are due.
+    pub(super) fn retention_due(&self) {
+        let _ = self.tx.send(ReclaimMsg::RetentionDue);
+    }
+
+    /// Hands the background thread a handle on the opened instance.
+    pub(super) fn attach(&self, wal: Walrus) {
+        let _ = self.tx.send(ReclaimMsg::Attach(Box::new(wal)));
+    }
+
+    /// Asks the background thread to finish its queued work and exit.
+    pub(super) fn stop(&self) {
+        let _ = self.tx.send(ReclaimMsg::Stop);
+    }
+
+    // Returns once the reclaimer went through every file queued so far
+    fn run_now(&self) -> io::Result<()> {
+        let (done_tx, done_rx) = mpsc::channel();
+        self.tx
+            .send(ReclaimMsg::Now(done_tx))
+            .map_err(|_| io::Error::other("background reclaimer stopped"))?;
+        done_rx
+            .recv()
+            .map_err(|_| io::Error::other("background reclaimer stopped"))
+    }
+}
+
+/// Work queued for the background thread besides fsyncs: files to reclaim, retention
+/// to apply, and callers of [`Walrus::reclaim_now`] waiting for both.
+pub(super) struct Maintenance {
+    pending: HashSet<String>,
+    // Blocks of deleted files whose summaries are still to be dropped
+    reclaimed: HashSet<u64>,
+    waiting: Vec<mpsc::Sender<()>>,
+    wal: Option<Box<Walrus>>,
+    retention_due: bool,
+    last_age_sweep: Instant,
+    pub(super) stopping: bool,
+}
+
+impl Maintenance {
+    pub(super) fn new() -> Self {
+        Self {
+            pending: HashSet::new(),
+            reclaimed: HashSet::new(),
+            waiting: Vec::new(),
+            wal: None,
+            retention_due: false,
+            last_age_sweep: Instant::now(),
+            stopping: false,
+        }
+    }
+
+    pub(super) fn take(&mut self, msg: ReclaimMsg) {
+        match msg {
+            ReclaimMsg::Candidate(path) => {
+                debug_print!("[reclaim] deletion requested: {}", path);
+                self.pending.insert(path);
+            }
+            // The due topics themselves are kept by the instance
+            ReclaimMsg::RetentionDue => self.retention_due = true,
+            ReclaimMsg::Attach(wal) => self.wal = Some(wal),
+            ReclaimMsg::Now(done) => self.waiting.push(done),
+            ReclaimMsg::Stop => self.stopping = true,
+        }
+    }
+
+    /// Applies the retention that is due, deletes the files that became reclaimable and
+    /// acknowledges the waiting callers. Retention is skipped once the instance stops.
+    pub(super) fn run(
+        &mut self,
+        rx: &mpsc::Receiver<ReclaimMsg>,
+        pool: &mut HashMap<String, StorageImpl>,
+        tracker: &StateTracker,
+    ) {
+        if let Some(wal) = self.wal.take() {
+            // Truncation checkpoints the discarded blocks and queues their files, and
+            // appends meanwhile may seal more blocks
+            while !self.stopping && std::mem::take(&mut self.retention_due) {
+                wal.apply_due_retention();
+                while let Ok(msg) = rx.try_recv() {
+                    self.take(msg);
+                }
+            }
+            self.wal = Some(wal);
+        }
+        self.reclaimed
+            .extend(reclaim_files(&mut self.pending, pool, tracker));
+        if !self.reclaimed.is_empty()
+            && let Some(wal) = &self.wal
+        {
+            wal.forget_reclaimed_blocks(&self.reclaimed);
+            self.reclaimed.clear();
+        }
+        for done in self.waiting.drain(..) {
+            let _ = done.send(());
+        }
+    }
+
+    /// Time left until the next sweep for blocks past their topic's age limit.
+    pub(super) fn until_age_sweep(&self) -> Duration {
+        AGE_SWEEP_INTERVAL.saturating_sub(self.last_age_sweep.elapsed())
+    }
+
+    /// Expires the blocks of topics with an age limit, at most once per
+    /// `AGE_SWEEP_INTERVAL`, so topics that stopped receiving appends age out too.
+    pub(super) fn sweep_aged(&mut self) {
+        if self.stopping || !self.until_age_sweep().is_zero() {
+            return;
+        }
+        self.last_age_sweep = Instant::now();
+        if let Some(wal) = &self.wal {
+            wal.expire_aged_topics();
+        }
+    }
+}
+
+/// Deletes the queued files whose blocks are all checkpointed and that no block is
+/// being written to, after closing the background pool's handle on them. Files that
+/// stopped being reclaimable, because a reader seeked back into them, are dropped
+/// from the queue; they are queued again once consumed. Returns the ids of the blocks
+/// the deleted files held.
+fn reclaim_files(
+    pending: &mut HashSet<String>,
+    pool: &mut HashMap<String, StorageImpl>,
+    tracker: &StateTracker,
+) -> Vec<u64> {
+    let counters = &tracker.reclaimer().counters;
+    let mut reclaimed = Vec::new();
+    for path in pending.drain() {
+        if !tracker.is_reclaimable(&path) {
+            debug_print!("[reclaim] file no longer reclaimable: {}", path);
+            continue;
+        }
+        pool.remove(&path);
+        tracker.mmaps().evict(&path);
+        let bytes = fs::metadata(&path).map_or(0, |m| m.len());
+        match fs::remove_file(&path) {
+            Ok(_) => {
+                debug_print!("[reclaim] deleted file {}", path);
+                reclaimed.extend(tracker.forget_file(&path));
+                counters.files.fetch_add(1, Ordering::Relaxed);
+                counters.bytes.fetch_add(bytes, Ordering::Relaxed);
+            }
+            Err(e) => debug_print!("[reclaim] delete failed for {}: {}", path, e),
+        }
+    }
+    reclaimed
+}
+
+impl Walrus {
+    // Readers have passed the blocks of a deleted file, so nothing seeks into them again
+    fn forget_reclaimed_blocks(&self, block_ids: &HashSet<u64>) {
+        for (topic, next_seq) in self.reader.seek_index.discard_blocks(block_ids) {
+            if let Err(e) = self.record_reclaimed_floor(&topic, next_seq) {
+                debug_print!("[reclaim] floor update failed: col={}, err={}", topic, e);
+            }
+        }
+        self.prune_aborted_txns();
+    }
+
+    // Recovery resumes a topic's sequence numbers from its blocks, so one whose blocks
+    // were all reclaimed keeps them in its floor, like a truncated topic. The floor also
+    // counts the reclaimed blocks, which recovery will not find, in front of the chain.
+    fn record_reclaimed_floor(&self, topic: &str, next_seq: u64) -> io::Result<()> {
+        let next_seq = match self.existing_writer(topic)? {
+            Some(writer) => writer.written_tail()?.0.max(next_seq),
+            None => next_seq,
+        };
+        let reclaimed = match self
+            .reader
+            .data
+            .read()
+            .map_err(|_| io::Error::other("reader map read lock poisoned"))?
+            .get(topic)
+        {
+            Some(info_arc) => info_arc
+                .read()
+                .map_err(|_| io::Error::other("col info read lock poisoned"))?
+                .chain
+                .iter()
+                .take_while(|b| self.reader.seek_index.summary(topic, b.id).is_none())
+                .count() as u64,
+            None => 0,
+        };
+
+        let mut idx = self
+            .read_offset_index
+            .write()
+            .map_err(|_| io::Error::other("read offset index lock poisoned"))?;
+        let (old_floor, old_dropped) = idx
+            .get(&floor_key(topic))
+            .map_or((0, 0), |pos| (pos.cur_block_idx, pos.cur_block_offset));
+        idx.update(
+            &[],
+            vec![(
+                floor_key(topic),
+                next_seq.max(old_floor),
+                reclaimed.max(old_dropped),
+            )],
+        )
+    }
+
+    /// Deletes the data files whose blocks are all checkpointed without waiting for the
+    /// background reclaimer to get to them, and returns the figures afterwards.
+    pub fn reclaim_now(&self) -> io::Result<ReclaimStats> {
+        let reclaimer = self.allocator.tracker().reclaimer();
+        for path in self.reclaimable_files() {
+            reclaimer.candidate(path);
+        }
+        reclaimer.run_now()?;
+        Ok(self.reclaim_stats())
+    }
+
+    pub fn reclaim_stats(&self) -> ReclaimStats {
+        let mut stats = ReclaimStats::default();
+        for path in self.reclaimable_files() {
+            stats.reclaimable_files += 1;
+            stats.reclaimable_bytes += fs::metadata(&path).map_or(0, |m| m.len());
+        }
+        let counters = &self.allocator.tracker().reclaimer().counters;
+        stats.reclaimed_files = counters.files.load(Ordering::Relaxed);
+        stats.reclaimed_bytes = counters.bytes.load(Ordering::Relaxed);
+        stats
+    }
+
+    fn reclaimable_files(&self) -> Vec<String> {
+        let tracker = self.allocator.tracker();
+        tracker
+            .file_paths()
+            .into_iter()
+            .filter(|path| tracker.is_reclaimable(path))
+            .collect()
+    }
+}
@@ -0,0 +1,244 @@
+use super::manifest::SealedBlock;
+use super::seek_index::BlockSummary;
+use crate::wal::block::{Block, Metadata};
+use crate::wal::config::{FILE_HEADER_SIZE, Geometry, PREFIX_META_SIZE, debug_print};
+use crate::wal::file_header::FileHeader;
+use crate::wal::storage::{SharedMmap, SharedMmapKeeper, StorageOptions};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// The blocks found in one data file, before they are numbered and chained.
+pub(super) struct ScannedFile {
+    pub(super) mmap: Arc<SharedMmap>,
+    pub(super) data_start: u64,
+    pub(super) block_size: u64,
+    pub(super) file_created_ms: u64,
+    /// Block slots the scan went through, written or not; the next file's blocks are
+    /// numbered after them
+    pub(super) slots: u64,
+    pub(super) blocks: Vec<ScannedBlock>,
+}
+
+pub(super) struct ScannedBlock {
+    pub(super) offset: u64,
+    pub(super) block: SealedBlock,
+    pub(super) from_manifest: bool,
+}
+
+/// Scans `files` on a pool of worker threads and returns what each holds, in the
+/// same order; `None` for a file that could not be opened. Records are numbered
+/// from what they carry, as if every topic started in the file: the caller rescans
+/// the few blocks whose numbering depends on the files before them.
+pub(super) fn scan_files(
+    files: &[String],
+    storage: StorageOptions,
+    mmaps: &SharedMmapKeeper,
+    recorded: &HashMap<(String, u64), SealedBlock>,
+) -> io::Result<Vec<Option<ScannedFile>>> {
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(files.len());
+    let next = AtomicUsize::new(0);
+    let mut scanned: Vec<Option<io::Result<Option<ScannedFile>>>> =
+        files.iter().map(|_| None).collect();
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    // Files are taken one at a time, so one large file does not hold up others
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(file_path) = files.get(i) else {
+                            break;
+                        };
+                        done.push((i, scan_file(file_path, storage, mmaps, recorded)));
+                    }
+                    done
+                })
+            })
+            .collect();
+        for handle in handles {
+            let done = handle
+                .join()
+                .map_err(|_| io::Error::other("recovery worker panicked"))?;
+            for (i, result) in done {
+                scanned[i] = Some(result);
+            }
+        }
+        Ok::<_, io::Error>(())
+    })?;
+    scanned
+        .into_iter()
+        .map(|result| result.unwrap_or(Ok(None)))
+        .collect()
+}
+
+fn scan_file(
+    file_path: &str,
+    storage: StorageOptions,
+    mmaps: &SharedMmapKeeper,
+    recorded: &HashMap<(String, u64), SealedBlock>,
+) -> io::Result<Option<ScannedFile>> {
+    let mmap = match mmaps.get_mmap_arc(file_path, storage) {
+        Ok(m) => m,
+        Err(e) => {
+            debug_print!("[recovery] mmap open failed for {}: {}", file_path, e);
+            return Ok(None);
+        }
+    };
+    // Refuse files written by a newer build rather than misreading their blocks;
+    // headerless files are the original layout with the default geometry.
+    // Each file is scanned with the block size it was written with, which
+    // may differ from this instance's.
+    let mut header_buf = vec![0u8; (FILE_HEADER_SIZE as usize).min(mmap.len())];
+    mmap.read(0, &mut header_buf);
+    let (data_start, block_size, file_size) = match FileHeader::decode(&header_buf) {
+        Ok(Some(header)) => (
+            header.header_size as u64,
+            header.block_size,
+            header.block_size.saturating_mul(header.blocks_per_file),
+        ),
+        Ok(None) => {
+            let geometry = Geometry::default();
+            (0, geometry.block_size, geometry.file_size())
+        }
+        Err(e) => {
+            return Err(io::Error::new(
+                e.kind(),
+                format!("cannot recover wal file {}: {}", file_path, e),
+            ));
+        }
+    };
+    let file_name = Path::new(file_path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    // Files are named after their creation time; stand-in append time for older records
+    let file_created_ms = file_name.parse::<u64>().unwrap_or(0);
+
+    let mut blocks = Vec::new();
+    let data_end = data_start.saturating_add(file_size).min(mmap.len() as u64);
+    let mut block_offset: u64 = data_start;
+    while block_offset + block_size <= data_end {
+        // heuristic: if first bytes are zero, assume no more blocks
+        if !block_written(&mmap, block_offset) {
+            // Unless a rolled back batch zeroed the first header of a block that
+            // other blocks were handed out after
+            let mut next = block_offset + block_size;
+            while next + block_size <= data_end && !block_written(&mmap, next) {
+                next += block_size;
+            }
+            if next + block_size > data_end {
+                break;
+            }
+            block_offset += block_size;
+            continue;
+        }
+
+        // A recorded block is taken as is unless it no longer looks like the one recorded
+        if let Some(sealed) = recorded
+            .get(&(file_name.to_string(), block_offset))
+            .filter(|b| b.fits(&mmap, block_offset, block_size))
+        {
+            blocks.push(ScannedBlock {
+                offset: block_offset,
+                block: sealed.clone(),
+                from_manifest: true,
+            });
+            block_offset += block_size;
+            continue;
+        }
+
+        // try to read first metadata to get column name
+        let mut meta_buf = vec![0u8; PREFIX_META_SIZE];
+        mmap.read(block_offset as usize, &mut meta_buf);
+        let meta_len = meta_buf[0] as usize;
+        if meta_len == 0 || meta_len > PREFIX_META_SIZE - 2 {
+            block_offset += block_size;
+            continue;
+        }
+        let Ok(md) = Metadata::decode_prefix(&meta_buf) else {
+            break;
+        };
+        let block_stub = stub(file_path, &mmap, block_offset, block_size);
+        let Some((used, summary)) = scan_block(&block_stub, 0, file_created_ms) else {
+            break;
+        };
+        blocks.push(ScannedBlock {
+            offset: block_offset,
+            block: SealedBlock {
+                topic: md.owned_by,
+                used,
+                summary,
+            },
+            from_manifest: false,
+        });
+        block_offset += block_size;
+    }
+
+    Ok(Some(ScannedFile {
+        mmap,
+        data_start,
+        block_size,
+        file_created_ms,
+        slots: (block_offset - data_start) / block_size,
+        blocks,
+    }))
+}
+
+/// An empty block at `offset`, to read records through.
+pub(super) fn stub(file_path: &str, mmap: &Arc<SharedMmap>, offset: u64, block_size: u64) -> Block {
+    Block {
+        id: 0,
+        file_path: file_path.to_string(),
+        offset,
+        limit: block_size,
+        mmap: mmap.clone(),
+        used: 0,
+    }
+}
+
+/// Reads the records of a block the manifest does not cover, numbering them from
+/// `next_seq` on, and returns the bytes they take up with their summary.
+pub(super) fn scan_block(
+    block: &Block,
+    next_seq: u64,
+    file_created_ms: u64,
+) -> Option<(u64, BlockSummary)> {
+    let mut next_seq = next_seq;
+    let mut used: u64 = 0;
+    let mut summary: Option<BlockSummary> = None;
+    while let Ok((entry, consumed, txn)) = block.read_record(used) {
+        // Records written before sequence numbers existed all decode as 0;
+        // count those so the topic still continues monotonically.
+        let seq = entry.seq.max(next_seq);
+        next_seq = seq + 1;
+        let ts = if entry.timestamp_ms == 0 {
+            file_created_ms
+        } else {
+            entry.timestamp_ms
+        };
+        match summary.as_mut() {
+            Some(summary) => summary.push(seq, used, ts, txn),
+            None => summary = Some(BlockSummary::start(block.id, seq, used, ts, txn)),
+        }
+        used += consumed as u64;
+        // no room left for another record prefix
+        if used + PREFIX_META_SIZE as u64 > block.limit {
+            break;
+        }
+    }
+    Some((used, summary?))
+}
+
+// Whether anything was written at the start of the block at `offset`
+fn block_written(mmap: &SharedMmap, offset: u64) -> bool {
+    let mut probe = [0u8; 8];
+    mmap.read(offset as usize, &mut probe);
+    probe.iter().any(|&b| b != 0)
+}
@@ -0,0 +1,244 @@
+use super::Walrus;
+use super::seek_index::BlockSummary;
+use super::walrus_seek::SeekTarget;
+use crate::wal::config::{debug_print, now_millis};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+/// Limits on what a topic keeps, for [`WalrusBuilder::retention`](crate::WalrusBuilder::retention)
+/// and [`Walrus::set_topic_retention`]. A policy without limits keeps everything.
+///
+/// Retention works on whole sealed blocks, like [`Walrus::truncate_topic_before`]: the
+/// oldest blocks go once a limit is exceeded without them, so a topic may stay up to a
+/// block above its limits, and the writer's active block is always kept.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub(super) max_age: Option<Duration>,
+    pub(super) max_bytes: Option<u64>,
+    pub(super) max_entries: Option<u64>,
+}
+
+impl RetentionPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Discards blocks whose newest entry was appended more than `age` ago.
+    pub fn max_age(mut self, age: Duration) -> Self {
+        self.max_age = Some(age);
+        self
+    }
+
+    /// Discards the oldest blocks while the topic holds more than `bytes`, entry
+    /// metadata prefixes included.
+    pub fn max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = Some(bytes);
+        self
+    }
+
+    /// Discards the oldest blocks while the topic holds more than `entries` entries.
+    pub fn max_entries(mut self, entries: u64) -> Self {
+        self.max_entries = Some(entries);
+        self
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.max_age.is_none() && self.max_bytes.is_none() && self.max_entries.is_none()
+    }
+}
+
+/// Retention policies of an instance and the topics that sealed a block since their
+/// policy was last applied. Nothing here is persisted.
+pub(super) struct Retention {
+    default: RwLock<Option<RetentionPolicy>>,
+    topics: RwLock<HashMap<String, RetentionPolicy>>,
+    due: Mutex<HashSet<String>>,
+}
+
+impl Retention {
+    pub(super) fn new() -> Self {
+        Self {
+            default: RwLock::new(None),
+            topics: RwLock::new(HashMap::new()),
+            due: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub(super) fn set_default(&self, policy: Option<RetentionPolicy>) {
+        if let Ok(mut guard) = self.default.write() {
+            *guard = policy;
+        }
+    }
+
+    fn set(&self, topic: &str, policy: Option<RetentionPolicy>) {
+        if let Ok(mut guard) = self.topics.write() {
+            match policy {
+                Some(policy) => guard.insert(topic.to_string(), policy),
+                None => guard.remove(topic),
+            };
+        }
+    }
+
+    /// The policy `topic` follows, if it has any limit.
+    fn policy_for(&self, topic: &str) -> Option<RetentionPolicy> {
+        let own = self.topics.read().ok().and_then(|m| m.get(topic).copied());
+        own.or_else(|| self.default.read().ok().and_then(|d| *d))
+            .filter(|p| !p.is_unlimited())
+    }
+
+    /// Called when `topic` seals a block, the only time it can start exceeding a limit
+    /// other than age. Returns whether its policy became due.
+    pub(super) fn block_sealed(&self, topic: &str) -> bool {
+        self.policy_for(topic).is_some() && self.mark_due(topic)
+    }
+
+    fn mark_due(&self, topic: &str) -> bool {
+        self.due
+            .lock()
+            .map(|mut due| {
+                due.insert(topic.to_string());
+            })
+            .is_ok()
+    }
+
+    fn take_due(&self) -> Vec<String> {
+        self.due
+            .lock()
+            .map(|mut due| due.drain().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Walrus {
+    /// Sets the retention policy of `topic`, overriding the instance's default, and
+    /// applies it right away. [`RetentionPolicy::new`] exempts the topic from the default.
+    pub fn set_topic_retention(&self, topic: &str, policy: RetentionPolicy) -> io::Result<()> {
+        self.reader.retention.set(topic, Some(policy));
+        self.enforce_topic_retention(topic).map(|_| ())
+    }
+
+    /// Puts `topic` back under the instance's default retention policy.
+    pub fn clear_topic_retention(&self, topic: &str) -> io::Result<()> {
+        self.reader.retention.set(topic, None);
+        self.enforce_topic_retention(topic).map(|_| ())
+    }
+
+    /// Applies the retention policies of every topic and returns how many entries were
+    /// discarded. The background thread applies policies on its own shortly after a topic
+    /// seals a block, and expires blocks past their age limit about once a second.
+    pub fn enforce_retention(&self) -> io::Result<u64> {
+        let mut discarded = 0;
+        for topic in self.list_topics() {
+            discarded += self.enforce_topic_retention(&topic)?;
+        }
+        Ok(discarded)
+    }
+
+    /// Applies the policies of topics that sealed a block since the last time. Nobody
+    /// waits on the result, so a failure is logged and the topic is tried again on the
+    /// next pass.
+    pub(super) fn apply_due_retention(&self) {
+        for topic in self.reader.retention.take_due() {
+            if let Err(e) = self.enforce_topic_retention(&topic) {
+                debug_print!("[retention] enforce failed: col={}, err={}", topic, e);
+                self.reader.retention.mark_due(&topic);
+            }
+        }
+    }
+
+    /// Applies the policies with an age limit, for topics that stopped sealing blocks.
+    pub(super) fn expire_aged_topics(&self) {
+        for topic in self.list_topics() {
+            let aged = self
+                .reader
+                .retention
+                .policy_for(&topic)
+                .is_some_and(|p| p.max_age.is_some());
+            if aged && let Err(e) = self.enforce_topic_retention(&topic) {
+                debug_print!("[retention] expiry failed: col={}, err={}", topic, e);
+            }
+        }
+    }
+
+    // Discards the sealed blocks of `topic` its policy no longer keeps, by truncating
+    // before the first entry kept. Returns how many entries were discarded.
+    fn enforce_topic_retention(&self, topic: &str) -> io::Result<u64> {
+        let Some(policy) = self.reader.retention.policy_for(topic) else {
+            return Ok(0);
+        };
+        let summaries = self.reader.seek_index.summaries(topic);
+        let (Some(first), Some(last)) = (summaries.first(), summaries.last()) else {
+            return Ok(0);
+        };
+        let (first_seq, next_seq) = (first.first_seq, last.next_seq);
+
+        let sealed: Vec<(u64, u64)> = match self
+            .reader
+            .data
+            .read()
+            .map_err(|_| io::Error::other("reader map read lock poisoned"))?
+            .get(topic)
+        {
+            Some(info_arc) => {
+                let info = info_arc
+                    .read()
+                    .map_err(|_| io::Error::other("col info read lock poisoned"))?;
+                let dropped = self.reader.dropped_blocks(topic);
+                info.chain
+                    .iter()
+                    .skip(dropped)
+                    .map(|b| (b.id, b.used))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        let active = match self.existing_writer(topic)? {
+            Some(writer) => writer.snapshot_block()?.1,
+            None => 0,
+        };
+
+        // Sealed blocks with their summary, oldest first; reclaimed ones have none left
+        let blocks: Vec<(&BlockSummary, u64)> = sealed
+            .iter()
+            .filter_map(|(id, used)| {
+                summaries
+                    .iter()
+                    .find(|s| s.block_id == *id)
+                    .map(|s| (s, *used))
+            })
+            .collect();
+        let mut keep_from = first_seq;
+        if let Some(max) = policy.max_entries {
+            keep_from = keep_from.max(next_seq.saturating_sub(max));
+        }
+        if let Some(age) = policy.max_age {
+            let cutoff = now_millis().saturating_sub(age.as_millis() as u64);
+            for (summary, _) in blocks.iter().take_while(|(s, _)| s.last_ts < cutoff) {
+                keep_from = keep_from.max(summary.next_seq);
+            }
+        }
+        if let Some(max) = policy.max_bytes {
+            let mut total = blocks.iter().map(|(_, used)| used).sum::<u64>() + active;
+            for (summary, used) in &blocks {
+                if total <= max {
+                    break;
+                }
+                total -= used;
+                keep_from = keep_from.max(summary.next_seq);
+            }
+        }
+        if keep_from == first_seq {
+            return Ok(0);
+        }
+
+        let floor = self.truncate_topic_before(topic, SeekTarget::Sequence(keep_from))?;
+        debug_print!(
+            "[retention] expired blocks: col={}, first_kept={}",
+            topic,
+            floor
+        );
+        Ok(floor.saturating_sub(first_seq))
+    }
+}
@@ -0,0 +1,252 @@
+use crate::wal::block::TxnMark;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+// One sparse position is kept every this many entries of a block
+const SEEK_POINT_STRIDE: u64 = 64;
+
+#[derive(Clone, Copy, Debug)]
+pub(super) struct SeekPoint {
+    pub(super) seq: u64,
+    pub(super) offset: u64,
+    pub(super) timestamp_ms: u64,
+}
+
+/// In-memory summary of one block of a topic: its sequence range, append
+/// times, and a sparse list of entry positions to start scans from.
+#[derive(Clone, Debug)]
+pub(super) struct BlockSummary {
+    pub(super) block_id: u64,
+    pub(super) first_seq: u64,
+    // one past the last sequence number written to the block
+    pub(super) next_seq: u64,
+    pub(super) first_ts: u64,
+    pub(super) last_ts: u64,
+    pub(super) points: Vec<SeekPoint>,
+    // Transaction marks of the block's records, repeats in a row folded into one
+    pub(super) txns: Vec<TxnMark>,
+}
+
+impl BlockSummary {
+    /// Summary of a block whose first entry is `seq`.
+    pub(super) fn start(block_id: u64, seq: u64, offset: u64, ts: u64, txn: TxnMark) -> Self {
+        let mut summary = BlockSummary {
+            block_id,
+            first_seq: seq,
+            next_seq: seq,
+            first_ts: ts,
+            last_ts: ts,
+            points: Vec::new(),
+            txns: Vec::new(),
+        };
+        summary.push(seq, offset, ts, txn);
+        summary
+    }
+
+    /// Adds the block's next entry.
+    pub(super) fn push(&mut self, seq: u64, offset: u64, ts: u64, txn: TxnMark) {
+        if (seq - self.first_seq).is_multiple_of(SEEK_POINT_STRIDE) {
+            self.points.push(SeekPoint {
+                seq,
+                offset,
+                timestamp_ms: ts,
+            });
+        }
+        if txn != TxnMark::None && self.txns.last() != Some(&txn) {
+            self.txns.push(txn);
+        }
+        self.next_seq = seq + 1;
+        self.last_ts = self.last_ts.max(ts);
+    }
+
+    /// Closest indexed position at or before `seq`.
+    pub(super) fn point_for_seq(&self, seq: u64) -> SeekPoint {
+        let idx = self.points.partition_point(|p| p.seq <= seq);
+        self.points[idx.saturating_sub(1)]
+    }
+
+    /// Last indexed position appended strictly before `timestamp_ms`, or the block start.
+    pub(super) fn point_for_timestamp(&self, timestamp_ms: u64) -> SeekPoint {
+        let idx = self
+            .points
+            .iter()
+            .take_while(|p| p.timestamp_ms < timestamp_ms)
+            .count();
+        self.points[idx.saturating_sub(1)]
+    }
+}
+
+/// Per-topic block summaries, fed by writers as entries land and by recovery
+/// while it replays blocks. Summaries of sealed blocks are also kept in the
+/// block manifest, which recovery rebuilds this index from.
+pub(super) struct SeekIndex {
+    // Topics are added far less often than entries, so appends only read-lock the map
+    topics: RwLock<HashMap<String, Arc<RwLock<Vec<BlockSummary>>>>>,
+}
+
+impl SeekIndex {
+    pub(super) fn new() -> Self {
+        Self {
+            topics: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Records an entry; entries must arrive in sequence order per topic.
+    pub(super) fn record(
+        &self,
+        topic: &str,
+        block_id: u64,
+        seq: u64,
+        offset: u64,
+        ts: u64,
+        txn: TxnMark,
+    ) {
+        let push = |blocks: &RwLock<Vec<BlockSummary>>| {
+            let Ok(mut blocks) = blocks.write() else {
+                return;
+            };
+            match blocks.last_mut() {
+                Some(last) if last.block_id == block_id => last.push(seq, offset, ts, txn),
+                _ => blocks.push(BlockSummary::start(block_id, seq, offset, ts, txn)),
+            }
+        };
+        // The map stays locked while recording, so a topic being forgotten cannot lose it
+        if let Ok(map) = self.topics.read()
+            && let Some(blocks) = map.get(topic)
+        {
+            push(blocks);
+            return;
+        }
+        if let Ok(mut map) = self.topics.write() {
+            push(map.entry(topic.to_string()).or_default());
+        }
+    }
+
+    /// Adds the summary of a whole block, following the blocks recorded so far.
+    pub(super) fn insert(&self, topic: &str, summary: BlockSummary) {
+        if let Ok(mut map) = self.topics.write()
+            && let Ok(mut blocks) = map.entry(topic.to_string()).or_default().write()
+        {
+            blocks.push(summary);
+        }
+    }
+
+    fn blocks(&self, topic: &str) -> Option<Arc<RwLock<Vec<BlockSummary>>>> {
+        self.topics.read().ok()?.get(topic).cloned()
+    }
+
+    /// Summary of block `block_id` of `topic`, if it holds any recorded entry.
+    pub(super) fn summary(&self, topic: &str, block_id: u64) -> Option<BlockSummary> {
+        let blocks = self.blocks(topic)?;
+        let blocks = blocks.read().ok()?;
+        blocks
+            .iter()
+            .rev()
+            .find(|b| b.block_id == block_id)
+            .cloned()
+    }
+
+    /// Number of entries recorded for `topic`.
+    pub(super) fn entry_total(&self, topic: &str) -> u64 {
+        self.blocks(topic)
+            .and_then(|blocks| {
+                blocks
+                    .read()
+                    .ok()
+                    .map(|blocks| blocks.iter().map(|b| b.next_seq - b.first_seq).sum())
+            })
+            .unwrap_or(0)
+    }
+
+    /// Forgets the blocks of `topic` holding only entries below `floor`.
+    pub(super) fn discard_before(&self, topic: &str, floor: u64) {
+        if let Some(blocks) = self.blocks(topic)
+            && let Ok(mut blocks) = blocks.write()
+        {
+            blocks.retain(|b| b.next_seq > floor);
+        }
+    }
+
+    /// Forgets the blocks in `block_ids`, whichever topic they belong to. Returns the
+    /// topics left without blocks, with the sequence number that followed their last one.
+    pub(super) fn discard_blocks(&self, block_ids: &HashSet<u64>) -> Vec<(String, u64)> {
+        let mut emptied = Vec::new();
+        let Ok(map) = self.topics.read() else {
+            return emptied;
+        };
+        for (topic, blocks) in map.iter() {
+            let Ok(mut blocks) = blocks.write() else {
+                continue;
+            };
+            let next_seq = blocks.last().map(|b| b.next_seq);
+            blocks.retain(|b| !block_ids.contains(&b.block_id));
+            if let Some(next_seq) = next_seq
+                && blocks.is_empty()
+            {
+                emptied.push((topic.clone(), next_seq));
+            }
+        }
+        emptied
+    }
+
+    /// Renumbers the blocks of `topic` found in `moved`, keyed by their old id.
+    pub(super) fn relocate(&self, topic: &str, moved: &HashMap<u64, u64>) {
+        if let Some(blocks) = self.blocks(topic)
+            && let Ok(mut blocks) = blocks.write()
+        {
+            for block in blocks.iter_mut() {
+                if let Some(&id) = moved.get(&block.block_id) {
+                    block.block_id = id;
+                }
+            }
+        }
+    }
+
+    /// Drops `topic` once none of its blocks are left.
+    pub(super) fn forget_topic(&self, topic: &str) {
+        if let Ok(mut map) = self.topics.write()
+            && map
+                .get(topic)
+                .is_some_and(|blocks| blocks.read().is_ok_and(|b| b.is_empty()))
+        {
+            map.remove(topic);
+        }
+    }
+
+    /// Transactions with entries in a retained block.
+    pub(super) fn txn_ids(&self) -> HashSet<u64> {
+        let mut ids = HashSet::new();
+        if let Ok(map) = self.topics.read() {
+            for blocks in map.values() {
+                let Ok(blocks) = blocks.read() else {
+                    continue;
+                };
+                for txn in blocks.iter().flat_map(|b| &b.txns) {
+                    if let TxnMark::Data(id) = txn {
+                        ids.insert(*id);
+                    }
+                }
+            }
+        }
+        ids
+    }
+
+    /// Topics with at least one retained block.
+    pub(super) fn topics(&self) -> Vec<String> {
+        self.topics
+            .read()
+            .map(|m| {
+                m.iter()
+                    .filter(|(_, blocks)| blocks.read().is_ok_and(|b| !b.is_empty()))
+                    .map(|(topic, _)| topic.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub(super) fn summaries(&self, topic: &str) -> Vec<BlockSummary> {
+        self.blocks(topic)
+            .and_then(|blocks| blocks.read().ok().map(|b| b.clone()))
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,264 @@
+use super::reader::validate_group;
+use super::topic_signal::TopicSignal;
+use super::walrus_commit::{CommitToken, Lease};
+use super::walrus_seek::SeekTarget;
+use super::{ReadConsistency, Walrus};
+use crate::wal::block::Entry;
+use std::collections::VecDeque;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// When a subscription acknowledges the entries it has handed out.
+///
+/// An acknowledgement writes the read offset index the same way a checkpointing read
+/// does: every time under [`ReadConsistency::StrictlyAtOnce`], every `persist_every`
+/// acknowledged entries under [`ReadConsistency::AtLeastOnce`]. Entries that were read
+/// but not persisted are read again after a restart.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CheckpointPolicy {
+    /// Each entry is acknowledged when the next one is requested, that is once the
+    /// consumer is done with it.
+    #[default]
+    Auto,
+    /// Nothing is acknowledged until [`Subscription::ack`] is called.
+    Manual,
+    /// Entries are acknowledged in groups of this many, when the entry after the group
+    /// is requested. `EveryN(0)` behaves like `EveryN(1)`.
+    EveryN(u32),
+}
+
+/// Options for [`Walrus::subscribe`].
+#[derive(Clone, Debug)]
+pub struct SubscribeOptions {
+    pub(super) start: Option<SeekTarget>,
+    pub(super) checkpoint: CheckpointPolicy,
+    pub(super) max_batch_bytes: usize,
+    pub(super) group: Option<String>,
+    pub(super) idle_timeout: Option<Duration>,
+}
+
+impl Default for SubscribeOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SubscribeOptions {
+    pub fn new() -> Self {
+        Self {
+            start: None,
+            checkpoint: CheckpointPolicy::Auto,
+            max_batch_bytes: 1024 * 1024,
+            group: None,
+            idle_timeout: None,
+        }
+    }
+
+    /// Moves the cursor to `target` before the first read. By default the subscription
+    /// resumes from the cursor's current position.
+    pub fn start(mut self, target: SeekTarget) -> Self {
+        self.start = Some(target);
+        self
+    }
+
+    pub fn checkpoint(mut self, policy: CheckpointPolicy) -> Self {
+        self.checkpoint = policy;
+        self
+    }
+
+    /// Upper bound on the bytes fetched by one batch read. Defaults to 1MB.
+    pub fn max_batch_bytes(mut self, bytes: usize) -> Self {
+        self.max_batch_bytes = bytes.max(1);
+        self
+    }
+
+    /// Reads through `group`'s cursor instead of the topic's default cursor.
+    pub fn group(mut self, group: &str) -> Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    /// Ends the subscription once the topic stays caught up for this long. Without it
+    /// the subscription waits for new entries indefinitely.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+}
+
+/// Read-ahead buffer and acknowledgement bookkeeping shared by [`Subscription`] and
+/// the async stream.
+pub(super) struct SubscriptionState {
+    pub(super) group: Option<String>,
+    pub(super) topic: String,
+    pub(super) max_batch_bytes: usize,
+    pub(super) idle_timeout: Option<Duration>,
+    pub(super) signal: Arc<TopicSignal>,
+    policy: CheckpointPolicy,
+    consistency: ReadConsistency,
+    buffer: VecDeque<Entry>,
+    batch_end: Option<CommitToken>,
+    // Position right after the last entry handed out
+    handed_out_end: Option<CommitToken>,
+    unacked: u32,
+    acked_since_persist: u32,
+}
+
+impl SubscriptionState {
+    pub(super) fn open(wal: &Walrus, topic: &str, options: SubscribeOptions) -> io::Result<Self> {
+        if let Some(group) = &options.group {
+            validate_group(group)?;
+        }
+        if let Some(target) = options.start {
+            wal.seek_topic_for(options.group.as_deref(), topic, target)?;
+        }
+        Ok(Self::new(wal, topic, options))
+    }
+
+    fn new(wal: &Walrus, topic: &str, options: SubscribeOptions) -> Self {
+        Self {
+            signal: wal.reader.signals.for_topic(topic),
+            group: options.group,
+            topic: topic.to_string(),
+            max_batch_bytes: options.max_batch_bytes,
+            idle_timeout: options.idle_timeout,
+            policy: options.checkpoint,
+            consistency: wal.read_consistency,
+            buffer: VecDeque::new(),
+            batch_end: None,
+            handed_out_end: None,
+            unacked: 0,
+            acked_since_persist: 0,
+        }
+    }
+
+    pub(super) fn fill(&mut self, lease: Lease) {
+        self.buffer.extend(lease.entries);
+        self.batch_end = Some(lease.token);
+    }
+
+    pub(super) fn pop(&mut self) -> Option<Entry> {
+        let entry = self.buffer.pop_front()?;
+        self.handed_out_end = match self.buffer.front() {
+            Some(next) => Some(CommitToken::before(
+                self.group.as_deref(),
+                &self.topic,
+                next,
+            )),
+            None => self.batch_end.clone(),
+        };
+        self.unacked = self.unacked.saturating_add(1);
+        Some(entry)
+    }
+
+    /// Acknowledgement the checkpoint policy calls for before the next entry is handed
+    /// out. Returns the position to persist, if any.
+    pub(super) fn auto_ack(&mut self) -> Option<CommitToken> {
+        let due = match self.policy {
+            CheckpointPolicy::Auto => self.unacked > 0,
+            CheckpointPolicy::Manual => false,
+            CheckpointPolicy::EveryN(n) => self.unacked >= n.max(1),
+        };
+        if due { self.ack() } else { None }
+    }
+
+    /// Acknowledges every entry handed out so far. Returns the position to persist
+    /// when the read consistency calls for an index write.
+    pub(super) fn ack(&mut self) -> Option<CommitToken> {
+        if self.unacked == 0 {
+            return None;
+        }
+        self.acked_since_persist = self.acked_since_persist.saturating_add(self.unacked);
+        self.unacked = 0;
+        let persist = match self.consistency {
+            ReadConsistency::StrictlyAtOnce => true,
+            ReadConsistency::AtLeastOnce { persist_every } => {
+                self.acked_since_persist >= persist_every.max(1)
+            }
+        };
+        if !persist {
+            return None;
+        }
+        self.acked_since_persist = 0;
+        self.handed_out_end.clone()
+    }
+}
+
+/// Blocking iterator over a topic's entries, returned by [`Walrus::subscribe`].
+///
+/// Entries are fetched in batches through the topic's (or group's) cursor. When the
+/// topic is caught up, `next` waits for a writer to append to it, or returns `None`
+/// once the idle timeout passes. The subscription assumes it is the only reader of
+/// its cursor.
+pub struct Subscription<'a> {
+    wal: &'a Walrus,
+    state: SubscriptionState,
+}
+
+impl Subscription<'_> {
+    /// Acknowledges every entry returned so far. Only needed with
+    /// [`CheckpointPolicy::Manual`], or to acknowledge an unfinished group early.
+    pub fn ack(&mut self) -> io::Result<()> {
+        match self.state.ack() {
+            Some(token) => self.wal.persist_cursor_position(&token),
+            None => Ok(()),
+        }
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.state.topic
+    }
+}
+
+impl Iterator for Subscription<'_> {
+    type Item = io::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(token) = self.state.auto_ack()
+            && let Err(e) = self.wal.persist_cursor_position(&token)
+        {
+            return Some(Err(e));
+        }
+        let deadline = self
+            .state
+            .idle_timeout
+            .and_then(|timeout| Instant::now().checked_add(timeout));
+        loop {
+            if let Some(entry) = self.state.pop() {
+                return Some(Ok(entry));
+            }
+            // Taken before reading so an append racing with the read still wakes us
+            let seen = self.state.signal.generation();
+            match self.wal.lease_for(
+                self.state.group.as_deref(),
+                &self.state.topic,
+                self.state.max_batch_bytes,
+            ) {
+                Ok(Some(lease)) => self.state.fill(lease),
+                Ok(None) => {
+                    if !self.state.signal.wait_past(seen, deadline) {
+                        return None;
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl Walrus {
+    /// Subscribes to `topic`: the returned iterator yields its entries in order and
+    /// waits for new ones when caught up. Progress is checkpointed per the options'
+    /// [`CheckpointPolicy`].
+    pub fn subscribe(
+        &self,
+        topic: &str,
+        options: SubscribeOptions,
+    ) -> io::Result<Subscription<'_>> {
+        Ok(Subscription {
+            wal: self,
+            state: SubscriptionState::open(self, topic, options)?,
+        })
+    }
+}
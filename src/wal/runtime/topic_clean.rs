@@ -5,8 +5,8 @@ use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::mpsc;
-use std::sync::{Arc, RwLock};
-use std::thread;
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
 use std::time::Duration;
 
 #[derive(Archive, Deserialize, Serialize, Debug, Clone)]
@@ -75,6 +75,17 @@ impl CleanMarkerStore {
         Self::persist_map(&self.path, &guard)
     }
 
+    pub fn remove(&self, topic: &str) -> std::io::Result<()> {
+        let mut guard = self
+            .store
+            .write()
+            .map_err(|_| std::io::Error::other("store lock poisoned"))?;
+        if guard.remove(topic).is_none() {
+            return Ok(());
+        }
+        Self::persist_map(&self.path, &guard)
+    }
+
     fn persist_map(path: &str, map: &HashMap<String, CleanMarkerRecord>) -> std::io::Result<()> {
         let tmp_path = format!("{}.tmp", path);
         let bytes = rkyv::to_bytes::<_, 256>(map).map_err(|e| {
@@ -129,21 +140,31 @@ impl Default for TopicCleanState {
     }
 }
 
+enum PersistMsg {
+    Topic(String),
+    Stop,
+}
+
 pub struct TopicCleanTracker {
     states: RwLock<HashMap<String, Arc<TopicCleanState>>>,
     store: Arc<CleanMarkerStore>,
-    persist_tx: mpsc::Sender<String>,
+    persist_tx: mpsc::Sender<PersistMsg>,
+    persister: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl TopicCleanTracker {
     pub fn new(store: Arc<CleanMarkerStore>) -> Arc<Self> {
-        let (tx, rx) = mpsc::channel::<String>();
+        let (tx, rx) = mpsc::channel::<PersistMsg>();
         let tracker = Arc::new(Self {
             states: RwLock::new(HashMap::new()),
             store,
             persist_tx: tx,
+            persister: Mutex::new(None),
         });
-        Self::spawn_persister(&tracker, rx);
+        let handle = Self::spawn_persister(&tracker, rx);
+        if let Ok(mut guard) = tracker.persister.lock() {
+            *guard = Some(handle);
+        }
         tracker
     }
 
@@ -175,10 +196,18 @@ impl TopicCleanTracker {
             .unwrap_or(true)
     }
 
+    /// Drops the marker of a deleted topic, in memory and on disk.
+    pub fn forget(&self, topic: &str) -> std::io::Result<()> {
+        if let Ok(mut guard) = self.states.write() {
+            guard.remove(topic);
+        }
+        self.store.remove(topic)
+    }
+
     fn update_state(&self, topic: &str, desired_clean: bool) {
         let state = self.get_or_insert_state(topic);
         if state.update(desired_clean).is_some() {
-            let _ = self.persist_tx.send(topic.to_string());
+            let _ = self.persist_tx.send(PersistMsg::Topic(topic.to_string()));
         }
     }
 
@@ -198,16 +227,40 @@ impl TopicCleanTracker {
             .clone()
     }
 
-    fn spawn_persister(tracker: &Arc<Self>, rx: mpsc::Receiver<String>) {
+    /// Stops the persister thread and writes every marker, so the file on disk
+    /// matches memory. Markers changed afterwards are no longer persisted.
+    pub fn close(&self) -> std::io::Result<()> {
+        let _ = self.persist_tx.send(PersistMsg::Stop);
+        let handle = self
+            .persister
+            .lock()
+            .ok()
+            .and_then(|mut guard| guard.take());
+        if let Some(handle) = handle {
+            handle
+                .join()
+                .map_err(|_| std::io::Error::other("clean marker persister panicked"))?;
+        }
+        self.persist_all()
+    }
+
+    fn spawn_persister(tracker: &Arc<Self>, rx: mpsc::Receiver<PersistMsg>) -> JoinHandle<()> {
         let weak = Arc::downgrade(tracker);
         thread::spawn(move || {
             let mut pending = HashSet::new();
-            loop {
+            let mut stopping = false;
+            while !stopping {
                 match rx.recv_timeout(Duration::from_millis(5)) {
-                    Ok(topic) => {
-                        pending.insert(topic);
-                        while let Ok(t) = rx.try_recv() {
-                            pending.insert(t);
+                    Ok(msg) => {
+                        let mut next = Some(msg);
+                        while let Some(msg) = next {
+                            match msg {
+                                PersistMsg::Topic(topic) => {
+                                    pending.insert(topic);
+                                }
+                                PersistMsg::Stop => stopping = true,
+                            }
+                            next = rx.try_recv().ok();
                         }
                     }
                     Err(mpsc::RecvTimeoutError::Timeout) => {}
@@ -225,7 +278,7 @@ impl TopicCleanTracker {
                 }
                 pending.clear();
             }
-        });
+        })
     }
 
     fn persist_topics(&self, topics: &HashSet<String>) -> std::io::Result<()> {
@@ -245,6 +298,10 @@ impl TopicCleanTracker {
 
     #[cfg(test)]
     pub fn force_flush_for_test(&self) -> std::io::Result<()> {
+        self.persist_all()
+    }
+
+    fn persist_all(&self) -> std::io::Result<()> {
         let snapshot = {
             let guard = self.states.read().unwrap();
             guard
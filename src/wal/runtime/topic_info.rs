@@ -0,0 +1,205 @@
+use super::Walrus;
+use super::reader::{TAIL_FLAG, cursor_key, validate_group};
+use crate::wal::block::Block;
+use std::io;
+
+/// One block of a topic, as reported by [`Walrus::topic_info`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockInfo {
+    pub id: u64,
+    /// WAL file holding the block.
+    pub file: String,
+    /// Bytes written to the block, entry metadata prefixes included.
+    pub used: u64,
+}
+
+impl BlockInfo {
+    fn new(block: &Block, used: u64) -> Self {
+        Self {
+            id: block.id,
+            file: block.file_path.clone(),
+            used,
+        }
+    }
+}
+
+/// Where a cursor reads next: a block and a byte offset within it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReaderPosition {
+    pub block_id: u64,
+    pub offset: u64,
+}
+
+/// Snapshot of a topic returned by [`Walrus::topic_info`]. The fields are read one after
+/// the other, so concurrent appends and reads may leave them slightly out of step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TopicInfo {
+    pub topic: String,
+    /// Sealed blocks still readable, oldest first.
+    pub blocks: Vec<BlockInfo>,
+    /// The writer's active block; its `used` is the writer's tail offset. `None` until
+    /// the topic is appended to in this process.
+    pub active_block: Option<BlockInfo>,
+    /// Next read position of the cursor; `None` when there is nothing to read from.
+    pub reader_position: Option<ReaderPosition>,
+    /// Entries the cursor has not consumed yet.
+    pub unread_entries: u64,
+    /// Bytes between the cursor and the writer's tail, entry metadata prefixes included.
+    pub unread_bytes: u64,
+    /// Sequence number the next append to the topic gets.
+    pub next_seq: u64,
+    /// Whether a batch or async append to the topic is in flight.
+    pub batch_write_pending: bool,
+}
+
+impl Walrus {
+    /// Topics with entries still on disk, sorted by name.
+    pub fn list_topics(&self) -> Vec<String> {
+        let mut topics = self.reader.seek_index.topics();
+        topics.sort();
+        topics
+    }
+
+    /// Describes `topic` from the point of view of its default cursor: its blocks, where
+    /// the cursor stands and how much is left to read. Unknown topics come back empty.
+    pub fn topic_info(&self, topic: &str) -> io::Result<TopicInfo> {
+        self.topic_info_for(None, topic)
+    }
+
+    /// Same as [`Walrus::topic_info`], for `group`'s cursor on the topic.
+    pub fn topic_info_as(&self, group: &str, topic: &str) -> io::Result<TopicInfo> {
+        validate_group(group)?;
+        self.topic_info_for(Some(group), topic)
+    }
+
+    fn topic_info_for(&self, group: Option<&str>, topic: &str) -> io::Result<TopicInfo> {
+        let writer = self
+            .writers
+            .read()
+            .map_err(|_| io::Error::other("writers read lock poisoned"))?
+            .get(topic)
+            .cloned();
+        let active = match &writer {
+            Some(w) => Some(w.snapshot_block()?),
+            None => None,
+        };
+        let next_seq = match &writer {
+            Some(w) => w.written_tail()?.0,
+            None => self.recovered_next_seq(topic).unwrap_or_else(|| {
+                self.reader
+                    .seek_index
+                    .summaries(topic)
+                    .last()
+                    .map_or(0, |s| s.next_seq)
+            }),
+        };
+
+        // A cursor that does not exist yet would copy the default cursor's chain
+        let key = cursor_key(group, topic);
+        let (own, default) = {
+            let map = self
+                .reader
+                .data
+                .read()
+                .map_err(|_| io::Error::other("reader map read lock poisoned"))?;
+            (map.get(&key).cloned(), map.get(topic).cloned())
+        };
+        let dropped = self.reader.dropped_blocks(topic);
+        let (chain, in_memory) = match own.as_ref().or(default.as_ref()) {
+            Some(arc) => {
+                let info = arc
+                    .read()
+                    .map_err(|_| io::Error::other("col info read lock poisoned"))?;
+                let position = (own.is_some() && info.hydrated_from_index).then_some((
+                    info.cur_block_idx,
+                    info.cur_block_offset,
+                    (info.tail_block_id, info.tail_offset),
+                ));
+                (info.chain.clone(), position)
+            }
+            None => (Vec::new(), None),
+        };
+        let (idx, offset, tail) = match in_memory {
+            Some(position) => position,
+            None => self.persisted_position(&key, &chain, dropped),
+        };
+
+        let mut unread_bytes: u64 = chain.iter().skip(idx).map(|b| b.used).sum();
+        let reader_position = if let Some(block) = chain.get(idx) {
+            unread_bytes -= offset.min(block.used);
+            Some(ReaderPosition {
+                block_id: block.id,
+                offset,
+            })
+        } else {
+            match &active {
+                Some((block, _)) if tail.0 == block.id => Some(ReaderPosition {
+                    block_id: block.id,
+                    offset: tail.1,
+                }),
+                Some((block, _)) => Some(ReaderPosition {
+                    block_id: block.id,
+                    offset: 0,
+                }),
+                None => None,
+            }
+        };
+        if let Some((block, written)) = &active {
+            let read = match reader_position {
+                Some(pos) if pos.block_id == block.id => pos.offset.min(*written),
+                _ => 0,
+            };
+            unread_bytes += written - read;
+        }
+
+        Ok(TopicInfo {
+            topic: topic.to_string(),
+            blocks: chain
+                .iter()
+                .skip(dropped)
+                .map(|b| BlockInfo::new(b, b.used))
+                .collect(),
+            active_block: active
+                .as_ref()
+                .map(|(block, written)| BlockInfo::new(block, *written)),
+            reader_position,
+            unread_entries: match group {
+                Some(group) => self.get_topic_entry_count_as(group, topic),
+                None => self.get_topic_entry_count(topic),
+            },
+            unread_bytes,
+            next_seq,
+            batch_write_pending: writer.as_ref().is_some_and(|w| w.batch_in_flight()),
+        })
+    }
+
+    // Where a cursor that has not read since startup resumes, resolved the way the first
+    // read through it will: (chain index, offset, tail block id and offset)
+    fn persisted_position(
+        &self,
+        key: &str,
+        chain: &[Block],
+        dropped: usize,
+    ) -> (usize, u64, (u64, u64)) {
+        let start = (dropped.min(chain.len()), 0, (0, 0));
+        let Ok(idx_guard) = self.read_offset_index.read() else {
+            return start;
+        };
+        let Some(pos) = idx_guard.get(key) else {
+            return start;
+        };
+        if pos.cur_block_idx & TAIL_FLAG == 0 {
+            let idx = (pos.cur_block_idx as usize).min(chain.len());
+            let offset = chain
+                .get(idx)
+                .map_or(0, |b| pos.cur_block_offset.min(b.used));
+            return (idx, offset, (0, 0));
+        }
+        let tail_id = pos.cur_block_idx & !TAIL_FLAG;
+        match chain.iter().position(|b| b.id == tail_id) {
+            Some(at) => (at, pos.cur_block_offset.min(chain[at].used), (0, 0)),
+            None if chain.is_empty() => (0, 0, (tail_id, pos.cur_block_offset)),
+            None => start,
+        }
+    }
+}
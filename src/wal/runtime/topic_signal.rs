@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::task::Waker;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+/// Per-topic change counter. Writers bump it after appending or sealing a block; blocking
+/// reads remember the value, find nothing to read, then wait for it to move.
+pub(super) struct TopicSignal {
+    generation: AtomicU64,
+    // Blocked readers plus registered wakers. Writers only take the lock when it is
+    // non-zero; waiters count themselves before checking the generation, so a bump
+    // either finds them counted or is seen by their check.
+    waiters: AtomicUsize,
+    // Async waiters, woken and dropped on the next change
+    wakers: Mutex<Vec<Waker>>,
+    changed: Condvar,
+}
+
+impl TopicSignal {
+    fn new() -> Self {
+        Self {
+            generation: AtomicU64::new(0),
+            waiters: AtomicUsize::new(0),
+            wakers: Mutex::new(Vec::new()),
+            changed: Condvar::new(),
+        }
+    }
+
+    pub(super) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    pub(super) fn notify(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        if self.waiters.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        // Taking the lock waits for a counted reader to actually block on the condvar
+        let wakers = match self.wakers.lock() {
+            Ok(mut wakers) => std::mem::take(&mut *wakers),
+            Err(_) => return,
+        };
+        self.waiters.fetch_sub(wakers.len(), Ordering::SeqCst);
+        self.changed.notify_all();
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Blocks until the generation differs from `seen` or `deadline` passes (never, for
+    /// `None`). Returns whether it changed.
+    pub(super) fn wait_past(&self, seen: u64, deadline: Option<Instant>) -> bool {
+        let Ok(mut wakers) = self.wakers.lock() else {
+            return false;
+        };
+        self.waiters.fetch_add(1, Ordering::SeqCst);
+        let changed = loop {
+            if self.generation() != seen {
+                break true;
+            }
+            let waited = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break false;
+                    }
+                    self.changed
+                        .wait_timeout(wakers, deadline - now)
+                        .map(|(wakers, _)| wakers)
+                        .map_err(|_| ())
+                }
+                None => self.changed.wait(wakers).map_err(|_| ()),
+            };
+            wakers = match waited {
+                Ok(wakers) => wakers,
+                Err(_) => {
+                    self.waiters.fetch_sub(1, Ordering::SeqCst);
+                    return false;
+                }
+            };
+        };
+        self.waiters.fetch_sub(1, Ordering::SeqCst);
+        changed
+    }
+
+    /// Future-style counterpart of [`TopicSignal::wait_past`]: ready once the generation
+    /// differs from `seen`, otherwise registers `cx`'s waker for the next change.
+    #[cfg(feature = "async")]
+    pub(super) fn poll_past(&self, seen: u64, cx: &mut Context<'_>) -> Poll<()> {
+        if self.generation() != seen {
+            return Poll::Ready(());
+        }
+        let Ok(mut wakers) = self.wakers.lock() else {
+            return Poll::Ready(());
+        };
+        if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            self.waiters.fetch_add(1, Ordering::SeqCst);
+            wakers.push(cx.waker().clone());
+        }
+        // Checked again once counted, as a bump before that skipped the wakers
+        if self.generation() != seen {
+            return Poll::Ready(());
+        }
+        Poll::Pending
+    }
+}
+
+/// The signals of every topic, created on first use by either side.
+pub(super) struct TopicSignals {
+    topics: RwLock<HashMap<String, Arc<TopicSignal>>>,
+}
+
+impl TopicSignals {
+    pub(super) fn new() -> Self {
+        Self {
+            topics: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub(super) fn for_topic(&self, col: &str) -> Arc<TopicSignal> {
+        if let Ok(topics) = self.topics.read()
+            && let Some(signal) = topics.get(col)
+        {
+            return signal.clone();
+        }
+        match self.topics.write() {
+            Ok(mut topics) => topics
+                .entry(col.to_string())
+                .or_insert_with(|| Arc::new(TopicSignal::new()))
+                .clone(),
+            // A poisoned map only costs the wakeups; readers still time out
+            Err(_) => Arc::new(TopicSignal::new()),
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use super::Walrus;
+use super::writer::StagedRecord;
+use crate::wal::block::{TxnMark, encode_headers};
+use crate::wal::config::debug_print;
+use std::collections::HashSet;
+use std::io;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// A commit or abort marker is one empty record in each topic of the transaction
+const MARKER: &[&[u8]] = &[&[]];
+
+/// What a reader does with a record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Visibility {
+    Visible,
+    /// Passed over like a consumed entry: a transaction marker or an aborted entry.
+    Hidden,
+    /// Entry of a transaction still in flight; readers stop in front of it.
+    Pending,
+}
+
+/// Fate of the transactions of one instance. A transactional entry is committed
+/// unless its transaction is still open or was aborted.
+pub(super) struct TxnRegistry {
+    next_id: AtomicU64,
+    state: RwLock<TxnState>,
+}
+
+#[derive(Default)]
+struct TxnState {
+    open: HashSet<u64>,
+    aborted: HashSet<u64>,
+}
+
+impl TxnRegistry {
+    pub(super) fn new() -> Self {
+        Self {
+            next_id: AtomicU64::new(1),
+            state: RwLock::new(TxnState::default()),
+        }
+    }
+
+    fn begin(&self) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut state) = self.state.write() {
+            state.open.insert(id);
+        }
+        id
+    }
+
+    fn finish(&self, id: u64, committed: bool) {
+        if let Ok(mut state) = self.state.write() {
+            state.open.remove(&id);
+            if !committed {
+                state.aborted.insert(id);
+            }
+        }
+    }
+
+    pub(super) fn visibility(&self, txn: TxnMark) -> Visibility {
+        let id = match txn {
+            TxnMark::None => return Visibility::Visible,
+            TxnMark::Commit(_) | TxnMark::Abort(_) => return Visibility::Hidden,
+            TxnMark::Data(id) => id,
+        };
+        self.state.read().map_or(Visibility::Pending, |state| {
+            if state.open.contains(&id) {
+                Visibility::Pending
+            } else if state.aborted.contains(&id) {
+                Visibility::Hidden
+            } else {
+                Visibility::Visible
+            }
+        })
+    }
+
+    /// Forgets the aborted transactions `live` no longer holds entries of, as readers
+    /// cannot come across those entries any more.
+    fn prune_aborted(&self, live: impl FnOnce() -> HashSet<u64>) {
+        if self
+            .state
+            .read()
+            .is_ok_and(|state| state.aborted.is_empty())
+        {
+            return;
+        }
+        let live = live();
+        if let Ok(mut state) = self.state.write() {
+            state.aborted.retain(|id| live.contains(id));
+        }
+    }
+
+    /// Replaces the state with what recovery found: no transaction is open any more, the
+    /// ones without a commit marker are aborted, and new ones get ids past every id seen.
+    pub(super) fn recover(&self, scan: TxnScan) {
+        self.next_id.fetch_max(scan.max_id + 1, Ordering::Relaxed);
+        if let Ok(mut state) = self.state.write() {
+            *state = TxnState {
+                open: HashSet::new(),
+                aborted: scan.entries.difference(&scan.committed).copied().collect(),
+            };
+        }
+    }
+}
+
+/// Transactional records seen by recovery.
+#[derive(Default)]
+pub(super) struct TxnScan {
+    entries: HashSet<u64>,
+    committed: HashSet<u64>,
+    max_id: u64,
+}
+
+impl TxnScan {
+    pub(super) fn note(&mut self, txn: TxnMark) {
+        let id = match txn {
+            TxnMark::None => return,
+            TxnMark::Data(id) => {
+                self.entries.insert(id);
+                id
+            }
+            TxnMark::Commit(id) => {
+                self.committed.insert(id);
+                id
+            }
+            TxnMark::Abort(id) => id,
+        };
+        self.max_id = self.max_id.max(id);
+    }
+}
+
+/// Entries appended to any number of topics that readers see all at once, on
+/// [`Transaction::commit`], or never. Dropping the handle aborts the transaction.
+pub struct Transaction<'a> {
+    wal: &'a Walrus,
+    id: u64,
+    // Topics appended to so far, in the order of their first append
+    topics: Vec<String>,
+    finished: bool,
+}
+
+impl Walrus {
+    /// Drops the aborted transactions whose entries are all in discarded or reclaimed
+    /// blocks.
+    pub(super) fn prune_aborted_txns(&self) {
+        self.txns.prune_aborted(|| self.reader.seek_index.txn_ids());
+    }
+
+    /// Starts a transaction. Entries appended through it are written right away but
+    /// stay invisible to readers until [`Transaction::commit`]; an abort, dropping the
+    /// handle or a crash discards them.
+    ///
+    /// Readers stop in front of the first entry of a transaction in flight, so entries
+    /// appended to its topics after that one, inside the transaction or not, become
+    /// readable once it is decided. Committing writes a commit marker to each topic of
+    /// the transaction, atomically like [`Walrus::batch_append_multi`], and aborting
+    /// writes abort markers. Markers take a sequence number and, like aborted entries,
+    /// count towards the topic's entry count until a reader passes over them; readers
+    /// never return either. Recovery aborts every transaction without a commit marker.
+    pub fn begin_transaction(&self) -> Transaction<'_> {
+        Transaction {
+            wal: self,
+            id: self.txns.begin(),
+            topics: Vec::new(),
+            finished: false,
+        }
+    }
+
+    fn append_in_txn(&self, id: u64, topic: &str, data: &[u8], headers: &[u8]) -> io::Result<u64> {
+        self.mark_topic_dirty(topic);
+        let writer = self.get_or_create_writer(topic)?;
+        let mut writes = writer.stage()?;
+        let seq = match writes.push_in_txn(data, headers, TxnMark::Data(id)) {
+            Ok(seq) => seq,
+            Err(e) => {
+                writes.abort();
+                return Err(e);
+            }
+        };
+        let records: Vec<&StagedRecord> = writes.records().iter().collect();
+        let outcome = self
+            .write_ring
+            .write(&records)
+            .and_then(|()| writes.sealed().iter().try_for_each(|b| b.mmap.flush()));
+        drop(records);
+        if let Err(e) = outcome {
+            writes.abort();
+            return Err(e);
+        }
+        // Fsynced by the commit, before its markers
+        writes.commit(false);
+        self.increment_topic_entry_count(topic, 1);
+        Ok(seq)
+    }
+
+    fn commit_txn(&self, id: u64, topics: &[String]) -> io::Result<()> {
+        let result = self.write_commit_markers(id, topics);
+        self.txns.finish(id, result.is_ok());
+        self.notify_topics(topics);
+        debug_print!(
+            "[txn] finished: id={}, topics={}, committed={}",
+            id,
+            topics.len(),
+            result.is_ok()
+        );
+        result
+    }
+
+    fn write_commit_markers(&self, id: u64, topics: &[String]) -> io::Result<()> {
+        if topics.is_empty() {
+            return Ok(());
+        }
+        // The entries must be durable before the markers that make them visible
+        for topic in topics {
+            self.get_or_create_writer(topic)?.sync()?;
+        }
+        let batches: Vec<(&str, &[&[u8]])> = topics.iter().map(|t| (t.as_str(), MARKER)).collect();
+        self.append_multi(&batches, TxnMark::Commit(id)).map(|_| ())
+    }
+
+    fn abort_txn(&self, id: u64, topics: &[String]) -> io::Result<()> {
+        // Aborted from here on: recovery treats a transaction without commit markers the same
+        self.txns.finish(id, false);
+        self.notify_topics(topics);
+        debug_print!("[txn] aborted: id={}, topics={}", id, topics.len());
+        if topics.is_empty() {
+            return Ok(());
+        }
+        let batches: Vec<(&str, &[&[u8]])> = topics.iter().map(|t| (t.as_str(), MARKER)).collect();
+        self.append_multi(&batches, TxnMark::Abort(id)).map(|_| ())
+    }
+
+    // Readers stopped in front of a decided transaction's entries look at them again
+    fn notify_topics(&self, topics: &[String]) {
+        for topic in topics {
+            self.reader.signals.for_topic(topic).notify();
+        }
+    }
+}
+
+impl Transaction<'_> {
+    /// Identifier of the transaction, unique within the log.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Appends one entry to `topic` as part of the transaction and returns its sequence
+    /// number.
+    pub fn append(&mut self, topic: &str, data: &[u8]) -> io::Result<u64> {
+        self.append_with_headers(topic, data, &[])
+    }
+
+    /// Like [`Transaction::append`], storing `headers` with the entry.
+    pub fn append_with_headers(
+        &mut self,
+        topic: &str,
+        data: &[u8],
+        headers: &[(&str, &[u8])],
+    ) -> io::Result<u64> {
+        let encoded = encode_headers(headers)?;
+        let seq = self.wal.append_in_txn(self.id, topic, data, &encoded)?;
+        if !self.topics.iter().any(|t| t == topic) {
+            self.topics.push(topic.to_string());
+        }
+        Ok(seq)
+    }
+
+    /// Makes every entry of the transaction readable. The entries are fsynced, then the
+    /// commit markers written and fsynced in one atomic step; if that fails the
+    /// transaction is aborted instead and the error returned.
+    pub fn commit(mut self) -> io::Result<()> {
+        self.finished = true;
+        self.wal.commit_txn(self.id, &self.topics)
+    }
+
+    /// Discards every entry of the transaction. Readers skip them from now on even if
+    /// writing the abort markers fails.
+    pub fn abort(mut self) -> io::Result<()> {
+        self.finished = true;
+        self.wal.abort_txn(self.id, &self.topics)
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.wal.abort_txn(self.id, &self.topics);
+        }
+    }
+}
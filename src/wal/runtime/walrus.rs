@@ -1,21 +1,30 @@
-use crate::wal::block::{Block, Metadata};
-use crate::wal::config::{
-    DEFAULT_BLOCK_SIZE, FsyncSchedule, MAX_FILE_SIZE, PREFIX_META_SIZE, debug_print,
-};
+use crate::wal::block::Block;
+use crate::wal::config::{FsyncSchedule, Geometry, debug_print, legacy_backend};
 use crate::wal::paths::WalPathManager;
-use crate::wal::storage::{SharedMmapKeeper, set_fsync_schedule};
+use crate::wal::storage::StorageOptions;
 use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::path::Path;
+use std::sync::atomic::AtomicU64;
 use std::sync::mpsc;
 use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
 
 use super::WalIndex;
-use super::allocator::{BlockAllocator, BlockStateTracker, FileStateTracker, flush_check};
+use super::allocator::{BlockAllocator, StateTracker};
 use super::background::start_background_workers;
-use super::reader::Reader;
+use super::builder::WalrusBuilder;
+use super::group_commit::{GroupCommit, WriteRing};
+use super::manifest::SealedBlock;
+use super::reader::{ColReaderInfo, Reader, TAIL_FLAG, floor_key, split_cursor_key};
+use super::reclaim::Reclaimer;
+use super::recovery::{ScannedFile, scan_block, scan_files, stub};
+use super::retention::RetentionPolicy;
+use super::seek_index::BlockSummary;
 use super::topic_clean::{CleanMarkerStore, TopicCleanTracker};
+use super::transaction::{TxnRegistry, TxnScan};
+use super::walrus_multi::roll_back_unfinished_batches;
 use super::writer::Writer;
-use rkyv::Deserialize;
 
 #[derive(Clone, Copy, Debug)]
 pub enum ReadConsistency {
@@ -26,17 +35,36 @@ pub enum ReadConsistency {
 pub struct Walrus {
     pub(super) allocator: Arc<BlockAllocator>,
     pub(super) reader: Arc<Reader>,
-    pub(super) writers: RwLock<HashMap<String, Arc<Writer>>>,
+    pub(super) writers: Arc<RwLock<HashMap<String, Arc<Writer>>>>,
     pub(super) fsync_tx: Arc<mpsc::Sender<String>>,
     pub(super) read_offset_index: Arc<RwLock<WalIndex>>,
     pub(super) read_consistency: ReadConsistency,
     pub(super) fsync_schedule: FsyncSchedule,
     pub(super) paths: Arc<WalPathManager>,
-    topic_clean_tracker: Arc<TopicCleanTracker>,
-    topic_entry_counts: RwLock<HashMap<String, u64>>,
+    pub(super) topic_clean_tracker: Arc<TopicCleanTracker>,
+    // Flushes and reclaims in the background; taken when the instance shuts down
+    pub(super) background: Option<JoinHandle<()>>,
+    topic_entry_counts: Arc<RwLock<HashMap<String, u64>>>,
+    // Unread entries per topic for each named consumer group
+    group_entry_counts: Arc<RwLock<HashMap<String, HashMap<String, u64>>>>,
+    // Next sequence number per topic as rebuilt by recovery; consumed when the writer is created
+    recovered_next_seq: Arc<RwLock<HashMap<String, u64>>>,
+    // Set when appends go through group commit
+    pub(super) group_commit: Option<Arc<GroupCommit>>,
+    // Writes the records of multi-topic batches and transactions
+    pub(super) write_ring: Arc<WriteRing>,
+    pub(super) txns: Arc<TxnRegistry>,
+    // Names the intent files of multi-topic batches; recovery removes those left behind
+    pub(super) next_intent: Arc<AtomicU64>,
 }
 
 impl Walrus {
+    /// Starts a [`WalrusBuilder`] for instances that need more than the defaults,
+    /// such as a custom block size or file size.
+    pub fn builder() -> WalrusBuilder {
+        WalrusBuilder::new()
+    }
+
     pub fn new() -> std::io::Result<Self> {
         Self::with_consistency(ReadConsistency::StrictlyAtOnce)
     }
@@ -49,8 +77,11 @@ impl Walrus {
         mode: ReadConsistency,
         fsync_schedule: FsyncSchedule,
     ) -> std::io::Result<Self> {
-        let paths = Arc::new(WalPathManager::default());
-        Self::with_paths(paths, mode, fsync_schedule)
+        Self::builder()
+            .backend(legacy_backend())
+            .consistency(mode)
+            .fsync(fsync_schedule)
+            .open()
     }
 
     pub fn new_for_key(key: &str) -> std::io::Result<Self> {
@@ -66,44 +97,104 @@ impl Walrus {
         mode: ReadConsistency,
         fsync_schedule: FsyncSchedule,
     ) -> std::io::Result<Self> {
-        let paths = WalPathManager::for_key(key);
-        Self::with_paths(Arc::new(paths), mode, fsync_schedule)
+        Self::builder()
+            .backend(legacy_backend())
+            .namespace(key)
+            .consistency(mode)
+            .fsync(fsync_schedule)
+            .open()
     }
 
-    fn with_paths(
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn with_paths(
         paths: Arc<WalPathManager>,
         mode: ReadConsistency,
         fsync_schedule: FsyncSchedule,
+        geometry: Geometry,
+        storage: StorageOptions,
+        group_commit: bool,
+        retention: Option<RetentionPolicy>,
+        compact_on_open: Option<f64>,
     ) -> std::io::Result<Self> {
         debug_print!("[walrus] new");
 
-        // Store the fsync schedule globally for SharedMmap::new to access
-        set_fsync_schedule(fsync_schedule);
-
-        let allocator = Arc::new(BlockAllocator::new(paths.clone())?);
-        let reader = Arc::new(Reader::new());
-        let tx_arc = start_background_workers(fsync_schedule);
+        // Block and file state, and the reclaimer, belong to this instance alone
+        let (reclaimer, reclaim_rx) = Reclaimer::channel();
+        let tracker = Arc::new(StateTracker::new(reclaimer));
+        let allocator = Arc::new(BlockAllocator::new(
+            paths.clone(),
+            geometry,
+            storage,
+            tracker.clone(),
+        )?);
+        let reader = Arc::new(Reader::new(tracker.clone()));
+        reader.retention.set_default(retention);
         let clean_store = Arc::new(CleanMarkerStore::new_in(&paths, "topic_clean")?);
+        let idx = WalIndex::new_in(&paths, "read_offset_idx")?;
+
+        // Threads start once nothing can fail before the instance exists to stop them
+        let (tx_arc, background) =
+            start_background_workers(fsync_schedule, storage, tracker, reclaim_rx);
         let topic_clean_tracker = TopicCleanTracker::new(clean_store.clone());
         topic_clean_tracker.hydrate(clean_store.snapshot());
-
-        let idx = WalIndex::new_in(&paths, "read_offset_idx")?;
         let instance = Walrus {
             allocator,
             reader,
-            writers: RwLock::new(HashMap::new()),
+            writers: Arc::new(RwLock::new(HashMap::new())),
             fsync_tx: tx_arc,
             read_offset_index: Arc::new(RwLock::new(idx)),
             read_consistency: mode,
             fsync_schedule,
             paths,
             topic_clean_tracker,
-            topic_entry_counts: RwLock::new(HashMap::new()),
+            background: Some(background),
+            topic_entry_counts: Arc::new(RwLock::new(HashMap::new())),
+            group_entry_counts: Arc::new(RwLock::new(HashMap::new())),
+            recovered_next_seq: Arc::new(RwLock::new(HashMap::new())),
+            group_commit: group_commit
+                .then(|| Arc::new(GroupCommit::new(storage.backend, fsync_schedule))),
+            write_ring: Arc::new(WriteRing::new(storage.backend)),
+            txns: Arc::new(TxnRegistry::new()),
+            next_intent: Arc::new(AtomicU64::new(0)),
         };
         instance.startup_chore()?;
+        instance.apply_due_retention();
+        if let Some(max_live_ratio) = compact_on_open {
+            instance.compact_files(max_live_ratio)?;
+        }
+        // From here on the background thread applies retention
+        instance
+            .allocator
+            .tracker()
+            .reclaimer()
+            .attach(instance.handle());
         Ok(instance)
     }
 
+    // Another handle on the same state that does not own the background thread, so
+    // dropping it shuts nothing down
+    fn handle(&self) -> Walrus {
+        Walrus {
+            allocator: self.allocator.clone(),
+            reader: self.reader.clone(),
+            writers: self.writers.clone(),
+            fsync_tx: self.fsync_tx.clone(),
+            read_offset_index: self.read_offset_index.clone(),
+            read_consistency: self.read_consistency,
+            fsync_schedule: self.fsync_schedule,
+            paths: self.paths.clone(),
+            topic_clean_tracker: self.topic_clean_tracker.clone(),
+            background: None,
+            topic_entry_counts: self.topic_entry_counts.clone(),
+            group_entry_counts: self.group_entry_counts.clone(),
+            recovered_next_seq: self.recovered_next_seq.clone(),
+            group_commit: self.group_commit.clone(),
+            write_ring: self.write_ring.clone(),
+            txns: self.txns.clone(),
+            next_intent: self.next_intent.clone(),
+        }
+    }
+
     pub fn mark_topic_dirty(&self, topic: &str) {
         self.topic_clean_tracker.mark_dirty(topic);
     }
@@ -124,6 +215,16 @@ impl Walrus {
             .unwrap_or(0)
     }
 
+    /// Unread entries of `topic` for consumer group `group`. A group that has not
+    /// read the topic yet has every retained entry ahead of it.
+    pub fn get_topic_entry_count_as(&self, group: &str, topic: &str) -> u64 {
+        self.group_entry_counts
+            .read()
+            .ok()
+            .and_then(|m| m.get(topic).and_then(|g| g.get(group)).copied())
+            .unwrap_or_else(|| self.reader.seek_index.entry_total(topic))
+    }
+
     pub fn get_topic_entry_counts(&self) -> HashMap<String, u64> {
         self.topic_entry_counts
             .read()
@@ -180,16 +281,111 @@ impl Walrus {
             let entry = guard.entry(topic.to_string()).or_insert(0);
             *entry = entry.saturating_add(delta);
         }
+        if let Ok(mut guard) = self.group_entry_counts.write()
+            && let Some(groups) = guard.get_mut(topic)
+        {
+            for count in groups.values_mut() {
+                *count = count.saturating_add(delta);
+            }
+        }
+    }
+
+    pub(super) fn set_entry_count(&self, group: Option<&str>, topic: &str, count: u64) {
+        match group {
+            None => {
+                if let Ok(mut guard) = self.topic_entry_counts.write() {
+                    guard.insert(topic.to_string(), count);
+                }
+            }
+            Some(group) => {
+                if let Ok(mut guard) = self.group_entry_counts.write() {
+                    guard
+                        .entry(topic.to_string())
+                        .or_default()
+                        .insert(group.to_string(), count);
+                }
+            }
+        }
+    }
+
+    /// Next sequence number recovery found for `topic`, while no writer has taken it.
+    pub(super) fn recovered_next_seq(&self, topic: &str) -> Option<u64> {
+        self.recovered_next_seq
+            .read()
+            .ok()
+            .and_then(|m| m.get(topic).copied())
+    }
+
+    /// Drops the entry counts and clean marker of a deleted topic. When `resume_seq` is
+    /// set, the topic's next writer continues from it.
+    pub(super) fn forget_topic_state(
+        &self,
+        topic: &str,
+        resume_seq: Option<u64>,
+    ) -> std::io::Result<()> {
+        if let Some(seq) = resume_seq
+            && let Ok(mut guard) = self.recovered_next_seq.write()
+        {
+            guard.insert(topic.to_string(), seq);
+        }
+        if let Ok(mut guard) = self.topic_entry_counts.write() {
+            guard.remove(topic);
+        }
+        if let Ok(mut guard) = self.group_entry_counts.write() {
+            guard.remove(topic);
+        }
+        self.topic_clean_tracker.forget(topic)
     }
 
-    pub(super) fn decrement_topic_entry_count(&self, topic: &str, delta: u64) {
+    pub(super) fn decrement_entry_count(&self, group: Option<&str>, topic: &str, delta: u64) {
         if delta == 0 {
             return;
         }
-        if let Ok(mut guard) = self.topic_entry_counts.write() {
-            let entry = guard.entry(topic.to_string()).or_insert(0);
-            *entry = entry.saturating_sub(delta);
+        match group {
+            None => {
+                if let Ok(mut guard) = self.topic_entry_counts.write() {
+                    let entry = guard.entry(topic.to_string()).or_insert(0);
+                    *entry = entry.saturating_sub(delta);
+                }
+            }
+            Some(group) => {
+                if let Ok(mut guard) = self.group_entry_counts.write() {
+                    let entry = guard
+                        .entry(topic.to_string())
+                        .or_default()
+                        .entry(group.to_string())
+                        .or_insert(0);
+                    *entry = entry.saturating_sub(delta);
+                }
+            }
+        }
+    }
+
+    /// Cursor of `group` (None for the default group) on `col_name`. The first time a
+    /// named group shows up, its unread count starts at every entry of the topic.
+    pub(super) fn cursor_info(
+        &self,
+        group: Option<&str>,
+        col_name: &str,
+    ) -> std::io::Result<Arc<RwLock<ColReaderInfo>>> {
+        let info_arc = self.reader.col_info(col_name, group)?;
+        if let Some(group) = group {
+            let known = self.group_entry_counts.read().is_ok_and(|m| {
+                m.get(col_name)
+                    .is_some_and(|groups| groups.contains_key(group))
+            });
+            if !known {
+                let total = self.reader.seek_index.entry_total(col_name);
+                if let Ok(mut guard) = self.group_entry_counts.write() {
+                    guard
+                        .entry(col_name.to_string())
+                        .or_default()
+                        .entry(group.to_string())
+                        .or_insert(total);
+                }
+            }
         }
+        Ok(info_arc)
     }
 
     pub(super) fn get_or_create_writer(&self, col_name: &str) -> std::io::Result<Arc<Writer>> {
@@ -212,6 +408,13 @@ impl Walrus {
             return Ok(writer);
         }
 
+        let next_seq = self
+            .recovered_next_seq
+            .write()
+            .ok()
+            .and_then(|mut m| m.remove(col_name))
+            .unwrap_or(0);
+
         // SAFETY: The returned block will be held by this writer only
         // and appended/sealed before being exposed to readers.
         let initial_block = unsafe { self.allocator.get_next_available_block()? };
@@ -222,6 +425,7 @@ impl Walrus {
             col_name.to_string(),
             self.fsync_tx.clone(),
             self.fsync_schedule,
+            next_seq,
         ));
         map.insert(col_name.to_string(), writer.clone());
         Ok(writer)
@@ -229,6 +433,14 @@ impl Walrus {
 
     pub(super) fn startup_chore(&self) -> std::io::Result<()> {
         // Minimal recovery: scan wal data dir, build reader chains, and rebuild trackers
+        let manifest = self.allocator.manifest();
+        roll_back_unfinished_batches(
+            self.paths.root(),
+            self.allocator.storage(),
+            self.allocator.tracker().mmaps(),
+            manifest,
+        )?;
+        let tracker = self.allocator.tracker();
         let dir = match fs::read_dir(self.paths.root()) {
             Ok(d) => d,
             Err(_) => return Ok(()),
@@ -262,94 +474,131 @@ impl Walrus {
         let mut next_block_id: usize = 1;
         let mut seen_files = HashSet::new();
         let mut topic_block_entry_counts: HashMap<String, Vec<u64>> = HashMap::new();
-
-        for file_path in files.iter() {
-            let mmap = match SharedMmapKeeper::get_mmap_arc(file_path) {
-                Ok(m) => m,
-                Err(e) => {
-                    debug_print!("[recovery] mmap open failed for {}: {}", file_path, e);
-                    continue;
-                }
+        let mut topic_next_seq: HashMap<String, u64> = HashMap::new();
+        // Truncation floors of deleted and truncated topics, with the number of discarded
+        // blocks their persisted cursor positions still count
+        let floors: HashMap<String, (u64, u64)> = match self.read_offset_index.read() {
+            Ok(idx_guard) => idx_guard
+                .keys()
+                .filter_map(|key| match split_cursor_key(key) {
+                    (Some(""), topic) => idx_guard
+                        .get(key)
+                        .map(|pos| (topic.to_string(), (pos.cur_block_idx, pos.cur_block_offset))),
+                    _ => None,
+                })
+                .collect(),
+            Err(_) => HashMap::new(),
+        };
+        let mut files_with_blocks = HashSet::new();
+        let mut txn_scan = TxnScan::default();
+        // Sealed blocks are taken from the manifest; only the others are read record by record
+        let recorded = manifest.load();
+        // Files are scanned concurrently, then chained here in file order
+        let scanned = scan_files(
+            &files,
+            self.allocator.storage(),
+            self.allocator.tracker().mmaps(),
+            &recorded,
+        )?;
+        let mut recovered_blocks: Vec<(String, u64, SealedBlock)> = Vec::new();
+        let mut reused_blocks: usize = 0;
+
+        for (file_path, scanned) in files.iter().zip(scanned) {
+            let Some(ScannedFile {
+                mmap,
+                data_start,
+                block_size,
+                file_created_ms,
+                slots,
+                blocks,
+            }) = scanned
+            else {
+                continue;
             };
             seen_files.insert(file_path.clone());
-            FileStateTracker::register_file_if_absent(file_path);
-            debug_print!("[recovery] file {}", file_path);
-
-            let mut block_offset: u64 = 0;
-            while block_offset + DEFAULT_BLOCK_SIZE <= MAX_FILE_SIZE {
-                // heuristic: if first bytes are zero, assume no more blocks
-                let mut probe = [0u8; 8];
-                mmap.read(block_offset as usize, &mut probe);
-                if probe.iter().all(|&b| b == 0) {
-                    break;
-                }
-
-                let mut used: u64 = 0;
-                let mut entries_in_block: u64 = 0;
-
-                // try to read first metadata to get column name (with 2-byte length prefix)
-                let mut meta_buf = vec![0u8; PREFIX_META_SIZE];
-                mmap.read(block_offset as usize, &mut meta_buf);
-                let meta_len = (meta_buf[0] as usize) | ((meta_buf[1] as usize) << 8);
-                if meta_len == 0 || meta_len > PREFIX_META_SIZE - 2 {
-                    block_offset += DEFAULT_BLOCK_SIZE;
-                    next_block_id += 1;
-                    continue;
-                }
-                let mut aligned = rkyv::AlignedVec::with_capacity(meta_len);
-                aligned.extend_from_slice(&meta_buf[2..2 + meta_len]);
-                // SAFETY: `aligned` was constructed from a bounded metadata slice
-                // read from our file; alignment is ensured by `AlignedVec`.
-                // SAFETY: `aligned` is built from bounded bytes inside the block,
-                // copied into `AlignedVec` ensuring alignment for rkyv.
-                let archived = unsafe { rkyv::archived_root::<Metadata>(&aligned[..]) };
-                let md: Metadata = match archived.deserialize(&mut rkyv::Infallible) {
-                    Ok(m) => m,
-                    Err(_) => {
-                        break;
+            tracker.register_file_if_absent(file_path);
+            debug_print!(
+                "[recovery] file {}, data_start={}, block_size={}",
+                file_path,
+                data_start,
+                block_size
+            );
+            let file_name = Path::new(file_path)
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default();
+            let first_block_id = next_block_id;
+            next_block_id += slots as usize;
+
+            for scanned_block in blocks {
+                let block_offset = scanned_block.offset;
+                let block_id = first_block_id + ((block_offset - data_start) / block_size) as usize;
+                // The scan numbered the block's records as if its topic started in this file,
+                // which only holds when they carry sequence numbers past the earlier files'
+                let topic_next = topic_next_seq
+                    .get(&scanned_block.block.topic)
+                    .copied()
+                    .unwrap_or(0);
+                let sealed = if scanned_block.block.summary.first_seq >= topic_next {
+                    if scanned_block.from_manifest {
+                        reused_blocks += 1;
                     }
-                };
-                let col_name = md.owned_by;
-
-                // scan entries to compute used
-                let block_stub = Block {
-                    id: next_block_id as u64,
-                    file_path: file_path.clone(),
-                    offset: block_offset,
-                    limit: DEFAULT_BLOCK_SIZE,
-                    mmap: mmap.clone(),
-                    used: 0,
-                };
-                let mut in_block_off: u64 = 0;
-                loop {
-                    match block_stub.read(in_block_off) {
-                        Ok((_entry, consumed)) => {
-                            used += consumed as u64;
-                            in_block_off += consumed as u64;
-                            entries_in_block = entries_in_block.saturating_add(1);
-                            if in_block_off >= DEFAULT_BLOCK_SIZE {
-                                break;
-                            }
+                    scanned_block.block
+                } else {
+                    let block_stub = stub(file_path, &mmap, block_offset, block_size);
+                    match scan_block(&block_stub, topic_next, file_created_ms) {
+                        Some((used, summary)) => SealedBlock {
+                            topic: scanned_block.block.topic,
+                            used,
+                            summary,
+                        },
+                        None => {
+                            next_block_id = block_id;
+                            break;
                         }
-                        Err(_) => break,
                     }
+                };
+                for &txn in &sealed.summary.txns {
+                    txn_scan.note(txn);
                 }
-                if used == 0 {
-                    break;
-                }
+                let col_name = sealed.topic.clone();
+                let used = sealed.used;
+                let next_seq = sealed.summary.next_seq;
+                let entries_in_block = next_seq - sealed.summary.first_seq;
 
                 let block = Block {
-                    id: next_block_id as u64,
+                    id: block_id as u64,
                     file_path: file_path.clone(),
                     offset: block_offset,
-                    limit: DEFAULT_BLOCK_SIZE,
+                    limit: block_size,
                     mmap: mmap.clone(),
                     used,
                 };
                 // register and append
-                BlockStateTracker::register_block(next_block_id, file_path);
-                FileStateTracker::add_block_to_file_state(file_path);
-                if !col_name.is_empty() {
+                tracker.register_block(block_id, file_path);
+                files_with_blocks.insert(file_path.clone());
+                // Every entry is below the topic's floor: the block was truncated or deleted
+                let discarded = floors
+                    .get(&col_name)
+                    .is_some_and(|&(floor, _)| next_seq <= floor);
+                if discarded {
+                    topic_next_seq.insert(col_name.clone(), next_seq);
+                    tracker.set_checkpointed_true(block_id);
+                    debug_print!(
+                        "[recovery] discarded block below floor: file={}, block_id={}, col={}",
+                        file_path,
+                        block_id,
+                        col_name
+                    );
+                } else if !col_name.is_empty() {
+                    self.reader.seek_index.insert(
+                        &col_name,
+                        BlockSummary {
+                            block_id: block_id as u64,
+                            ..sealed.summary.clone()
+                        },
+                    );
+                    topic_next_seq.insert(col_name.clone(), next_seq);
                     let _ = self.reader.append_block_to_chain(&col_name, block.clone());
                     topic_block_entry_counts
                         .entry(col_name.clone())
@@ -363,19 +612,55 @@ impl Walrus {
                         col_name
                     );
                 }
-                next_block_id += 1;
-                block_offset += DEFAULT_BLOCK_SIZE;
+                recovered_blocks.push((file_name.to_string(), block_offset, sealed));
+            }
+        }
+        if !files.is_empty() {
+            debug_print!(
+                "[recovery] blocks: {} from the manifest, {} scanned",
+                reused_blocks,
+                recovered_blocks.len() - reused_blocks
+            );
+        }
+        manifest.reset(&recovered_blocks)?;
+
+        self.txns.recover(txn_scan);
+
+        // New blocks always go to the file the allocator just created, so recovered files
+        // only ever lose blocks and can be reclaimed once all of them are checkpointed
+        for file_path in files_with_blocks {
+            tracker.set_fully_allocated(file_path);
+        }
+
+        self.apply_floors_after_recovery(&floors, &topic_block_entry_counts)?;
+        for (topic, &(floor, _)) in floors.iter() {
+            let next = topic_next_seq.entry(topic.clone()).or_insert(0);
+            *next = (*next).max(floor);
+        }
+
+        // Recreate the cursors of named consumer groups persisted in the index
+        if let Ok(idx_guard) = self.read_offset_index.read() {
+            for key in idx_guard.keys() {
+                if let (Some(group), topic) = split_cursor_key(key)
+                    && !group.is_empty()
+                    && topic_block_entry_counts.contains_key(topic)
+                {
+                    self.reader.col_info(topic, Some(group))?;
+                }
             }
         }
 
         self.rebuild_topic_entry_counts_after_recovery(&topic_block_entry_counts)?;
+        if let Ok(mut guard) = self.recovered_next_seq.write() {
+            *guard = topic_next_seq;
+        }
 
         // hydrate index into memory and mark checkpointed blocks
         if let Ok(idx_guard) = self.read_offset_index.read() {
             let map = self.reader.data.read().ok();
             if let Some(map) = map {
-                for (col, info_arc) in map.iter() {
-                    if let Some(pos) = idx_guard.get(col) {
+                for (key, info_arc) in map.iter() {
+                    if let Some(pos) = idx_guard.get(key) {
                         let mut info = match info_arc.write() {
                             Ok(v) => v,
                             Err(_) => continue,
@@ -391,12 +676,24 @@ impl Walrus {
                         } else {
                             info.cur_block_offset = 0;
                         }
-                        for i in 0..ib {
-                            BlockStateTracker::set_checkpointed_true(info.chain[i].id as usize);
-                        }
-                        if ib < info.chain.len() && info.cur_block_offset >= info.chain[ib].used {
-                            BlockStateTracker::set_checkpointed_true(info.chain[ib].id as usize);
-                        }
+                        // Blocks this cursor has fully consumed; a tail position names its block
+                        let (at, offset) = if pos.cur_block_idx & TAIL_FLAG != 0 {
+                            let tail_id = pos.cur_block_idx & !TAIL_FLAG;
+                            let at = info.chain.iter().position(|b| b.id == tail_id);
+                            (at.unwrap_or(info.chain.len()), pos.cur_block_offset)
+                        } else {
+                            (ib, info.cur_block_offset)
+                        };
+                        let passed = match info.chain.get(at) {
+                            Some(block) if offset >= block.used => at + 1,
+                            _ => at,
+                        };
+                        self.reader.set_blocks_passed(
+                            split_cursor_key(key).1,
+                            key,
+                            passed,
+                            &info.chain,
+                        );
                     }
                 }
             }
@@ -404,7 +701,7 @@ impl Walrus {
 
         // enqueue deletion checks
         for f in seen_files.into_iter() {
-            flush_check(f);
+            tracker.flush_check(f);
         }
 
         unsafe {
@@ -416,11 +713,68 @@ impl Walrus {
 }
 
 impl Walrus {
+    /// Recovery leaves blocks below a topic's floor out of its chain, so persisted
+    /// positions that still count discarded blocks are shifted back by that many; those
+    /// inside the discarded blocks land on the first kept one. The floors are rewritten
+    /// with nothing left to discount, in the same index write.
+    fn apply_floors_after_recovery(
+        &self,
+        floors: &HashMap<String, (u64, u64)>,
+        topic_block_entry_counts: &HashMap<String, Vec<u64>>,
+    ) -> std::io::Result<()> {
+        if floors.is_empty() {
+            return Ok(());
+        }
+        let mut idx_guard = self
+            .read_offset_index
+            .write()
+            .map_err(|_| std::io::Error::other("read offset index lock poisoned"))?;
+        let mut updates = Vec::new();
+        for key in idx_guard.keys() {
+            let (group, topic) = split_cursor_key(key);
+            if group == Some("") {
+                continue;
+            }
+            let Some(&(_, dropped)) = floors.get(topic) else {
+                continue;
+            };
+            let Some(pos) = idx_guard.get(key) else {
+                continue;
+            };
+            if dropped > 0 && pos.cur_block_idx & TAIL_FLAG == 0 {
+                let (idx, offset) = if pos.cur_block_idx < dropped {
+                    (0, 0)
+                } else {
+                    (pos.cur_block_idx - dropped, pos.cur_block_offset)
+                };
+                updates.push((key.to_string(), idx, offset));
+            }
+        }
+        let mut changed = !updates.is_empty();
+        for (topic, &(floor, dropped)) in floors.iter() {
+            if dropped > 0 {
+                updates.push((floor_key(topic), floor, 0));
+                changed = true;
+            }
+        }
+        if changed {
+            idx_guard.update(&[], updates)?;
+        }
+        drop(idx_guard);
+
+        // A deleted topic may have crashed before its clean marker was dropped
+        for topic in floors.keys() {
+            if !topic_block_entry_counts.contains_key(topic) {
+                self.topic_clean_tracker.forget(topic)?;
+            }
+        }
+        Ok(())
+    }
+
     fn rebuild_topic_entry_counts_after_recovery(
         &self,
         topic_block_entry_counts: &HashMap<String, Vec<u64>>,
     ) -> std::io::Result<()> {
-        const TAIL_FLAG: u64 = 1u64 << 63;
 
         fn count_entries_in_block_up_to(block: &Block, limit: u64) -> u64 {
             let limit = limit.min(block.used);
@@ -443,15 +797,17 @@ impl Walrus {
         }
 
         let mut counts: HashMap<String, u64> = HashMap::new();
+        let mut group_counts: HashMap<String, HashMap<String, u64>> = HashMap::new();
 
         let idx_guard = self.read_offset_index.read().ok();
         let reader_guard = self.reader.data.read().ok();
 
         if let Some(reader_guard) = reader_guard {
-            for (topic, info_arc) in reader_guard.iter() {
+            for (key, info_arc) in reader_guard.iter() {
                 let Ok(info) = info_arc.read() else {
                     continue;
                 };
+                let (group, topic) = split_cursor_key(key);
 
                 let total_entries: u64 = topic_block_entry_counts
                     .get(topic)
@@ -461,7 +817,7 @@ impl Walrus {
                 let mut consumed_entries: u64 = 0;
 
                 if let Some(idx_guard) = idx_guard.as_ref() {
-                    if let Some(pos) = idx_guard.get(topic) {
+                    if let Some(pos) = idx_guard.get(key) {
                         if (pos.cur_block_idx & TAIL_FLAG) != 0 {
                             let tail_block_id = pos.cur_block_idx & (!TAIL_FLAG);
                             if let Some(tail_idx) = info
@@ -528,13 +884,27 @@ impl Walrus {
                     }
                 }
 
-                counts.insert(topic.to_string(), total_entries.saturating_sub(consumed_entries));
+                let remaining = total_entries.saturating_sub(consumed_entries);
+                match group {
+                    Some(group) => {
+                        group_counts
+                            .entry(topic.to_string())
+                            .or_default()
+                            .insert(group.to_string(), remaining);
+                    }
+                    None => {
+                        counts.insert(topic.to_string(), remaining);
+                    }
+                }
             }
         }
 
         if let Ok(mut guard) = self.topic_entry_counts.write() {
             *guard = counts;
         }
+        if let Ok(mut guard) = self.group_entry_counts.write() {
+            *guard = group_counts;
+        }
 
         Ok(())
     }
@@ -543,6 +913,7 @@ impl Walrus {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::wal::config::StorageBackend;
     use rand::random;
     use std::fs;
     use std::path::Path;
@@ -556,6 +927,14 @@ mod tests {
         let _ = fs::remove_dir_all(path);
     }
 
+    fn open_fd_for_key(key: &str, mode: ReadConsistency) -> std::io::Result<Walrus> {
+        Walrus::builder()
+            .namespace(key)
+            .consistency(mode)
+            .backend(StorageBackend::Fd)
+            .open()
+    }
+
     #[test]
     fn append_marks_topic_dirty() {
         let key = unique_key();
@@ -623,9 +1002,8 @@ mod tests {
     #[test]
     fn test_batch_read_scanning() {
         let key = unique_key();
-        crate::wal::config::enable_fd_backend();
 
-        let wal = Walrus::with_consistency_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
+        let wal = open_fd_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
 
         // 1. Write a sequence of entries
         let count = 100;
@@ -703,10 +1081,9 @@ mod tests {
     #[test]
     fn entry_counts_decrement_on_checkpoint_and_survive_restart_strict() {
         let key = unique_key();
-        crate::wal::config::enable_fd_backend();
 
         {
-            let wal = Walrus::with_consistency_for_key(&key, ReadConsistency::StrictlyAtOnce)
+            let wal = open_fd_for_key(&key, ReadConsistency::StrictlyAtOnce)
                 .unwrap();
             wal.append_for_topic("counts", b"one").unwrap();
             wal.append_for_topic("counts", b"two").unwrap();
@@ -725,7 +1102,7 @@ mod tests {
 
         {
             // Restart should rebuild counts from WAL + persisted read offsets.
-            let wal = Walrus::with_consistency_for_key(&key, ReadConsistency::StrictlyAtOnce)
+            let wal = open_fd_for_key(&key, ReadConsistency::StrictlyAtOnce)
                 .unwrap();
             assert_eq!(wal.get_topic_entry_count("counts"), 3);
 
@@ -742,9 +1119,8 @@ mod tests {
     #[test]
     fn entry_counts_increment_immediately_single_and_batch() {
         let key = unique_key();
-        crate::wal::config::enable_fd_backend();
 
-        let wal = Walrus::with_consistency_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
+        let wal = open_fd_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
 
         assert_eq!(wal.get_topic_entry_count("a"), 0);
         wal.append_for_topic("a", b"1").unwrap();
@@ -761,9 +1137,8 @@ mod tests {
     #[test]
     fn entry_counts_are_per_topic() {
         let key = unique_key();
-        crate::wal::config::enable_fd_backend();
 
-        let wal = Walrus::with_consistency_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
+        let wal = open_fd_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
         wal.append_for_topic("t1", b"a").unwrap();
         wal.batch_append_for_topic("t2", &[b"a", b"b"]).unwrap();
         wal.append_for_topic("t1", b"b").unwrap();
@@ -778,9 +1153,8 @@ mod tests {
     #[test]
     fn entry_counts_decrement_on_read_next_checkpoint_only() {
         let key = unique_key();
-        crate::wal::config::enable_fd_backend();
 
-        let wal = Walrus::with_consistency_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
+        let wal = open_fd_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
         wal.batch_append_for_topic("t", &[b"a", b"b", b"c"]).unwrap();
         assert_eq!(wal.get_topic_entry_count("t"), 3);
 
@@ -806,9 +1180,8 @@ mod tests {
     #[test]
     fn entry_counts_decrement_on_batch_read_checkpoint_only() {
         let key = unique_key();
-        crate::wal::config::enable_fd_backend();
 
-        let wal = Walrus::with_consistency_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
+        let wal = open_fd_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
         wal.batch_append_for_topic("t", &[b"a", b"b", b"c", b"d", b"e"])
             .unwrap();
         assert_eq!(wal.get_topic_entry_count("t"), 5);
@@ -834,11 +1207,10 @@ mod tests {
     #[test]
     fn entry_counts_survive_restart_after_partial_read_next_strict() {
         let key = unique_key();
-        crate::wal::config::enable_fd_backend();
 
         {
             let wal =
-                Walrus::with_consistency_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
+                open_fd_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
             wal.batch_append_for_topic("t", &[b"a", b"b", b"c", b"d"])
                 .unwrap();
             assert_eq!(wal.get_topic_entry_count("t"), 4);
@@ -850,7 +1222,7 @@ mod tests {
 
         {
             let wal =
-                Walrus::with_consistency_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
+                open_fd_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
             assert_eq!(wal.get_topic_entry_count("t"), 2);
         }
 
@@ -860,11 +1232,10 @@ mod tests {
     #[test]
     fn entry_counts_survive_restart_after_batch_read_strict() {
         let key = unique_key();
-        crate::wal::config::enable_fd_backend();
 
         {
             let wal =
-                Walrus::with_consistency_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
+                open_fd_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
             wal.batch_append_for_topic("t", &[b"a", b"b", b"c"]).unwrap();
             assert_eq!(wal.get_topic_entry_count("t"), 3);
 
@@ -875,7 +1246,7 @@ mod tests {
 
         {
             let wal =
-                Walrus::with_consistency_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
+                open_fd_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
             assert_eq!(wal.get_topic_entry_count("t"), 0);
         }
 
@@ -885,11 +1256,10 @@ mod tests {
     #[test]
     fn entry_counts_survive_restart_with_multiple_topics_strict() {
         let key = unique_key();
-        crate::wal::config::enable_fd_backend();
 
         {
             let wal =
-                Walrus::with_consistency_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
+                open_fd_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
             wal.batch_append_for_topic("t1", &[b"a", b"b"]).unwrap();
             wal.batch_append_for_topic("t2", &[b"a", b"b", b"c"]).unwrap();
             assert_eq!(wal.get_topic_entry_count("t1"), 2);
@@ -901,7 +1271,7 @@ mod tests {
 
         {
             let wal =
-                Walrus::with_consistency_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
+                open_fd_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
             assert_eq!(wal.get_topic_entry_count("t1"), 2);
             assert_eq!(wal.get_topic_entry_count("t2"), 2);
         }
@@ -912,9 +1282,8 @@ mod tests {
     #[test]
     fn batch_append_over_limit_does_not_change_count() {
         let key = unique_key();
-        crate::wal::config::enable_fd_backend();
 
-        let wal = Walrus::with_consistency_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
+        let wal = open_fd_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
         wal.append_for_topic("t", b"seed").unwrap();
         assert_eq!(wal.get_topic_entry_count("t"), 1);
 
@@ -929,9 +1298,8 @@ mod tests {
     #[test]
     fn stateless_batch_read_checkpoint_does_not_change_count() {
         let key = unique_key();
-        crate::wal::config::enable_fd_backend();
 
-        let wal = Walrus::with_consistency_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
+        let wal = open_fd_for_key(&key, ReadConsistency::StrictlyAtOnce).unwrap();
         wal.batch_append_for_topic("t", &[b"a", b"b", b"c"]).unwrap();
         assert_eq!(wal.get_topic_entry_count("t"), 3);
 
@@ -949,9 +1317,8 @@ mod tests {
     #[test]
     fn entry_counts_are_accurate_in_process_at_least_once_mode() {
         let key = unique_key();
-        crate::wal::config::enable_fd_backend();
 
-        let wal = Walrus::with_consistency_for_key(
+        let wal = open_fd_for_key(
             &key,
             ReadConsistency::AtLeastOnce { persist_every: 10_000 },
         )
@@ -0,0 +1,66 @@
+use super::Walrus;
+use crate::wal::config::{FsyncSchedule, debug_print};
+use std::io;
+
+impl Walrus {
+    /// Shuts the instance down: every entry appended so far is fsynced (unless the
+    /// schedule is `FsyncSchedule::NoFsync`), the read offset index, the clean
+    /// markers and the block manifest are written out, the pending background fsyncs
+    /// and reclamations run, and the background threads are joined. Its files are
+    /// unmapped and closed once the instance is gone, so the same directory can be
+    /// opened again right away, without scanning the blocks written so far.
+    ///
+    /// Dropping the instance does the same but ignores errors; `close` reports the
+    /// first one. Under `ReadConsistency::AtLeastOnce`, entries read since the last
+    /// checkpoint are delivered again after reopening, as after a crash.
+    pub fn close(mut self) -> io::Result<()> {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> io::Result<()> {
+        let Some(background) = self.background.take() else {
+            return Ok(());
+        };
+        debug_print!("[walrus] shutting down {}", self.paths.root().display());
+
+        let synced = match self.fsync_schedule {
+            FsyncSchedule::NoFsync => Ok(()),
+            _ => self.sync_all(),
+        };
+        let indexed = self
+            .read_offset_index
+            .write()
+            .map_err(|_| io::Error::other("read offset index lock poisoned"))
+            .and_then(|mut idx| idx.flush());
+        let marked = self.topic_clean_tracker.close();
+        let manifested = self.record_active_blocks();
+
+        // The thread runs the fsyncs and reclamations queued so far before exiting
+        self.allocator.tracker().reclaimer().stop();
+        let joined = background
+            .join()
+            .map_err(|_| io::Error::other("background thread panicked"));
+
+        synced.and(indexed).and(marked).and(manifested).and(joined)
+    }
+
+    // The next instance writes to new blocks, so the active ones are as good as sealed
+    fn record_active_blocks(&self) -> io::Result<()> {
+        let writers = self
+            .writers
+            .read()
+            .map_err(|_| io::Error::other("writers read lock poisoned"))?;
+        for writer in writers.values() {
+            writer.record_active_block()?;
+        }
+        self.allocator.manifest().sync()
+    }
+}
+
+impl Drop for Walrus {
+    fn drop(&mut self) {
+        if let Err(e) = self.shutdown() {
+            debug_print!("[walrus] shutdown error: {}", e);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use super::Walrus;
+use super::reader::{TAIL_FLAG, cursor_key, floor_key, validate_group};
+use crate::wal::block::Entry;
+use crate::wal::config::debug_print;
+use std::io;
+
+/// Position right after a leased batch, handed to [`Walrus::commit`] once the batch is
+/// processed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommitToken {
+    pub(super) group: Option<String>,
+    pub(super) topic: String,
+    pub(super) block_id: u64,
+    pub(super) offset: u64,
+    pub(super) next_seq: u64,
+}
+
+impl CommitToken {
+    /// Sequence number of the first entry read after this token is committed.
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Consumer group whose cursor the token belongs to; `None` for the default group.
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    /// Token for the position of `entry`, that is right before it.
+    pub(super) fn before(group: Option<&str>, topic: &str, entry: &Entry) -> Self {
+        Self {
+            group: group.map(str::to_string),
+            topic: topic.to_string(),
+            block_id: entry.block_id,
+            offset: entry.offset,
+            next_seq: entry.seq,
+        }
+    }
+}
+
+/// Entries read without being consumed, and the token that consumes them.
+#[derive(Debug)]
+pub struct Lease {
+    pub entries: Vec<Entry>,
+    pub token: CommitToken,
+}
+
+impl Walrus {
+    /// Reads up to `max_bytes` of entries from the topic's cursor without consuming them.
+    /// Later leases continue after this one, but the persisted read position only moves
+    /// when the returned token is passed to [`Walrus::commit`]; entries whose lease was
+    /// never committed are read again after a restart. Returns `None` when caught up.
+    pub fn read_leased(&self, col_name: &str, max_bytes: usize) -> io::Result<Option<Lease>> {
+        self.lease_for(None, col_name, max_bytes)
+    }
+
+    /// Same as [`Walrus::read_leased`], through `group`'s own cursor.
+    pub fn read_leased_as(
+        &self,
+        group: &str,
+        col_name: &str,
+        max_bytes: usize,
+    ) -> io::Result<Option<Lease>> {
+        validate_group(group)?;
+        self.lease_for(Some(group), col_name, max_bytes)
+    }
+
+    /// Consumes everything up to `token`: the cursor's persisted position moves there,
+    /// regardless of the [`ReadConsistency`](super::ReadConsistency) batching. Committing
+    /// a token older than one already committed is a no-op, so leases may be committed
+    /// out of order.
+    pub fn commit(&self, col_name: &str, token: CommitToken) -> io::Result<()> {
+        if token.topic != col_name {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "commit token belongs to topic {}, not {}",
+                    token.topic, col_name
+                ),
+            ));
+        }
+        self.persist_cursor_position(&token)
+    }
+
+    pub(super) fn lease_for(
+        &self,
+        group: Option<&str>,
+        col_name: &str,
+        max_bytes: usize,
+    ) -> io::Result<Option<Lease>> {
+        let entries = self.batch_read_for(group, col_name, max_bytes, true, None, false)?;
+        let Some(last) = entries.last() else {
+            return Ok(None);
+        };
+        let info_arc = self.cursor_info(group, col_name)?;
+        let info = info_arc
+            .read()
+            .map_err(|_| io::Error::other("col info read lock poisoned"))?;
+        let (block_id, offset) = match info.chain.get(info.cur_block_idx) {
+            Some(block) => (block.id, info.cur_block_offset),
+            None if info.tail_block_id != 0 => (info.tail_block_id, info.tail_offset),
+            None => return Err(io::Error::other("cursor position unavailable")),
+        };
+        let token = CommitToken {
+            group: group.map(str::to_string),
+            topic: col_name.to_string(),
+            block_id,
+            offset,
+            next_seq: last.seq + 1,
+        };
+        drop(info);
+        Ok(Some(Lease { entries, token }))
+    }
+
+    /// Writes the token's position as the cursor's persisted position, leaving the
+    /// in-memory cursor (which may have read further ahead) alone.
+    pub(super) fn persist_cursor_position(&self, token: &CommitToken) -> io::Result<()> {
+        let key = cursor_key(token.group.as_deref(), &token.topic);
+        // Held through the index write so a racing older token cannot land last
+        let mut committed = self
+            .reader
+            .committed
+            .lock()
+            .map_err(|_| io::Error::other("committed positions lock poisoned"))?;
+        if committed
+            .get(&key)
+            .is_some_and(|&seq| seq >= token.next_seq)
+        {
+            return Ok(());
+        }
+        let sealed_idx = {
+            let info_arc = self.cursor_info(token.group.as_deref(), &token.topic)?;
+            let info = info_arc
+                .read()
+                .map_err(|_| io::Error::other("col info read lock poisoned"))?;
+            info.chain.iter().position(|b| b.id == token.block_id)
+        };
+        let persist_idx = match sealed_idx {
+            Some(idx) => idx as u64,
+            None => token.block_id | TAIL_FLAG,
+        };
+        debug_print!(
+            "[reader] commit: col={}, block_id={}, offset={}, next_seq={}",
+            token.topic,
+            token.block_id,
+            token.offset,
+            token.next_seq
+        );
+        let mut idx = self
+            .read_offset_index
+            .write()
+            .map_err(|_| io::Error::other("read offset index lock poisoned"))?;
+        // Leased before a truncation or deletion that already moved the cursor past it
+        if idx
+            .get(&floor_key(&token.topic))
+            .is_some_and(|floor| token.next_seq <= floor.cur_block_idx)
+        {
+            return Ok(());
+        }
+        idx.set(key.clone(), persist_idx, token.offset)?;
+        committed.insert(key, token.next_seq);
+        Ok(())
+    }
+}
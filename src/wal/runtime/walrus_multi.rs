@@ -0,0 +1,342 @@
+use super::Walrus;
+use super::manifest::BlockManifest;
+use super::writer::{StagedRecord, StagedWrites, Writer, validate_batch};
+use crate::wal::block::TxnMark;
+use crate::wal::config::{PREFIX_META_SIZE, debug_print};
+use crate::wal::storage::{SharedMmap, SharedMmapKeeper, StorageOptions};
+use rkyv::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+/// Suffix of the files listing the record headers of a multi-topic batch in flight.
+pub(super) const INTENT_SUFFIX: &str = ".intent";
+
+impl Walrus {
+    /// Atomically appends a batch to each of several topics and returns the sequence
+    /// numbers assigned to each, in the order given: either every entry of every batch
+    /// is appended or none is, and readers see them only once all are written and
+    /// fsynced. A topic may appear only once, and the entry and size limits of
+    /// [`Walrus::batch_append_for_topic`] apply to all the batches together.
+    ///
+    /// The position of every record header is persisted before the records are written
+    /// and dropped once they are fsynced. Recovery zeroes the headers of a batch it finds
+    /// unfinished, as a failed single-topic batch does, so a crash leaves none of it in
+    /// any topic.
+    pub fn batch_append_multi(&self, batches: &[(&str, &[&[u8]])]) -> io::Result<Vec<Range<u64>>> {
+        self.append_multi(batches, TxnMark::None)
+    }
+
+    /// [`Walrus::batch_append_multi`] with every record tagged `txn`; transactions write
+    /// their commit and abort markers through it.
+    pub(super) fn append_multi(
+        &self,
+        batches: &[(&str, &[&[u8]])],
+        txn: TxnMark,
+    ) -> io::Result<Vec<Range<u64>>> {
+        validate_multi(batches)?;
+        let mut writers = Vec::with_capacity(batches.len());
+        for (topic, _) in batches {
+            self.mark_topic_dirty(topic);
+            writers.push(self.get_or_create_writer(topic)?);
+        }
+
+        let (staged, ranges) = stage_multi(&writers, batches, txn)?;
+        let files = touched_files(&staged);
+        let records: Vec<&StagedRecord> = staged.iter().flat_map(|s| s.records()).collect();
+        let mut intent = None;
+        let outcome = BatchIntent::create(self, &records).and_then(|created| {
+            let created = intent.insert(created);
+            self.write_ring.write(&records)?;
+            flush_all(&files)?;
+            created.finish()
+        });
+        drop(records);
+
+        if let Err(e) = outcome {
+            for writes in staged {
+                writes.abort();
+            }
+            // The intent may only go once the zeroed headers are durable
+            if flush_all(&files).is_ok()
+                && let Some(intent) = intent
+            {
+                let _ = intent.finish();
+            }
+            return Err(e);
+        }
+        for writes in staged {
+            writes.commit(true);
+        }
+        for (topic, batch) in batches {
+            self.increment_topic_entry_count(topic, batch.len() as u64);
+        }
+        debug_print!(
+            "[batch] multi-topic batch committed: topics={}, entries={}",
+            batches.len(),
+            batches.iter().map(|(_, b)| b.len()).sum::<usize>()
+        );
+        Ok(ranges)
+    }
+}
+
+fn validate_multi(batches: &[(&str, &[&[u8]])]) -> io::Result<()> {
+    let mut topics = HashSet::new();
+    if !batches.iter().all(|(topic, _)| topics.insert(*topic)) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "a topic appears more than once in the batch",
+        ));
+    }
+    validate_batch(batches.iter().flat_map(|(_, batch)| batch.iter().copied()))?;
+    Ok(())
+}
+
+/// Locks every writer and plans its batch, returning the staged writes in locking order
+/// and the sequence numbers of each batch in the order given. Writers are locked in
+/// address order, like in a group commit, so the two never deadlock.
+fn stage_multi<'a>(
+    writers: &'a [Arc<Writer>],
+    batches: &[(&str, &[&[u8]])],
+    txn: TxnMark,
+) -> io::Result<(Vec<StagedWrites<'a>>, Vec<Range<u64>>)> {
+    let mut order: Vec<usize> = (0..writers.len()).collect();
+    order.sort_by_key(|&i| Arc::as_ptr(&writers[i]));
+
+    let mut staged = Vec::with_capacity(order.len());
+    let mut ranges = vec![0..0; batches.len()];
+    for i in order {
+        let planned = writers[i].stage().and_then(|mut writes| {
+            let first = writes.next_seq();
+            for data in batches[i].1 {
+                if let Err(e) = writes.push_in_txn(data, &[], txn) {
+                    writes.abort();
+                    return Err(e);
+                }
+            }
+            Ok((first..writes.next_seq(), writes))
+        });
+        match planned {
+            Ok((range, writes)) => {
+                ranges[i] = range;
+                staged.push(writes);
+            }
+            Err(e) => {
+                for writes in staged {
+                    writes.abort();
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok((staged, ranges))
+}
+
+// Files holding the staged records and the blocks they sealed
+fn touched_files(staged: &[StagedWrites<'_>]) -> HashMap<String, Arc<SharedMmap>> {
+    let mut files = HashMap::new();
+    for writes in staged {
+        let records = writes.records().iter().map(|r| &r.block);
+        for block in writes.sealed().iter().chain(records) {
+            files
+                .entry(block.file_path.clone())
+                .or_insert_with(|| block.mmap.clone());
+        }
+    }
+    files
+}
+
+fn flush_all(files: &HashMap<String, Arc<SharedMmap>>) -> io::Result<()> {
+    for mmap in files.values() {
+        mmap.flush()?;
+    }
+    Ok(())
+}
+
+/// Intent file of a multi-topic batch or a compaction, holding the data file name and
+/// offset of every record header to zero. While it exists, recovery treats the change
+/// as unfinished and zeroes them.
+pub(super) struct BatchIntent {
+    path: PathBuf,
+    root: PathBuf,
+}
+
+impl BatchIntent {
+    fn create(wal: &Walrus, records: &[&StagedRecord]) -> io::Result<Self> {
+        let headers: Vec<(String, u64)> = records
+            .iter()
+            .map(|record| {
+                (
+                    file_name(&record.block.file_path),
+                    record.block.offset + record.offset,
+                )
+            })
+            .collect();
+        Self::for_headers(wal, &headers)
+    }
+
+    /// An intent over `headers`, as `(data file path, offset)`.
+    pub(super) fn for_headers(wal: &Walrus, headers: &[(String, u64)]) -> io::Result<Self> {
+        let root = wal.paths.root().to_path_buf();
+        let id = wal.next_intent.fetch_add(1, Ordering::Relaxed);
+        let intent = Self {
+            path: root.join(format!("batch_{}{}", id, INTENT_SUFFIX)),
+            root,
+        };
+        intent.write(headers)?;
+        Ok(intent)
+    }
+
+    /// Lists `headers` in place of the headers so far, in one step: recovery finds
+    /// either list, never both.
+    pub(super) fn replace(&self, headers: &[(String, u64)]) -> io::Result<()> {
+        self.write(headers)
+    }
+
+    fn write(&self, headers: &[(String, u64)]) -> io::Result<()> {
+        let headers: Vec<(String, u64)> = headers
+            .iter()
+            .map(|(path, offset)| (file_name(path), *offset))
+            .collect();
+        let bytes = rkyv::to_bytes::<_, 256>(&headers)
+            .map_err(|e| io::Error::other(format!("intent serialize failed: {:?}", e)))?;
+        // Written aside and renamed, so recovery never finds a partial list
+        let mut tmp_name = self.path.clone().into_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_name);
+        fs::write(&tmp_path, &bytes)?;
+        fs::File::open(&tmp_path)?.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        fs::File::open(&self.root)?.sync_all()
+    }
+
+    pub(super) fn finish(&self) -> io::Result<()> {
+        fs::remove_file(&self.path)?;
+        fs::File::open(&self.root)?.sync_all()
+    }
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Zeroes the record headers of every multi-topic batch still in flight when the last
+/// process stopped, then deletes its intent file. Runs before recovery scans the data
+/// files, which then end each block at the first zeroed header like after a failed batch.
+/// The manifest forgets the touched files first, as their sealed blocks may shrink.
+pub(super) fn roll_back_unfinished_batches(
+    root: &Path,
+    storage: StorageOptions,
+    mmaps: &SharedMmapKeeper,
+    manifest: &BlockManifest,
+) -> io::Result<()> {
+    let Ok(dir) = fs::read_dir(root) else {
+        return Ok(());
+    };
+    for entry in dir.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.ends_with(&format!("{}.tmp", INTENT_SUFFIX)) {
+            // Never renamed into place, so none of its records was written
+            fs::remove_file(&path)?;
+            continue;
+        }
+        if !name.ends_with(INTENT_SUFFIX) {
+            continue;
+        }
+
+        let bytes = fs::read(&path)?;
+        let headers: Vec<(String, u64)> = if bytes.is_empty() {
+            Vec::new()
+        } else {
+            // SAFETY: intent files are written in full before being renamed into place
+            let archived = unsafe { rkyv::archived_root::<Vec<(String, u64)>>(&bytes) };
+            archived
+                .deserialize(&mut rkyv::Infallible)
+                .map_err(|_| io::Error::other("intent deserialize failed"))?
+        };
+        let mut touched: HashMap<PathBuf, Arc<SharedMmap>> = HashMap::new();
+        for (file_name, offset) in headers {
+            let file_path = root.join(&file_name);
+            if !file_path.exists() {
+                continue;
+            }
+            let mmap = match touched.get(&file_path) {
+                Some(mmap) => mmap.clone(),
+                None => {
+                    manifest.forget_file(&file_path.to_string_lossy())?;
+                    let mmap = mmaps.get_mmap_arc(&file_path.to_string_lossy(), storage)?;
+                    touched.insert(file_path, mmap.clone());
+                    mmap
+                }
+            };
+            mmap.write(offset as usize, &[0u8; PREFIX_META_SIZE]);
+        }
+        for mmap in touched.values() {
+            mmap.flush()?;
+        }
+        fs::remove_file(&path)?;
+        debug_print!("[recovery] rolled back unfinished batch: {}", name);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wal::config::FsyncSchedule;
+    use crate::wal::runtime::ReadConsistency;
+    use rand::random;
+
+    fn open(key: &str) -> Walrus {
+        Walrus::with_consistency_and_schedule_for_key(
+            key,
+            ReadConsistency::StrictlyAtOnce,
+            FsyncSchedule::NoFsync,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn unfinished_batch_is_rolled_back_on_recovery() {
+        let key = format!("multi_batch_test_{}", random::<u64>());
+        {
+            let wal = open(&key);
+            // The outbox block comes first in the file, so zeroing its first header must
+            // not hide the orders block behind it
+            let writers = vec![
+                wal.get_or_create_writer("outbox").unwrap(),
+                wal.get_or_create_writer("orders").unwrap(),
+            ];
+            wal.append_for_topic("orders", b"before").unwrap();
+
+            let command: &[&[u8]] = &[b"command"];
+            let events: &[&[u8]] = &[b"event-1", b"event-2"];
+            let batches = [("outbox", events), ("orders", command)];
+            let (staged, _) = stage_multi(&writers, &batches, TxnMark::None).unwrap();
+            let records: Vec<&StagedRecord> = staged.iter().flat_map(|s| s.records()).collect();
+            BatchIntent::create(&wal, &records).unwrap();
+            wal.write_ring.write(&records).unwrap();
+            flush_all(&touched_files(&staged)).unwrap();
+            // The process dies here: nothing was published and the intent stays
+        }
+
+        let wal = open(&key);
+        let entry = wal.read_next("orders", true).unwrap().unwrap();
+        assert_eq!((entry.seq, entry.data), (0, b"before".to_vec()));
+        assert!(wal.read_next("orders", true).unwrap().is_none());
+        assert!(wal.read_next("outbox", true).unwrap().is_none());
+        assert_eq!(wal.append_for_topic("orders", b"after").unwrap(), 1);
+        assert_eq!(wal.append_for_topic("outbox", b"after").unwrap(), 0);
+
+        drop(wal);
+        let _ = fs::remove_dir_all(Path::new("wal_files").join(&key));
+    }
+}
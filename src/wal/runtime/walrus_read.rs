@@ -1,18 +1,16 @@
-use super::allocator::BlockStateTracker;
-use super::reader::ColReaderInfo;
+use super::reader::{ColReaderInfo, TAIL_FLAG, cursor_key, validate_group};
+use super::transaction::Visibility;
 use super::{ReadConsistency, Walrus};
 use crate::wal::block::{Block, Entry, Metadata};
 use crate::wal::config::{MAX_BATCH_ENTRIES, PREFIX_META_SIZE, checksum64, debug_print};
 use std::io;
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
-use rkyv::{AlignedVec, Deserialize};
 use tracing::info;
 
 #[cfg(target_os = "linux")]
-use crate::wal::config::USE_FD_BACKEND;
-#[cfg(target_os = "linux")]
-use std::sync::atomic::Ordering;
+use crate::wal::config::StorageBackend;
 
 #[cfg(target_os = "linux")]
 use io_uring;
@@ -22,32 +20,52 @@ use std::os::unix::io::AsRawFd;
 
 impl Walrus {
     pub fn read_next(&self, col_name: &str, checkpoint: bool) -> io::Result<Option<Entry>> {
-        const TAIL_FLAG: u64 = 1u64 << 63;
-        let info_arc = if let Some(arc) = {
-            let map = self.reader.data.read().map_err(|_| {
-                io::Error::new(io::ErrorKind::Other, "reader map read lock poisoned")
-            })?;
-            map.get(col_name).cloned()
-        } {
-            arc
-        } else {
-            let mut map = self.reader.data.write().map_err(|_| {
-                io::Error::new(io::ErrorKind::Other, "reader map write lock poisoned")
-            })?;
-            map.entry(col_name.to_string())
-                .or_insert_with(|| {
-                    Arc::new(RwLock::new(ColReaderInfo {
-                        chain: Vec::new(),
-                        cur_block_idx: 0,
-                        cur_block_offset: 0,
-                        reads_since_persist: 0,
-                        tail_block_id: 0,
-                        tail_offset: 0,
-                        hydrated_from_index: false,
-                    }))
-                })
-                .clone()
-        };
+        self.read_next_for(None, col_name, checkpoint)
+    }
+
+    /// Like [`Walrus::read_next`], but when the topic is caught up waits up to `timeout`
+    /// for a writer to append to it instead of returning `None` right away. Returns `None`
+    /// if nothing arrived in time.
+    pub fn read_next_blocking(
+        &self,
+        col_name: &str,
+        checkpoint: bool,
+        timeout: Duration,
+    ) -> io::Result<Option<Entry>> {
+        let signal = self.reader.signals.for_topic(col_name);
+        let deadline = Instant::now().checked_add(timeout);
+        loop {
+            // Taken before reading so an append racing with the read still wakes us
+            let seen = signal.generation();
+            if let Some(entry) = self.read_next(col_name, checkpoint)? {
+                return Ok(Some(entry));
+            }
+            if !signal.wait_past(seen, deadline) {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Same as [`Walrus::read_next`], but reads through `group`'s own cursor on the topic,
+    /// so several consumer groups each see every entry.
+    pub fn read_next_as(
+        &self,
+        group: &str,
+        col_name: &str,
+        checkpoint: bool,
+    ) -> io::Result<Option<Entry>> {
+        validate_group(group)?;
+        self.read_next_for(Some(group), col_name, checkpoint)
+    }
+
+    fn read_next_for(
+        &self,
+        group: Option<&str>,
+        col_name: &str,
+        checkpoint: bool,
+    ) -> io::Result<Option<Entry>> {
+        let key = cursor_key(group, col_name);
+        let info_arc = self.cursor_info(group, col_name)?;
         let mut info = info_arc
             .write()
             .map_err(|_| io::Error::new(io::ErrorKind::Other, "col info write lock poisoned"))?;
@@ -61,9 +79,10 @@ impl Walrus {
 
         // Load persisted position (supports tail sentinel)
         let mut persisted_tail: Option<(u64 /*block_id*/, u64 /*offset*/)> = None;
+        let newly_hydrated = !info.hydrated_from_index;
         if !info.hydrated_from_index {
             if let Ok(idx_guard) = self.read_offset_index.read() {
-                if let Some(pos) = idx_guard.get(col_name) {
+                if let Some(pos) = idx_guard.get(&key) {
                     if (pos.cur_block_idx & TAIL_FLAG) != 0 {
                         let tail_block_id = pos.cur_block_idx & (!TAIL_FLAG);
                         persisted_tail = Some((tail_block_id, pos.cur_block_offset));
@@ -112,6 +131,12 @@ impl Walrus {
             persisted_tail = None;
         }
 
+        // First read through this cursor registers it for block reclamation
+        if newly_hydrated {
+            self.reader
+                .set_blocks_passed(col_name, &key, info.cur_block_idx, &info.chain);
+        }
+
         // Important: release the per-column lock; we'll reacquire each iteration
         drop(info);
 
@@ -134,19 +159,28 @@ impl Walrus {
                         off,
                         block.used
                     );
-                    BlockStateTracker::set_checkpointed_true(block.id as usize);
                     info.cur_block_idx += 1;
                     info.cur_block_offset = 0;
+                    self.reader
+                        .set_blocks_passed(col_name, &key, info.cur_block_idx, &info.chain);
                     continue;
                 }
 
-                match block.read(off) {
-                    Ok((entry, consumed)) => {
+                match block.read_record(off) {
+                    Ok((entry, consumed, txn)) => {
+                        let hidden = match self.txns.visibility(txn) {
+                            Visibility::Visible => false,
+                            Visibility::Hidden => true,
+                            Visibility::Pending => return Ok(None),
+                        };
                         // Compute new offset and decide whether to commit progress
                         let new_off = off + consumed as u64;
                         let mut maybe_persist = None;
-                        if checkpoint {
+                        // Records readers never return are passed over even by peeks
+                        if checkpoint || hidden {
                             info.cur_block_offset = new_off;
+                        }
+                        if checkpoint {
                             maybe_persist = if self.should_persist(&mut info, false) {
                                 Some((info.cur_block_idx as u64, new_off))
                             } else {
@@ -159,7 +193,7 @@ impl Walrus {
                         if checkpoint {
                             if let Some((idx_val, off_val)) = maybe_persist {
                                 if let Ok(mut idx_guard) = self.read_offset_index.write() {
-                                    let _ = idx_guard.set(col_name.to_string(), idx_val, off_val);
+                                    let _ = idx_guard.set(key.clone(), idx_val, off_val);
                                 }
                             }
                         }
@@ -172,7 +206,10 @@ impl Walrus {
                             new_off
                         );
                         if checkpoint {
-                            self.decrement_topic_entry_count(col_name, 1);
+                            self.decrement_entry_count(group, col_name, 1);
+                        }
+                        if hidden {
+                            continue;
                         }
                         return Ok(Some(entry));
                     }
@@ -223,7 +260,7 @@ impl Walrus {
                             if self.should_persist(&mut info, true) {
                                 if let Ok(mut idx_guard) = self.read_offset_index.write() {
                                     let _ = idx_guard.set(
-                                        col_name.to_string(),
+                                        key.clone(),
                                         info.cur_block_idx as u64,
                                         info.cur_block_offset,
                                     );
@@ -239,11 +276,8 @@ impl Walrus {
                         if checkpoint {
                             if self.should_persist(&mut info, true) {
                                 if let Ok(mut idx_guard) = self.read_offset_index.write() {
-                                    let _ = idx_guard.set(
-                                        col_name.to_string(),
-                                        active_block.id | TAIL_FLAG,
-                                        0,
-                                    );
+                                    let _ =
+                                        idx_guard.set(key.clone(), active_block.id | TAIL_FLAG, 0);
                                 }
                             }
                         }
@@ -255,8 +289,7 @@ impl Walrus {
                 if checkpoint {
                     if self.should_persist(&mut info, true) {
                         if let Ok(mut idx_guard) = self.read_offset_index.write() {
-                            let _ =
-                                idx_guard.set(col_name.to_string(), active_block.id | TAIL_FLAG, 0);
+                            let _ = idx_guard.set(key.clone(), active_block.id | TAIL_FLAG, 0);
                         }
                     }
                 }
@@ -283,17 +316,24 @@ impl Walrus {
             }
 
             if tail_off < written {
-                match active_block.read(tail_off) {
-                    Ok((entry, consumed)) => {
+                match active_block.read_record(tail_off) {
+                    Ok((entry, consumed, txn)) => {
+                        let hidden = match self.txns.visibility(txn) {
+                            Visibility::Visible => false,
+                            Visibility::Hidden => true,
+                            Visibility::Pending => return Ok(None),
+                        };
                         let new_off = tail_off + consumed as u64;
                         // Reacquire column lock to update in-memory progress, then decide persistence
                         let mut info = info_arc.write().map_err(|_| {
                             io::Error::new(io::ErrorKind::Other, "col info write lock poisoned")
                         })?;
                         let mut maybe_persist = None;
-                        if checkpoint {
+                        if checkpoint || hidden {
                             info.tail_block_id = active_block.id;
                             info.tail_offset = new_off;
+                        }
+                        if checkpoint {
                             maybe_persist = if self.should_persist(&mut info, false) {
                                 Some((tail_block_id | TAIL_FLAG, new_off))
                             } else {
@@ -304,7 +344,7 @@ impl Walrus {
                         if checkpoint {
                             if let Some((idx_val, off_val)) = maybe_persist {
                                 if let Ok(mut idx_guard) = self.read_offset_index.write() {
-                                    let _ = idx_guard.set(col_name.to_string(), idx_val, off_val);
+                                    let _ = idx_guard.set(key.clone(), idx_val, off_val);
                                 }
                             }
                         }
@@ -317,7 +357,10 @@ impl Walrus {
                             new_off
                         );
                         if checkpoint {
-                            self.decrement_topic_entry_count(col_name, 1);
+                            self.decrement_entry_count(group, col_name, 1);
+                        }
+                        if hidden {
+                            continue;
                         }
                         return Ok(Some(entry));
                     }
@@ -372,6 +415,91 @@ impl Walrus {
         checkpoint: bool,
         start_offset: Option<u64>,
     ) -> io::Result<Vec<Entry>> {
+        self.batch_read_for(None, col_name, max_bytes, checkpoint, start_offset, true)
+    }
+
+    /// Like [`Walrus::batch_read_for_topic`] from the topic's cursor, but when the topic is
+    /// caught up waits up to `timeout` for a writer to append to it. Returns an empty
+    /// batch if nothing arrived in time.
+    pub fn batch_read_for_topic_blocking(
+        &self,
+        col_name: &str,
+        max_bytes: usize,
+        checkpoint: bool,
+        timeout: Duration,
+    ) -> io::Result<Vec<Entry>> {
+        let signal = self.reader.signals.for_topic(col_name);
+        let deadline = Instant::now().checked_add(timeout);
+        loop {
+            let seen = signal.generation();
+            let entries = self.batch_read_for_topic(col_name, max_bytes, checkpoint, None)?;
+            if !entries.is_empty() || !signal.wait_past(seen, deadline) {
+                return Ok(entries);
+            }
+        }
+    }
+
+    /// Same as [`Walrus::batch_read_for_topic`], but reads and checkpoints through
+    /// `group`'s own cursor. Stateless reads (`start_offset`) do not touch any cursor.
+    pub fn batch_read_for_topic_as(
+        &self,
+        group: &str,
+        col_name: &str,
+        max_bytes: usize,
+        checkpoint: bool,
+        start_offset: Option<u64>,
+    ) -> io::Result<Vec<Entry>> {
+        validate_group(group)?;
+        self.batch_read_for(
+            Some(group),
+            col_name,
+            max_bytes,
+            checkpoint,
+            start_offset,
+            true,
+        )
+    }
+
+    /// With `persist` false a checkpoint only moves the in-memory cursor; the caller
+    /// writes the read offset index itself (subscriptions do so on ack).
+    pub(super) fn batch_read_for(
+        &self,
+        group: Option<&str>,
+        col_name: &str,
+        max_bytes: usize,
+        checkpoint: bool,
+        start_offset: Option<u64>,
+        persist: bool,
+    ) -> io::Result<Vec<Entry>> {
+        loop {
+            let (entries, only_hidden) = self.batch_read_pass(
+                group,
+                col_name,
+                max_bytes,
+                checkpoint,
+                start_offset,
+                persist,
+            )?;
+            // A budget spent on markers and aborted entries says nothing about what is
+            // left; the cursor moved past them, so read on
+            if entries.is_empty() && only_hidden && start_offset.is_none() {
+                continue;
+            }
+            return Ok(entries);
+        }
+    }
+
+    /// One planned read for [`Walrus::batch_read_for`], also telling whether every record
+    /// it parsed was one readers never return.
+    fn batch_read_pass(
+        &self,
+        group: Option<&str>,
+        col_name: &str,
+        max_bytes: usize,
+        checkpoint: bool,
+        start_offset: Option<u64>,
+        persist: bool,
+    ) -> io::Result<(Vec<Entry>, bool)> {
         // Helper struct for read planning
         struct ReadPlan {
             blk: Block,
@@ -381,7 +509,7 @@ impl Walrus {
             chain_idx: Option<usize>,
         }
 
-        const TAIL_FLAG: u64 = 1u64 << 63;
+        let key = cursor_key(group, col_name);
 
         info!(
             "batch_read_for_topic: col_name={}, max_bytes={}, checkpoint={}, start_offset={:?}",
@@ -498,26 +626,15 @@ impl Walrus {
                     // Read header
                     blk.mmap
                         .read((blk.offset + scan_pos) as usize, &mut meta_buf);
-                    let meta_len = (meta_buf[0] as usize) | ((meta_buf[1] as usize) << 8);
-                    if meta_len == 0 || meta_len > PREFIX_META_SIZE - 2 {
-                        info!(
-                            "batch_read_for_topic: (stateless) breaking meta_len invalid: {}",
-                            meta_len
-                        );
-                        break; // Corrupt/Zeroed
-                    }
-
                     // Decode metadata to get read_size
-                    let mut aligned = AlignedVec::with_capacity(meta_len);
-                    aligned.extend_from_slice(&meta_buf[2..2 + meta_len]);
-                    let archived = unsafe { rkyv::archived_root::<Metadata>(&aligned[..]) };
-                    let meta: Metadata = match archived.deserialize(&mut rkyv::Infallible) {
+                    let meta = match Metadata::decode_prefix(&meta_buf) {
                         Ok(m) => m,
-                        Err(_) => {
+                        Err(e) => {
                             info!(
-                                "batch_read_for_topic: (stateless) breaking meta deserialize error"
+                                "batch_read_for_topic: (stateless) breaking invalid metadata: {}",
+                                e
                             );
-                            break;
+                            break; // Corrupt/Zeroed
                         }
                     };
                     let data_size = meta.read_size;
@@ -526,8 +643,8 @@ impl Walrus {
                     let entry_end = scan_pos + entry_total;
 
                     info!(
-                        "batch_read_for_topic: (stateless) scanned entry: meta_len={}, data_size={}, entry_total={}, entry_end={}",
-                        meta_len, data_size, entry_total, entry_end
+                        "batch_read_for_topic: (stateless) scanned entry: data_size={}, entry_total={}, entry_end={}",
+                        data_size, entry_total, entry_end
                     );
 
                     // Special handling for start_offset = 0 to skip small initial entries (likely internal metadata)
@@ -585,31 +702,7 @@ impl Walrus {
             (chain, c_idx, c_off, 0, rem, None, trim, hint)
         } else {
             // --- Stateful Read (Shared State) ---
-            let info_arc = if let Some(arc) = {
-                let map = self.reader.data.read().map_err(|_| {
-                    io::Error::new(io::ErrorKind::Other, "reader map read lock poisoned")
-                })?;
-                map.get(col_name).cloned()
-            } {
-                arc
-            } else {
-                let mut map = self.reader.data.write().map_err(|_| {
-                    io::Error::new(io::ErrorKind::Other, "reader map write lock poisoned")
-                })?;
-                map.entry(col_name.to_string())
-                    .or_insert_with(|| {
-                        Arc::new(RwLock::new(ColReaderInfo {
-                            chain: Vec::new(),
-                            cur_block_idx: 0,
-                            cur_block_offset: 0,
-                            reads_since_persist: 0,
-                            tail_block_id: 0,
-                            tail_offset: 0,
-                            hydrated_from_index: false,
-                        }))
-                    })
-                    .clone()
-            };
+            let info_arc = self.cursor_info(group, col_name)?;
 
             _held_arc = Some(info_arc);
             let mut info = _held_arc.as_ref().unwrap().write().map_err(|_| {
@@ -618,9 +711,10 @@ impl Walrus {
 
             // Hydrate from index if needed
             let mut persisted_tail_for_fold: Option<(u64, u64)> = None;
+            let newly_hydrated = !info.hydrated_from_index;
             if !info.hydrated_from_index {
                 if let Ok(idx_guard) = self.read_offset_index.read() {
-                    if let Some(pos) = idx_guard.get(col_name) {
+                    if let Some(pos) = idx_guard.get(&key) {
                         if (pos.cur_block_idx & TAIL_FLAG) != 0 {
                             let tail_bid = pos.cur_block_idx & (!TAIL_FLAG);
                             info.tail_block_id = tail_bid;
@@ -663,6 +757,12 @@ impl Walrus {
                 }
             }
 
+            // First read through this cursor registers it for block reclamation
+            if newly_hydrated {
+                self.reader
+                    .set_blocks_passed(col_name, &key, info.cur_block_idx, &info.chain);
+            }
+
             let c_chain = info.chain.clone();
             let c_idx = info.cur_block_idx;
             let c_off = info.cur_block_offset;
@@ -676,12 +776,15 @@ impl Walrus {
         let mut plan: Vec<ReadPlan> = Vec::new();
         let mut planned_bytes: usize = 0;
         let chain_len_at_plan = chain.len();
+        // Set when the budget cut a sealed block short; nothing after it may be planned
+        let mut cut_short = false;
 
         while cur_idx < chain.len() && planned_bytes < max_bytes {
             let block = chain[cur_idx].clone();
             if cur_off >= block.used {
                 if info_guard.is_some() {
-                    BlockStateTracker::set_checkpointed_true(block.id as usize);
+                    self.reader
+                        .set_blocks_passed(col_name, &key, cur_idx + 1, &chain);
                 }
                 cur_idx += 1;
                 cur_off = 0;
@@ -708,64 +811,43 @@ impl Walrus {
                     block
                         .mmap
                         .read((block.offset + cur_off) as usize, &mut meta_buf);
-                    let meta_len = (meta_buf[0] as usize) | ((meta_buf[1] as usize) << 8);
-                    if meta_len > 0 && meta_len <= PREFIX_META_SIZE - 2 {
-                        let mut aligned_peek_meta = AlignedVec::with_capacity(meta_len);
-                        aligned_peek_meta.extend_from_slice(&meta_buf[2..2 + meta_len]);
-                        let archived_peek_meta =
-                            unsafe { rkyv::archived_root::<Metadata>(&aligned_peek_meta[..]) };
-                        let meta_res: Result<Metadata, _> =
-                            archived_peek_meta.deserialize(&mut rkyv::Infallible);
-                        match meta_res {
-                            Ok(meta) => {
-                                let size1 = meta.read_size;
-                                let required1 = (PREFIX_META_SIZE + size1) as u64;
-
-                                // --- DOUBLE PEEK START ---
-                                let mut final_required = required1;
-
-                                if size1 < 128 {
-                                    let offset2 = cur_off + required1;
-                                    if offset2 + (PREFIX_META_SIZE as u64) <= block.used {
-                                        let mut meta_buf2 = [0u8; PREFIX_META_SIZE];
-                                        block.mmap.read(
-                                            (block.offset + offset2) as usize,
-                                            &mut meta_buf2,
-                                        );
-                                        let meta_len2 = (meta_buf2[0] as usize)
-                                            | ((meta_buf2[1] as usize) << 8);
-                                        if meta_len2 > 0 && meta_len2 <= PREFIX_META_SIZE - 2 {
-                                            let mut aligned2 = AlignedVec::with_capacity(meta_len2);
-                                            aligned2
-                                                .extend_from_slice(&meta_buf2[2..2 + meta_len2]);
-                                            let archived2 = unsafe {
-                                                rkyv::archived_root::<Metadata>(&aligned2[..])
-                                            };
-                                            let meta2_res: Result<Metadata, _> =
-                                                archived2.deserialize(&mut rkyv::Infallible);
-                                            let meta2 = meta2_res
-                                                .expect("infallible metadata deserialize");
-                                            let size2 = meta2.read_size;
-                                            let required2 = (PREFIX_META_SIZE + size2) as u64;
-                                            final_required = required1 + required2;
-                                        }
+                    match Metadata::decode_prefix(&meta_buf) {
+                        Ok(meta) => {
+                            let size1 = meta.read_size;
+                            let required1 = (PREFIX_META_SIZE + size1) as u64;
+
+                            // --- DOUBLE PEEK START ---
+                            let mut final_required = required1;
+
+                            if size1 < 128 {
+                                let offset2 = cur_off + required1;
+                                if offset2 + (PREFIX_META_SIZE as u64) <= block.used {
+                                    let mut meta_buf2 = [0u8; PREFIX_META_SIZE];
+                                    block
+                                        .mmap
+                                        .read((block.offset + offset2) as usize, &mut meta_buf2);
+                                    if let Ok(meta2) = Metadata::decode_prefix(&meta_buf2) {
+                                        let size2 = meta2.read_size;
+                                        let required2 = (PREFIX_META_SIZE + size2) as u64;
+                                        final_required = required1 + required2;
                                     }
                                 }
-                                // --- DOUBLE PEEK END ---
-
-                                if final_required > want {
-                                    want = final_required;
-                                }
                             }
-                            Err(_) => {
-                                // ignore error, fallback to want
+                            // --- DOUBLE PEEK END ---
+
+                            if final_required > want {
+                                want = final_required;
                             }
                         }
+                        Err(_) => {
+                            // ignore error, fallback to want
+                        }
                     }
                 }
             }
 
             let end = block.used.min(cur_off + want);
+            cut_short = end < block.used;
             if end > cur_off {
                 plan.push(ReadPlan {
                     blk: block.clone(),
@@ -781,7 +863,7 @@ impl Walrus {
         }
 
         // Plan tail if we're at the end of sealed chain
-        if cur_idx >= chain_len_at_plan {
+        if cur_idx >= chain_len_at_plan && !cut_short {
             if let Some((active_block, written)) = writer_snapshot.clone() {
                 // Determine start of tail read
                 let mut tail_start = if start_offset.is_some() {
@@ -806,15 +888,7 @@ impl Walrus {
                         active_block
                             .mmap
                             .read((active_block.offset + scan_pos) as usize, &mut meta_buf);
-                        let meta_len = (meta_buf[0] as usize) | ((meta_buf[1] as usize) << 8);
-                        if meta_len == 0 || meta_len > PREFIX_META_SIZE - 2 {
-                            break;
-                        }
-
-                        let mut aligned = AlignedVec::with_capacity(meta_len);
-                        aligned.extend_from_slice(&meta_buf[2..2 + meta_len]);
-                        let archived = unsafe { rkyv::archived_root::<Metadata>(&aligned[..]) };
-                        let meta: Metadata = match archived.deserialize(&mut rkyv::Infallible) {
+                        let meta = match Metadata::decode_prefix(&meta_buf) {
                             Ok(m) => m,
                             Err(_) => break,
                         };
@@ -855,7 +929,7 @@ impl Walrus {
         }
 
         if plan.is_empty() {
-            return Ok(Vec::new());
+            return Ok((Vec::new(), false));
         }
 
         // Hold lock across IO/parse when the read is stateful+checkpointing, to avoid duplicate consumption
@@ -870,7 +944,7 @@ impl Walrus {
 
         // 3) Read ranges via io_uring (FD backend) or mmap
         #[cfg(target_os = "linux")]
-        let buffers = if USE_FD_BACKEND.load(Ordering::Relaxed) {
+        let buffers = if self.allocator.storage().backend == StorageBackend::Fd {
             // io_uring path
             let ring_size = (plan.len() + 64).min(4096) as u32;
             let ring = match io_uring::IoUring::new(ring_size) {
@@ -989,10 +1063,15 @@ impl Walrus {
         let mut final_tail_block_id = 0u64;
         let mut final_tail_offset = 0u64;
         let mut entries_parsed = 0u32;
+        // Parsed records that are transaction markers or aborted entries
+        let mut hidden_parsed = 0u32;
         let mut saw_tail = false;
 
+        // Set once an entry is cut off or over budget; later ranges must not be parsed,
+        // or the cursor would skip past it
+        let mut stopped = false;
         for (plan_idx, read_plan) in plan.iter().enumerate() {
-            if entries.len() >= MAX_BATCH_ENTRIES {
+            if entries.len() >= MAX_BATCH_ENTRIES || stopped {
                 break;
             }
             let buffer = &buffers[plan_idx];
@@ -1004,23 +1083,12 @@ impl Walrus {
                 }
                 // Try to read metadata header
                 if buf_offset + PREFIX_META_SIZE > buffer.len() {
+                    stopped = true;
                     break; // Not enough data for header
                 }
 
-                let meta_len =
-                    (buffer[buf_offset] as usize) | ((buffer[buf_offset + 1] as usize) << 8);
-
-                if meta_len == 0 || meta_len > PREFIX_META_SIZE - 2 {
-                    // Invalid or zeroed header - stop parsing this block
-                    break;
-                }
-
-                // Deserialize metadata
-                let mut aligned = AlignedVec::with_capacity(meta_len);
-                aligned.extend_from_slice(&buffer[buf_offset + 2..buf_offset + 2 + meta_len]);
-
-                let archived = unsafe { rkyv::archived_root::<Metadata>(&aligned[..]) };
-                let meta: Metadata = match archived.deserialize(&mut rkyv::Infallible) {
+                // Invalid or zeroed header - stop parsing this block
+                let meta = match Metadata::decode_prefix(&buffer[buf_offset..]) {
                     Ok(m) => m,
                     Err(_) => {
                         break; // Parse error - stop
@@ -1032,14 +1100,28 @@ impl Walrus {
 
                 // Check if we have enough buffer space for the data
                 if buf_offset + entry_consumed > buffer.len() {
+                    stopped = true;
                     break; // Incomplete entry
                 }
 
+                let hidden = match self.txns.visibility(meta.txn()) {
+                    Visibility::Visible => false,
+                    Visibility::Hidden => true,
+                    // Nothing behind an undecided transaction may be returned yet
+                    Visibility::Pending => {
+                        stopped = true;
+                        break;
+                    }
+                };
+
                 // Enforce byte budget on payload bytes, but always allow at least one entry.
-                let next_total = total_data_bytes
-                    .checked_add(data_size)
-                    .unwrap_or(usize::MAX);
+                let next_total = if hidden {
+                    total_data_bytes
+                } else {
+                    total_data_bytes.saturating_add(data_size)
+                };
                 if next_total > max_bytes && !entries.is_empty() {
+                    stopped = true;
                     break;
                 }
 
@@ -1056,31 +1138,41 @@ impl Walrus {
                     ));
                 }
 
-                // Handle trimming
-                let mut final_data = data_slice.to_vec();
+                let entry_offset = read_plan.start + buf_offset as u64;
+                let headers_size = meta.headers_size as usize;
+                let mut entry =
+                    meta.into_entry(data_slice.to_vec(), read_plan.blk.id, entry_offset)?;
+
+                if hidden {
+                    hidden_parsed += 1;
+                    initial_trim = 0;
+                }
+
+                // Handle trimming; the trim is relative to the record body, headers included
                 if initial_trim > 0 {
-                    if initial_trim < final_data.len() {
-                        final_data = final_data[initial_trim..].to_vec();
+                    let trim = initial_trim.saturating_sub(headers_size);
+                    if trim < entry.data.len() {
+                        entry.data = entry.data[trim..].to_vec();
                     } else {
-                        final_data.clear();
+                        entry.data.clear();
                     }
                     initial_trim = 0; // Only for first entry
                 }
 
                 // Add to results
-                if !final_data.is_empty() {
+                if !hidden && !entry.data.is_empty() {
                     // Extract topic_id and chunk_idx from the payload prefix for logging
-                    if final_data.len() >= 9 {
-                        let t_idx = final_data[0];
+                    if entry.data.len() >= 9 {
+                        let t_idx = entry.data[0];
                         let mut c_idx_bytes = [0u8; 8];
-                        c_idx_bytes.copy_from_slice(&final_data[1..9]);
+                        c_idx_bytes.copy_from_slice(&entry.data[1..9]);
                         let c_idx = u64::from_be_bytes(c_idx_bytes); // Big-endian
                         info!(
                             "batch_read_for_topic: (stateless) pushing entry with t_idx={}, c_idx={}",
                             t_idx, c_idx
                         );
                     }
-                    entries.push(Entry { data: final_data });
+                    entries.push(entry);
                 }
 
                 total_data_bytes = next_total;
@@ -1102,7 +1194,10 @@ impl Walrus {
             }
         }
 
-        // 5) Commit progress (optional)
+        // 5) Commit progress (optional). A read that only passed over records readers never
+        // return moves the cursor past them even without a checkpoint.
+        let only_hidden = entries_parsed > 0 && hidden_parsed == entries_parsed;
+        let advance = checkpoint || (only_hidden && start_offset.is_none());
         if entries_parsed > 0 {
             enum PersistTarget {
                 Tail { blk_id: u64, off: u64 },
@@ -1112,10 +1207,13 @@ impl Walrus {
             let mut target = PersistTarget::None;
 
             let mut update_state = |info: &mut ColReaderInfo| {
-                if checkpoint {
-                    let mut should_persist_disk = true;
+                if advance {
+                    let mut should_persist_disk = persist && checkpoint;
 
-                    if let ReadConsistency::AtLeastOnce { persist_every } = self.read_consistency {
+                    if checkpoint
+                        && let ReadConsistency::AtLeastOnce { persist_every } =
+                            self.read_consistency
+                    {
                         let every = persist_every.max(1);
                         let total = info.reads_since_persist.saturating_add(entries_parsed);
                         if total >= every {
@@ -1163,7 +1261,7 @@ impl Walrus {
                 // Reacquire
                 let arc = {
                     let map = self.reader.data.read().unwrap();
-                    map.get(col_name).cloned()
+                    map.get(&key).cloned()
                 };
                 if let Some(arc) = arc {
                     if let Ok(mut info) = arc.write() {
@@ -1177,12 +1275,12 @@ impl Walrus {
                 match target {
                     PersistTarget::Tail { blk_id, off } => {
                         if let Ok(mut idx_guard) = self.read_offset_index.write() {
-                            let _ = idx_guard.set(col_name.to_string(), blk_id | TAIL_FLAG, off);
+                            let _ = idx_guard.set(key.clone(), blk_id | TAIL_FLAG, off);
                         }
                     }
                     PersistTarget::Sealed { idx, off } => {
                         if let Ok(mut idx_guard) = self.read_offset_index.write() {
-                            let _ = idx_guard.set(col_name.to_string(), idx, off);
+                            let _ = idx_guard.set(key.clone(), idx, off);
                         }
                     }
                     PersistTarget::None => {}
@@ -1191,9 +1289,9 @@ impl Walrus {
         }
 
         if checkpoint && start_offset.is_none() {
-            self.decrement_topic_entry_count(col_name, entries_parsed as u64);
+            self.decrement_entry_count(group, col_name, entries_parsed as u64);
         }
 
-        Ok(entries)
+        Ok((entries, only_hidden))
     }
 }
@@ -0,0 +1,217 @@
+use super::Walrus;
+use super::reader::{TAIL_FLAG, cursor_key, validate_group};
+use super::seek_index::BlockSummary;
+use crate::wal::block::Block;
+use crate::wal::config::{PREFIX_META_SIZE, debug_print};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+/// Where [`Walrus::seek_topic`] places a topic's read cursor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeekTarget {
+    /// The oldest entry still on disk.
+    Beginning,
+    /// Past the newest entry; only entries appended afterwards are read.
+    End,
+    /// The entry with this sequence number, or the first retained one after it.
+    Sequence(u64),
+    /// The first entry appended at or after this unix timestamp, in milliseconds.
+    Timestamp(u64),
+}
+
+impl Walrus {
+    /// Moves the topic's read cursor and persists it in the read offset index, so
+    /// subsequent reads (including after a restart) resume from there. Returns the
+    /// sequence number of the next entry that will be read.
+    pub fn seek_topic(&self, col_name: &str, target: SeekTarget) -> io::Result<u64> {
+        self.seek_topic_for(None, col_name, target)
+    }
+
+    /// Same as [`Walrus::seek_topic`], for `group`'s cursor on the topic. Other groups
+    /// keep their positions.
+    pub fn seek_topic_as(
+        &self,
+        group: &str,
+        col_name: &str,
+        target: SeekTarget,
+    ) -> io::Result<u64> {
+        validate_group(group)?;
+        self.seek_topic_for(Some(group), col_name, target)
+    }
+
+    pub(super) fn seek_topic_for(
+        &self,
+        group: Option<&str>,
+        col_name: &str,
+        target: SeekTarget,
+    ) -> io::Result<u64> {
+        let key = cursor_key(group, col_name);
+
+        // Take summaries before the writer snapshot so every indexed entry is within it
+        let summaries = self.reader.seek_index.summaries(col_name);
+        let writer_snapshot: Option<(Block, u64)> = {
+            let map = self
+                .writers
+                .read()
+                .map_err(|_| io::Error::other("writers read lock poisoned"))?;
+            match map.get(col_name) {
+                Some(w) => w.snapshot_block().ok(),
+                None => None,
+            }
+        };
+
+        let next_seq = match summaries.last() {
+            Some(last) => last.next_seq,
+            // Nothing left to read, possibly because every block was reclaimed
+            None => match self.existing_writer(col_name)? {
+                Some(writer) => writer.written_tail()?.0,
+                None => self.recovered_next_seq(col_name).unwrap_or(0),
+            },
+        };
+
+        let info_arc = self.cursor_info(group, col_name)?;
+        let mut info = info_arc
+            .write()
+            .map_err(|_| io::Error::other("col info write lock poisoned"))?;
+
+        // Candidate blocks in topic order: sealed blocks still on disk, then the active block
+        let mut file_exists: HashMap<&str, bool> = HashMap::new();
+        let mut candidates: Vec<(Block, u64)> = Vec::new();
+        for block in info.chain.iter() {
+            let exists = *file_exists
+                .entry(block.file_path.as_str())
+                .or_insert_with(|| Path::new(&block.file_path).exists());
+            if exists {
+                candidates.push((block.clone(), block.used));
+            }
+        }
+        let active = writer_snapshot.filter(|(a, _)| !info.chain.iter().any(|b| b.id == a.id));
+        if let Some((block, written)) = active.as_ref() {
+            candidates.push((block.clone(), *written));
+        }
+
+        let by_id: HashMap<u64, &BlockSummary> =
+            summaries.iter().map(|s| (s.block_id, s)).collect();
+
+        let found = candidates.iter().find_map(|(block, limit)| {
+            let summary = by_id.get(&block.id)?;
+            locate_in_block(block, *limit, summary, target).map(|(off, seq)| (block.id, off, seq))
+        });
+
+        let chain_len = info.chain.len();
+        let sealed_idx =
+            found.and_then(|(block_id, _, _)| info.chain.iter().position(|b| b.id == block_id));
+        let (persist_idx, persist_off, pos_seq) = match (found, sealed_idx) {
+            (Some((_, off, seq)), Some(idx)) => {
+                info.cur_block_idx = idx;
+                info.cur_block_offset = off;
+                info.tail_block_id = 0;
+                info.tail_offset = 0;
+                (idx as u64, off, seq)
+            }
+            (Some((block_id, off, seq)), None) => {
+                info.cur_block_idx = chain_len;
+                info.cur_block_offset = 0;
+                info.tail_block_id = block_id;
+                info.tail_offset = off;
+                (block_id | TAIL_FLAG, off, seq)
+            }
+            (None, _) => {
+                info.cur_block_idx = chain_len;
+                info.cur_block_offset = 0;
+                match active {
+                    Some((block, written)) => {
+                        info.tail_block_id = block.id;
+                        info.tail_offset = written;
+                        (block.id | TAIL_FLAG, written, next_seq)
+                    }
+                    None => {
+                        info.tail_block_id = 0;
+                        info.tail_offset = 0;
+                        (chain_len as u64, 0, next_seq)
+                    }
+                }
+            }
+        };
+        info.reads_since_persist = 0;
+        info.hydrated_from_index = true;
+
+        // Blocks behind the cursor are consumed; anything at or after it must stay on disk
+        self.reader
+            .set_blocks_passed(col_name, &key, info.cur_block_idx, &info.chain);
+
+        // Drop the column lock before touching the index to avoid lock inversion
+        drop(info);
+        // Tokens leased before the seek may point anywhere relative to the new position
+        if let Ok(mut committed) = self.reader.committed.lock() {
+            committed.remove(&key);
+        }
+        self.read_offset_index
+            .write()
+            .map_err(|_| io::Error::other("read offset index lock poisoned"))?
+            .set(key, persist_idx, persist_off)?;
+        self.set_entry_count(group, col_name, next_seq.saturating_sub(pos_seq));
+
+        debug_print!(
+            "[reader] seek: col={}, target={:?}, seq={}, idx={}, offset={}",
+            col_name,
+            target,
+            pos_seq,
+            persist_idx & !TAIL_FLAG,
+            persist_off
+        );
+        Ok(pos_seq)
+    }
+}
+
+// Offset and sequence number of the first entry in `block` matching `target`, if any
+fn locate_in_block(
+    block: &Block,
+    limit: u64,
+    summary: &BlockSummary,
+    target: SeekTarget,
+) -> Option<(u64, u64)> {
+    match target {
+        SeekTarget::Beginning => Some((0, summary.first_seq)),
+        SeekTarget::End => None,
+        SeekTarget::Sequence(n) => {
+            if n >= summary.next_seq {
+                return None;
+            }
+            let point = summary.point_for_seq(n);
+            let (mut off, mut seq) = (point.offset, point.seq);
+            while seq < n {
+                let meta = block.read_meta(off).ok()?;
+                off += (PREFIX_META_SIZE + meta.read_size) as u64;
+                seq += 1;
+                if off >= limit {
+                    return None;
+                }
+            }
+            Some((off, seq))
+        }
+        SeekTarget::Timestamp(ms) => {
+            if summary.last_ts < ms {
+                return None;
+            }
+            let point = summary.point_for_timestamp(ms);
+            let (mut off, mut seq) = (point.offset, point.seq);
+            while off < limit && seq < summary.next_seq {
+                let meta = block.read_meta(off).ok()?;
+                // Records from before append times were stored fall back to the block's time
+                let ts = if meta.timestamp_ms == 0 {
+                    summary.first_ts
+                } else {
+                    meta.timestamp_ms
+                };
+                if ts >= ms {
+                    return Some((off, seq));
+                }
+                off += (PREFIX_META_SIZE + meta.read_size) as u64;
+                seq += 1;
+            }
+            None
+        }
+    }
+}
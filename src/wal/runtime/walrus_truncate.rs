@@ -0,0 +1,192 @@
+use super::Walrus;
+use super::reader::{TAIL_FLAG, floor_key, split_cursor_key};
+use super::seek_index::BlockSummary;
+use super::walrus_seek::SeekTarget;
+use crate::wal::block::Block;
+use crate::wal::config::debug_print;
+use std::collections::HashMap;
+use std::io;
+
+impl Walrus {
+    /// Deletes `topic`: every entry appended so far is discarded, along with the cursors
+    /// of all its consumer groups, their persisted positions and the topic's clean
+    /// marker. The topic's blocks are checkpointed, so the background reclaimer removes
+    /// the files once nothing else lives in them.
+    ///
+    /// Deletion is durable once the read offset index is rewritten; recovery drops any
+    /// block of the topic still on disk from then on. Appending to the topic afterwards
+    /// starts it over, with sequence numbers continuing after the deleted entries.
+    /// Readers of the topic should be stopped first; fails with `WouldBlock` while a
+    /// batch write to it is in flight.
+    pub fn delete_topic(&self, topic: &str) -> io::Result<()> {
+        let writer = self
+            .writers
+            .read()
+            .map_err(|_| io::Error::other("writers read lock poisoned"))?
+            .get(topic)
+            .cloned();
+        // Entries appended from here on go to a fresh block and survive the deletion
+        let (writer_next, released) = match &writer {
+            Some(w) => w.release_active_block()?,
+            None => (0, None),
+        };
+        let recovered_next = self.recovered_next_seq(topic).unwrap_or(0);
+        let indexed_next = self
+            .reader
+            .seek_index
+            .summaries(topic)
+            .last()
+            .map_or(0, |s| s.next_seq);
+
+        let mut blocks = self.reader.forget_topic(topic);
+        blocks.extend(released);
+
+        let floor = {
+            let mut idx = self
+                .read_offset_index
+                .write()
+                .map_err(|_| io::Error::other("read offset index lock poisoned"))?;
+            let floor = idx
+                .get(&floor_key(topic))
+                .map_or(0, |pos| pos.cur_block_idx)
+                .max(writer_next)
+                .max(recovered_next)
+                .max(indexed_next);
+            let keys: Vec<String> = idx
+                .keys()
+                .filter(|key| split_cursor_key(key).1 == topic)
+                .map(str::to_string)
+                .collect();
+            // The chains are gone, so no persisted position counts dropped blocks anymore
+            idx.update(&keys, vec![(floor_key(topic), floor, 0)])?;
+            floor
+        };
+
+        for block in &blocks {
+            self.allocator
+                .tracker()
+                .set_checkpointed_true(block.id as usize);
+        }
+        self.reader.seek_index.discard_before(topic, floor);
+        self.reader.seek_index.forget_topic(topic);
+        self.prune_aborted_txns();
+        self.forget_topic_state(topic, writer.is_none().then_some(floor))?;
+
+        debug_print!(
+            "[truncate] deleted topic: col={}, floor={}, blocks={}",
+            topic,
+            floor,
+            blocks.len()
+        );
+        Ok(())
+    }
+
+    /// Discards the oldest entries of `topic`, up to `position`, and returns the sequence
+    /// number of the oldest entry kept. Cursors of every consumer group that were behind
+    /// the discarded entries move past them.
+    ///
+    /// Truncation works on whole sealed blocks: a block is discarded only when all of its
+    /// entries come before `position`, so entries sharing a block with the first one kept
+    /// stay readable, and the writer's active block is never discarded. The discarded
+    /// blocks are checkpointed and their files reclaimed like fully consumed ones.
+    pub fn truncate_topic_before(&self, topic: &str, position: SeekTarget) -> io::Result<u64> {
+        let summaries = self.reader.seek_index.summaries(topic);
+        let chain: Vec<Block> = match self
+            .reader
+            .data
+            .read()
+            .map_err(|_| io::Error::other("reader map read lock poisoned"))?
+            .get(topic)
+        {
+            Some(info_arc) => info_arc
+                .read()
+                .map_err(|_| io::Error::other("col info read lock poisoned"))?
+                .chain
+                .clone(),
+            None => Vec::new(),
+        };
+        let already = self.reader.dropped_blocks(topic);
+
+        let by_id: HashMap<u64, &BlockSummary> =
+            summaries.iter().map(|s| (s.block_id, s)).collect();
+        let mut dropped = already;
+        let mut floor = None;
+        for (idx, block) in chain.iter().enumerate().skip(already) {
+            // Blocks of reclaimed files lost their summary; every group passed them
+            let Some(summary) = by_id.get(&block.id) else {
+                dropped = idx + 1;
+                continue;
+            };
+            let before = match position {
+                SeekTarget::Beginning => false,
+                SeekTarget::End => true,
+                SeekTarget::Sequence(n) => summary.next_seq <= n,
+                SeekTarget::Timestamp(ms) => summary.last_ts < ms,
+            };
+            if !before {
+                break;
+            }
+            dropped = idx + 1;
+            floor = Some(summary.next_seq);
+        }
+        let Some(floor) = floor else {
+            return Ok(summaries.first().map_or(0, |s| s.first_seq));
+        };
+        let next_seq = summaries.last().map_or(floor, |s| s.next_seq);
+
+        // Persisted first: a crash from here on recovers the topic without these blocks.
+        // Positions behind the floor move to the first kept block, including tail
+        // positions naming a discarded block, which recovery would not find.
+        {
+            let mut idx = self
+                .read_offset_index
+                .write()
+                .map_err(|_| io::Error::other("read offset index lock poisoned"))?;
+            let discarded: Vec<u64> = chain.iter().take(dropped).map(|b| b.id).collect();
+            let mut updates = Vec::new();
+            for key in idx.keys() {
+                let (group, col) = split_cursor_key(key);
+                if col != topic || group == Some("") {
+                    continue;
+                }
+                let Some(pos) = idx.get(key) else {
+                    continue;
+                };
+                let behind = if pos.cur_block_idx & TAIL_FLAG != 0 {
+                    discarded.contains(&(pos.cur_block_idx & !TAIL_FLAG))
+                } else {
+                    pos.cur_block_idx < dropped as u64
+                };
+                if behind {
+                    updates.push((key.to_string(), dropped as u64, 0));
+                }
+            }
+            let (old_floor, old_dropped) = idx
+                .get(&floor_key(topic))
+                .map_or((0, 0), |pos| (pos.cur_block_idx, pos.cur_block_offset));
+            updates.push((
+                floor_key(topic),
+                floor.max(old_floor),
+                (dropped as u64).max(old_dropped),
+            ));
+            idx.update(&[], updates)?;
+        }
+
+        let moved = self.reader.discard_blocks(topic, dropped, &chain);
+        for key in &moved {
+            let (group, _) = split_cursor_key(key);
+            self.set_entry_count(group, topic, next_seq.saturating_sub(floor));
+        }
+        self.reader.seek_index.discard_before(topic, floor);
+        self.prune_aborted_txns();
+
+        debug_print!(
+            "[truncate] truncated topic: col={}, floor={}, dropped_blocks={}, moved_cursors={}",
+            topic,
+            floor,
+            dropped,
+            moved.len()
+        );
+        Ok(floor)
+    }
+}
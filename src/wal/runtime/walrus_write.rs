@@ -1,19 +1,118 @@
 use super::Walrus;
+use super::writer::Writer;
+use crate::wal::block::encode_headers;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
 
 impl Walrus {
-    pub fn append_for_topic(&self, col_name: &str, raw_bytes: &[u8]) -> std::io::Result<()> {
+    /// Appends one entry and returns its per-topic sequence number.
+    pub fn append_for_topic(&self, col_name: &str, raw_bytes: &[u8]) -> std::io::Result<u64> {
         self.mark_topic_dirty(col_name);
         let writer = self.get_or_create_writer(col_name)?;
-        writer.write(raw_bytes)?;
+        let seq = self.write_entry(writer, raw_bytes, &[])?;
         self.increment_topic_entry_count(col_name, 1);
-        Ok(())
+        Ok(seq)
+    }
+
+    /// Like [`Walrus::append_for_topic`], storing `headers` with the entry. They come
+    /// back on [`Entry::headers`](crate::Entry::headers); their encoded size is capped at 64KB.
+    pub fn append_for_topic_with_headers(
+        &self,
+        col_name: &str,
+        raw_bytes: &[u8],
+        headers: &[(&str, &[u8])],
+    ) -> std::io::Result<u64> {
+        let encoded = encode_headers(headers)?;
+        self.mark_topic_dirty(col_name);
+        let writer = self.get_or_create_writer(col_name)?;
+        let seq = self.write_entry(writer, raw_bytes, &encoded)?;
+        self.increment_topic_entry_count(col_name, 1);
+        Ok(seq)
+    }
+
+    /// Like [`Walrus::append_for_topic`], but only returns once the entry is fsynced,
+    /// whatever the fsync schedule. Concurrent durable appends to a topic share fsyncs.
+    pub fn append_for_topic_durable(
+        &self,
+        col_name: &str,
+        raw_bytes: &[u8],
+    ) -> std::io::Result<u64> {
+        let seq = self.append_for_topic(col_name, raw_bytes)?;
+        let writer = self.get_or_create_writer(col_name)?;
+        writer.sync_through(seq + 1)?;
+        Ok(seq)
     }
 
-    pub fn batch_append_for_topic(&self, col_name: &str, batch: &[&[u8]]) -> std::io::Result<()> {
+    /// Atomically appends a batch and returns the sequence numbers assigned to it,
+    /// in batch order. An empty batch yields an empty range.
+    pub fn batch_append_for_topic(
+        &self,
+        col_name: &str,
+        batch: &[&[u8]],
+    ) -> std::io::Result<Range<u64>> {
         self.mark_topic_dirty(col_name);
         let writer = self.get_or_create_writer(col_name)?;
-        writer.batch_write(batch)?;
+        let seqs = writer.batch_write(batch)?;
         self.increment_topic_entry_count(col_name, batch.len() as u64);
+        Ok(seqs)
+    }
+
+    /// The topic's writer, if this instance has appended to it.
+    pub(super) fn existing_writer(&self, col_name: &str) -> std::io::Result<Option<Arc<Writer>>> {
+        let map = self
+            .writers
+            .read()
+            .map_err(|_| std::io::Error::other("writers read lock poisoned"))?;
+        Ok(map.get(col_name).cloned())
+    }
+
+    fn write_entry(
+        &self,
+        writer: Arc<Writer>,
+        raw_bytes: &[u8],
+        headers: &[u8],
+    ) -> std::io::Result<u64> {
+        match &self.group_commit {
+            Some(group) => group.append(writer, raw_bytes, headers),
+            None => writer.write(raw_bytes, headers),
+        }
+    }
+
+    /// Blocks until every entry appended to `col_name` so far is fsynced, whatever
+    /// the fsync schedule. Concurrent callers share a single fsync.
+    pub fn sync_topic(&self, col_name: &str) -> std::io::Result<()> {
+        match self.existing_writer(col_name)? {
+            Some(writer) => writer.sync(),
+            // Nothing appended to this topic by this instance
+            None => Ok(()),
+        }
+    }
+
+    /// Blocks until every entry appended so far, on any topic, is fsynced. Each data
+    /// file is fsynced once no matter how many topics wrote to it.
+    pub fn sync_all(&self) -> std::io::Result<()> {
+        let writers: Vec<_> = {
+            let map = self
+                .writers
+                .read()
+                .map_err(|_| std::io::Error::other("writers read lock poisoned"))?;
+            map.values().cloned().collect()
+        };
+
+        let mut tails = Vec::with_capacity(writers.len());
+        let mut files = HashMap::new();
+        for writer in &writers {
+            let (target, block) = writer.written_tail()?;
+            files.entry(block.file_path.clone()).or_insert(block.mmap);
+            tails.push(target);
+        }
+        for mmap in files.values() {
+            mmap.flush()?;
+        }
+        for (writer, target) in writers.iter().zip(tails) {
+            writer.mark_synced(target);
+        }
         Ok(())
     }
 }
@@ -1,20 +1,21 @@
-use super::allocator::{BlockAllocator, FileStateTracker};
+use super::allocator::BlockAllocator;
 use super::reader::Reader;
-use crate::wal::block::Block;
+use super::topic_signal::TopicSignal;
+use crate::wal::block::{Block, TxnMark};
 #[cfg(target_os = "linux")]
 use crate::wal::block::Metadata;
 use crate::wal::config::{
-    DEFAULT_BLOCK_SIZE, FsyncSchedule, MAX_BATCH_BYTES, MAX_BATCH_ENTRIES, PREFIX_META_SIZE,
-    debug_print,
+    FsyncSchedule, MAX_BATCH_BYTES, MAX_BATCH_ENTRIES, PREFIX_META_SIZE, debug_print, now_millis,
 };
 #[cfg(target_os = "linux")]
-use crate::wal::config::{USE_FD_BACKEND, checksum64};
+use crate::wal::config::{StorageBackend, checksum64};
 use std::collections::HashSet;
 #[cfg(target_os = "linux")]
 use std::convert::TryFrom;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::mpsc;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
 
 #[cfg(target_os = "linux")]
 use std::os::unix::io::AsRawFd;
@@ -28,6 +29,24 @@ pub(super) struct Writer {
     current_offset: Mutex<u64>,
     fsync_schedule: FsyncSchedule,
     is_batch_writing: AtomicBool,
+    // Set while an async append is in flight; it holds neither lock during its write, so
+    // synchronous writes wait on `async_done` for it to finish
+    async_append: Mutex<bool>,
+    async_done: Condvar,
+    // Next per-topic sequence number; only advanced while holding `current_block`
+    next_seq: AtomicU64,
+    sync_state: Mutex<SyncState>,
+    sync_done: Condvar,
+    // Bumped whenever new entries become readable
+    signal: Arc<TopicSignal>,
+}
+
+// Group commit bookkeeping for explicit syncs
+struct SyncState {
+    // Every entry below this sequence number is known to be fsynced
+    synced_seq: u64,
+    // A caller is fsyncing on behalf of everyone waiting
+    syncing: bool,
 }
 
 impl Writer {
@@ -38,36 +57,50 @@ impl Writer {
         col: String,
         publisher: Arc<mpsc::Sender<String>>,
         fsync_schedule: FsyncSchedule,
+        next_seq: u64,
     ) -> Self {
         Writer {
             allocator,
             current_block: Mutex::new(current_block),
+            signal: reader.signals.for_topic(&col),
             reader,
             col: col.clone(),
             publisher,
             current_offset: Mutex::new(0),
             fsync_schedule,
             is_batch_writing: AtomicBool::new(false),
+            async_append: Mutex::new(false),
+            async_done: Condvar::new(),
+            next_seq: AtomicU64::new(next_seq),
+            // Recovered entries were written by an earlier process
+            sync_state: Mutex::new(SyncState {
+                synced_seq: next_seq,
+                syncing: false,
+            }),
+            sync_done: Condvar::new(),
         }
     }
 
-    pub(super) fn write(&self, data: &[u8]) -> std::io::Result<()> {
-        // Check if batch write is in progress
+    /// Appends one record; `headers` is the encoded header block stored ahead of `data`.
+    pub(super) fn write(&self, data: &[u8], headers: &[u8]) -> std::io::Result<u64> {
+        // A batch holds the locks while it plans; fail now rather than queue behind it
         if self.is_batch_writing.load(Ordering::Acquire) {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::WouldBlock,
                 "batch write in progress for this topic",
             ));
         }
+        let (mut block, mut cur) = self.lock_active()?;
 
-        let mut block = self.current_block.lock().map_err(|_| {
-            std::io::Error::new(std::io::ErrorKind::Other, "current_block lock poisoned")
-        })?;
-        let mut cur = self.current_offset.lock().map_err(|_| {
-            std::io::Error::new(std::io::ErrorKind::Other, "current_offset lock poisoned")
-        })?;
+        // Checked again under the locks, for a batch that started in between
+        if self.is_batch_writing.load(Ordering::Acquire) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::WouldBlock,
+                "batch write in progress for this topic",
+            ));
+        }
 
-        let need = (PREFIX_META_SIZE as u64) + (data.len() as u64);
+        let need = (PREFIX_META_SIZE as u64) + (headers.len() as u64) + (data.len() as u64);
         if *cur + need > block.limit {
             debug_print!(
                 "[writer] sealing: col={}, block_id={}, used={}, need={}, limit={}",
@@ -77,17 +110,20 @@ impl Writer {
                 need,
                 block.limit
             );
-            FileStateTracker::set_block_unlocked(block.id as usize);
+            // Allocate before sealing so an entry the allocator refuses leaves the
+            // current block active instead of sealed and still being written to.
+            // SAFETY: We hold `current_block` and `current_offset` mutexes, so
+            // this writer has exclusive ownership of the active block. The
+            // allocator's internal lock ensures unique block handout.
+            let new_block = unsafe { self.allocator.alloc_block(need) }?;
+            self.allocator.tracker().set_block_unlocked(block.id as usize);
             let mut sealed = block.clone();
             sealed.used = *cur;
             sealed.mmap.flush()?;
+            self.record_sealed(&sealed);
             let _ = self.reader.append_block_to_chain(&self.col, sealed);
             debug_print!("[writer] appended sealed block to chain: col={}", self.col);
             // switch to new block
-            // SAFETY: We hold `current_block` and `current_offset` mutexes, so
-            // this writer has exclusive ownership of the active block. The
-            // allocator's internal lock ensures unique block handout.
-            let new_block = unsafe { self.allocator.alloc_block(need) }?;
             debug_print!(
                 "[writer] switched to new block: col={}, new_block_id={}",
                 self.col,
@@ -96,23 +132,31 @@ impl Writer {
             *block = new_block;
             *cur = 0;
         }
-        let next_block_start = block.offset + block.limit; // simplistic for now
-        block.write(*cur, data, &self.col, next_block_start)?;
+        let seq = self.next_seq.load(Ordering::Acquire);
+        let timestamp_ms = now_millis();
+        block.write(*cur, headers, data, &self.col, seq, timestamp_ms)?;
         debug_print!(
-            "[writer] wrote: col={}, block_id={}, offset_before={}, bytes={}, offset_after={}",
+            "[writer] wrote: col={}, block_id={}, seq={}, offset_before={}, bytes={}, offset_after={}",
             self.col,
             block.id,
+            seq,
             *cur,
             need,
             *cur + need
         );
+        self.reader
+            .seek_index
+            .record(&self.col, block.id, seq, *cur, timestamp_ms, TxnMark::None);
         *cur += need;
+        self.next_seq.store(seq + 1, Ordering::Release);
+        self.signal.notify();
 
         // Handle fsync based on schedule
         match self.fsync_schedule {
             FsyncSchedule::SyncEach => {
                 // Immediate mmap flush, skip background flusher
                 block.mmap.flush()?;
+                self.mark_synced(seq + 1);
                 debug_print!(
                     "[writer] immediate fsync: col={}, block_id={}",
                     self.col,
@@ -129,10 +173,10 @@ impl Writer {
             }
         }
 
-        Ok(())
+        Ok(seq)
     }
 
-    pub(super) fn batch_write(&self, batch: &[&[u8]]) -> std::io::Result<()> {
+    pub(super) fn batch_write(&self, batch: &[&[u8]]) -> std::io::Result<Range<u64>> {
         // RAII guard to ensure batch flag is released
         struct BatchGuard<'a> {
             flag: &'a AtomicBool,
@@ -145,27 +189,11 @@ impl Writer {
         }
 
         // Phase 0: Validate batch size
-        if batch.len() > MAX_BATCH_ENTRIES {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                format!("batch exceeds {} entry limit", MAX_BATCH_ENTRIES),
-            ));
-        }
-
-        let total_bytes: u64 = batch
-            .iter()
-            .map(|data| (PREFIX_META_SIZE as u64) + (data.len() as u64))
-            .sum();
-
-        if total_bytes > MAX_BATCH_BYTES {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "batch exceeds 10GB limit",
-            ));
-        }
+        let total_bytes = validate_batch(batch.iter().copied())?;
 
         if batch.is_empty() {
-            return Ok(());
+            let next = self.next_seq.load(Ordering::Acquire);
+            return Ok(next..next);
         }
 
         // Try to acquire batch write flag
@@ -193,20 +221,21 @@ impl Writer {
         );
 
         // Phase 1: Pre-allocation & Planning
-        let mut block = self.current_block.lock().map_err(|_| {
-            std::io::Error::new(std::io::ErrorKind::Other, "current_block lock poisoned")
-        })?;
-        let mut cur_offset = self.current_offset.lock().map_err(|_| {
-            std::io::Error::new(std::io::ErrorKind::Other, "current_offset lock poisoned")
-        })?;
+        let (mut block, mut cur_offset) = self.lock_active()?;
 
         let mut revert_info = BatchRevertInfo {
             original_offset: *cur_offset,
             allocated_block_ids: Vec::new(),
         };
+        // Entry i of the batch gets `first_seq + i`; committed only once the batch succeeds
+        let first_seq = self.next_seq.load(Ordering::Acquire);
+        let seqs = first_seq..first_seq + batch.len() as u64;
+        let timestamp_ms = now_millis();
 
         // Build write plan: (Block, in_block_offset, batch_index)
         let mut write_plan: Vec<(Block, u64, usize)> = Vec::new();
+        // Blocks filled by the batch; they go to the manifest once it is written
+        let mut sealed_blocks: Vec<Block> = Vec::new();
         let mut batch_idx = 0;
 
         // Use a LOCAL offset for planning, don't update the writer's offset yet
@@ -231,16 +260,19 @@ impl Writer {
                     need,
                     block.limit
                 );
-                FileStateTracker::set_block_unlocked(block.id as usize);
+                self.allocator.tracker().set_block_unlocked(block.id as usize);
                 let mut sealed = block.clone();
                 sealed.used = planning_offset;
                 sealed.mmap.flush()?;
+                sealed_blocks.push(sealed.clone());
                 let _ = self.reader.append_block_to_chain(&self.col, sealed);
 
                 // Allocate new block
                 // SAFETY: We hold locks, so this writer has exclusive ownership
-                let new_block =
-                    unsafe { self.allocator.alloc_block(need.max(DEFAULT_BLOCK_SIZE))? };
+                let new_block = unsafe {
+                    self.allocator
+                        .alloc_block(need.max(self.allocator.geometry().block_size))?
+                };
                 debug_print!("[batch] allocated new block_id={}", new_block.id);
 
                 revert_info.allocated_block_ids.push(new_block.id);
@@ -266,18 +298,25 @@ impl Writer {
 
         #[cfg(target_os = "linux")]
         {
-            if USE_FD_BACKEND.load(Ordering::Relaxed) {
+            if self.allocator.storage().backend == StorageBackend::Fd {
                 // Prefer io_uring for FD backend, but fall back to the portable path if io_uring
                 // is unavailable (e.g. kernel/config restrictions) so behavior remains correct.
                 match self.submit_batch_via_io_uring(
                     &write_plan,
                     batch,
+                    first_seq,
+                    timestamp_ms,
                     &mut revert_info,
                     &mut *cur_offset,
                     planning_offset,
                     total_bytes_usize,
                 ) {
-                    Ok(()) => return Ok(()),
+                    Ok(()) => {
+                        self.index_batch(&write_plan, &sealed_blocks, first_seq, timestamp_ms);
+                        self.next_seq.store(seqs.end, Ordering::Release);
+                        self.signal.notify();
+                        return Ok(seqs);
+                    }
                     Err(e) => {
                         if e.to_string().contains("io_uring init failed") {
                             debug_print!(
@@ -295,9 +334,9 @@ impl Writer {
         // Fallback: use regular block.write() in a loop (mmap backend or non-Linux builds)
         for (blk, offset, data_idx) in write_plan.iter() {
             let data = batch[*data_idx];
-            let next_block_start = blk.offset + blk.limit;
+            let seq = first_seq + *data_idx as u64;
 
-            if let Err(e) = blk.write(*offset, data, &self.col, next_block_start) {
+            if let Err(e) = blk.write(*offset, &[], data, &self.col, seq, timestamp_ms) {
                 // Clean up any partially written headers up to and including the failed index
                 for (w_blk, w_off, _) in write_plan[0..=(*data_idx)].iter() {
                     let _ = w_blk.zero_range(*w_off, PREFIX_META_SIZE as u64);
@@ -313,7 +352,7 @@ impl Writer {
 
                 *cur_offset = revert_info.original_offset;
                 for block_id in revert_info.allocated_block_ids {
-                    FileStateTracker::set_block_unlocked(block_id as usize);
+                    self.allocator.tracker().set_block_unlocked(block_id as usize);
                 }
                 return Err(e);
             }
@@ -330,6 +369,9 @@ impl Writer {
 
         // NOW update the writer's offset to make data visible to readers
         *cur_offset = planning_offset;
+        self.index_batch(&write_plan, &sealed_blocks, first_seq, timestamp_ms);
+        self.next_seq.store(seqs.end, Ordering::Release);
+        self.signal.notify();
 
         debug_print!(
             "[batch] SUCCESS (mmap): wrote {} entries, {} bytes to topic={}",
@@ -337,14 +379,17 @@ impl Writer {
             total_bytes,
             self.col
         );
-        Ok(())
+        Ok(seqs)
     }
 
     #[cfg(target_os = "linux")]
+    #[allow(clippy::too_many_arguments)]
     fn submit_batch_via_io_uring(
         &self,
         write_plan: &[(Block, u64, usize)],
         batch: &[&[u8]],
+        first_seq: u64,
+        timestamp_ms: u64,
         revert_info: &mut BatchRevertInfo,
         cur_offset: &mut u64,
         planning_offset: u64,
@@ -369,19 +414,13 @@ impl Writer {
                 owned_by: self.col.to_string(),
                 next_block_start,
                 checksum: checksum64(data),
+                seq: first_seq + *data_idx as u64,
+                timestamp_ms,
+                headers_size: 0,
+                txn_id: 0,
+                txn_kind: 0,
             };
-
-            let meta_bytes = rkyv::to_bytes::<_, 256>(&new_meta).map_err(|e| {
-                std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("serialize metadata failed: {:?}", e),
-                )
-            })?;
-
-            let mut meta_buffer = vec![0u8; PREFIX_META_SIZE];
-            meta_buffer[0] = (meta_bytes.len() & 0xFF) as u8;
-            meta_buffer[1] = ((meta_bytes.len() >> 8) & 0xFF) as u8;
-            meta_buffer[2..2 + meta_bytes.len()].copy_from_slice(&meta_bytes);
+            let meta_buffer = new_meta.encode_prefix()?;
 
             let mut combined = Vec::with_capacity(PREFIX_META_SIZE + data.len());
             combined.extend_from_slice(&meta_buffer);
@@ -396,7 +435,7 @@ impl Writer {
                 // Rollback and fail
                 *cur_offset = revert_info.original_offset;
                 for block_id in revert_info.allocated_block_ids.iter() {
-                    FileStateTracker::set_block_unlocked(*block_id as usize);
+                    self.allocator.tracker().set_block_unlocked(*block_id as usize);
                 }
                 return Err(std::io::Error::new(
                     std::io::ErrorKind::Unsupported,
@@ -475,7 +514,7 @@ impl Writer {
                     // Rollback
                     *cur_offset = revert_info.original_offset;
                     for block_id in revert_info.allocated_block_ids.iter() {
-                        FileStateTracker::set_block_unlocked(*block_id as usize);
+                        self.allocator.tracker().set_block_unlocked(*block_id as usize);
                     }
                     return Err(std::io::Error::new(
                         std::io::ErrorKind::Other,
@@ -520,7 +559,7 @@ impl Writer {
                 // Rollback
                 *cur_offset = revert_info.original_offset;
                 for block_id in revert_info.allocated_block_ids.iter() {
-                    FileStateTracker::set_block_unlocked(*block_id as usize);
+                    self.allocator.tracker().set_block_unlocked(*block_id as usize);
                 }
                 Err(e)
             }
@@ -528,6 +567,60 @@ impl Writer {
     }
 }
 
+impl Writer {
+    fn index_batch(
+        &self,
+        write_plan: &[(Block, u64, usize)],
+        sealed: &[Block],
+        first_seq: u64,
+        timestamp_ms: u64,
+    ) {
+        for (blk, offset, data_idx) in write_plan.iter() {
+            self.reader.seek_index.record(
+                &self.col,
+                blk.id,
+                first_seq + *data_idx as u64,
+                *offset,
+                timestamp_ms,
+                TxnMark::None,
+            );
+        }
+        for block in sealed {
+            self.record_sealed(block);
+        }
+    }
+
+    /// Records a sealed block and the summary of its entries in the manifest.
+    fn record_sealed(&self, sealed: &Block) {
+        if let Some(summary) = self.reader.seek_index.summary(&self.col, sealed.id) {
+            self.allocator.manifest().record(sealed, &self.col, &summary);
+        }
+    }
+}
+
+/// Checks the payloads of a batch against the entry and byte limits of a batch write
+/// and returns the bytes their records take up, headers aside.
+pub(super) fn validate_batch<'a>(
+    batch: impl IntoIterator<Item = &'a [u8]>,
+) -> std::io::Result<u64> {
+    let (entries, total_bytes) = batch.into_iter().fold((0usize, 0u64), |(n, bytes), data| {
+        (n + 1, bytes + (PREFIX_META_SIZE as u64) + (data.len() as u64))
+    });
+    if entries > MAX_BATCH_ENTRIES {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("batch exceeds {} entry limit", MAX_BATCH_ENTRIES),
+        ));
+    }
+    if total_bytes > MAX_BATCH_BYTES {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "batch exceeds 10GB limit",
+        ));
+    }
+    Ok(total_bytes)
+}
+
 struct BatchRevertInfo {
     original_offset: u64,
     allocated_block_ids: Vec<u64>,
@@ -543,4 +636,467 @@ impl Writer {
         })?;
         Ok((block.clone(), *offset))
     }
+
+    /// Sequence number of the next entry and the block it will go to. Every entry below
+    /// it sits either in that block or in a sealed block, which was flushed when sealed.
+    pub(super) fn written_tail(&self) -> std::io::Result<(u64, Block)> {
+        let block = self
+            .current_block
+            .lock()
+            .map_err(|_| std::io::Error::other("current_block lock poisoned"))?;
+        Ok((self.next_seq.load(Ordering::Acquire), block.clone()))
+    }
+
+    /// Whether a batch or async append to this topic is in flight.
+    pub(super) fn batch_in_flight(&self) -> bool {
+        self.is_batch_writing.load(Ordering::Acquire)
+            || self.async_append.lock().map_or(true, |flag| *flag)
+    }
+
+    /// Locks the active block and offset for a synchronous write, first waiting for an
+    /// async append in flight to finish.
+    fn lock_active(&self) -> std::io::Result<(MutexGuard<'_, Block>, MutexGuard<'_, u64>)> {
+        loop {
+            let mut in_flight = self
+                .async_append
+                .lock()
+                .map_err(|_| std::io::Error::other("async append lock poisoned"))?;
+            while *in_flight {
+                in_flight = self
+                    .async_done
+                    .wait(in_flight)
+                    .map_err(|_| std::io::Error::other("async append lock poisoned"))?;
+            }
+            drop(in_flight);
+            let block = self
+                .current_block
+                .lock()
+                .map_err(|_| std::io::Error::other("current_block lock poisoned"))?;
+            let cur = self
+                .current_offset
+                .lock()
+                .map_err(|_| std::io::Error::other("current_offset lock poisoned"))?;
+            // An async append may have started in between and be waiting for these locks
+            let started = self
+                .async_append
+                .lock()
+                .map_err(|_| std::io::Error::other("async append lock poisoned"))?;
+            if !*started {
+                drop(started);
+                return Ok((block, cur));
+            }
+        }
+    }
+
+    /// Moves the writer to a fresh block without handing the current one to readers, for
+    /// a topic being deleted. Returns the sequence number of the next entry and the
+    /// released block, if anything was written to it; the caller owns its checkpoint.
+    pub(super) fn release_active_block(&self) -> std::io::Result<(u64, Option<Block>)> {
+        let (mut block, mut cur) = self.lock_active()?;
+        if self.is_batch_writing.load(Ordering::Acquire) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::WouldBlock,
+                "batch write in progress for this topic",
+            ));
+        }
+        let next_seq = self.next_seq.load(Ordering::Acquire);
+        if *cur == 0 {
+            return Ok((next_seq, None));
+        }
+        // SAFETY: We hold `current_block` and `current_offset`, so this writer exclusively
+        // owns the active block; the allocator's internal lock ensures unique handout.
+        let new_block = unsafe { self.allocator.get_next_available_block() }?;
+        self.allocator.tracker().set_block_unlocked(block.id as usize);
+        let mut released = std::mem::replace(&mut *block, new_block);
+        released.used = *cur;
+        *cur = 0;
+        debug_print!(
+            "[writer] released active block: col={}, block_id={}, used={}",
+            self.col,
+            released.id,
+            released.used
+        );
+        Ok((next_seq, Some(released)))
+    }
+
+    /// Flushes the active block and records it in the manifest, for an instance shutting
+    /// down: the next one starts on new blocks, so it is as good as sealed.
+    pub(super) fn record_active_block(&self) -> std::io::Result<()> {
+        let block = self
+            .current_block
+            .lock()
+            .map_err(|_| std::io::Error::other("current_block lock poisoned"))?;
+        let cur = self
+            .current_offset
+            .lock()
+            .map_err(|_| std::io::Error::other("current_offset lock poisoned"))?;
+        // An async append still in flight may yet write to it; recovery scans it instead
+        if *cur == 0 || self.batch_in_flight() {
+            return Ok(());
+        }
+        block.mmap.flush()?;
+        let mut active = block.clone();
+        active.used = *cur;
+        self.record_sealed(&active);
+        Ok(())
+    }
+
+    /// Records that every entry below `seq` has been fsynced and wakes waiting syncs.
+    pub(super) fn mark_synced(&self, seq: u64) {
+        if let Ok(mut state) = self.sync_state.lock() {
+            state.synced_seq = state.synced_seq.max(seq);
+        }
+        self.sync_done.notify_all();
+    }
+
+    /// Blocks until every entry written so far is fsynced.
+    pub(super) fn sync(&self) -> std::io::Result<()> {
+        self.sync_through(self.next_seq.load(Ordering::Acquire))
+    }
+
+    /// Blocks until every entry below `seq` is fsynced. Concurrent callers share one
+    /// fsync: the first one without a sync in flight flushes everything written so far
+    /// and the others wait for it, then only start another round if still needed.
+    pub(super) fn sync_through(&self, seq: u64) -> std::io::Result<()> {
+        let poisoned = || std::io::Error::other("sync_state lock poisoned");
+        let mut state = self.sync_state.lock().map_err(|_| poisoned())?;
+        loop {
+            if state.synced_seq >= seq {
+                return Ok(());
+            }
+            if !state.syncing {
+                break;
+            }
+            state = self.sync_done.wait(state).map_err(|_| poisoned())?;
+        }
+        state.syncing = true;
+        drop(state);
+
+        let result = self.written_tail().and_then(|(target, block)| {
+            block.mmap.flush()?;
+            debug_print!(
+                "[writer] explicit sync: col={}, block_id={}, through_seq={}",
+                self.col,
+                block.id,
+                target
+            );
+            Ok(target)
+        });
+
+        let mut state = self.sync_state.lock().map_err(|_| poisoned())?;
+        state.syncing = false;
+        if let Ok(target) = result {
+            state.synced_seq = state.synced_seq.max(target);
+        }
+        drop(state);
+        self.sync_done.notify_all();
+        result.map(|_| ())
+    }
+}
+
+/// One record planned by a group commit or an async append: its block, offset in that
+/// block, encoded bytes and sequence number.
+pub(super) struct StagedRecord {
+    pub(super) block: Block,
+    pub(super) offset: u64,
+    pub(super) bytes: Vec<u8>,
+    seq: u64,
+    txn: TxnMark,
+}
+
+/// Records planned against a copy of the writer's active block and offset. Readers see
+/// none of them until the plan is published.
+struct WritePlan {
+    block: Block,
+    cur: u64,
+    next_seq: u64,
+    timestamp_ms: u64,
+    // Blocks filled by this plan; they join the reader chain only on publish
+    sealed: Vec<Block>,
+    allocated_block_ids: Vec<u64>,
+    records: Vec<StagedRecord>,
+}
+
+impl WritePlan {
+    fn new(block: &Block, cur: u64, next_seq: u64) -> Self {
+        WritePlan {
+            block: block.clone(),
+            cur,
+            next_seq,
+            timestamp_ms: now_millis(),
+            sealed: Vec::new(),
+            allocated_block_ids: Vec::new(),
+            records: Vec::new(),
+        }
+    }
+
+    /// Plans one record and returns its sequence number. An entry the allocator refuses
+    /// fails on its own and leaves the records planned so far untouched.
+    fn push(
+        &mut self,
+        writer: &Writer,
+        data: &[u8],
+        headers: &[u8],
+        txn: TxnMark,
+    ) -> std::io::Result<u64> {
+        let need = (PREFIX_META_SIZE as u64) + (headers.len() as u64) + (data.len() as u64);
+        if self.cur + need > self.block.limit {
+            // SAFETY: The caller holds the writer's locks or its batch flag, so this plan
+            // exclusively owns the active block; the allocator hands out unique blocks.
+            let new_block = unsafe { writer.allocator.alloc_block(need) }?;
+            let mut sealed = std::mem::replace(&mut self.block, new_block);
+            sealed.used = self.cur;
+            self.sealed.push(sealed);
+            self.allocated_block_ids.push(self.block.id);
+            self.cur = 0;
+        }
+        let seq = self.next_seq;
+        let bytes = self
+            .block
+            .encode_record(headers, data, &writer.col, seq, self.timestamp_ms, txn)?;
+        self.records.push(StagedRecord {
+            block: self.block.clone(),
+            offset: self.cur,
+            bytes,
+            seq,
+            txn,
+        });
+        self.cur += need;
+        self.next_seq += 1;
+        Ok(seq)
+    }
+}
+
+impl Writer {
+    /// Makes a written plan visible: its sealed blocks join the reader chain, then the
+    /// active block, offset and sequence number move past its records. `synced` says
+    /// whether the records were fsynced along with the write.
+    fn publish(&self, plan: WritePlan, block: &mut Block, cur: &mut u64, synced: bool) {
+        for sealed in &plan.sealed {
+            self.allocator.tracker().set_block_unlocked(sealed.id as usize);
+            let _ = self.reader.append_block_to_chain(&self.col, sealed.clone());
+        }
+        let mut touched = HashSet::new();
+        for record in &plan.records {
+            self.reader.seek_index.record(
+                &self.col,
+                record.block.id,
+                record.seq,
+                record.offset,
+                plan.timestamp_ms,
+                record.txn,
+            );
+            touched.insert(record.block.file_path.clone());
+        }
+        for sealed in &plan.sealed {
+            self.record_sealed(sealed);
+        }
+        *block = plan.block;
+        *cur = plan.cur;
+        self.next_seq.store(plan.next_seq, Ordering::Release);
+        self.signal.notify();
+        if synced {
+            self.mark_synced(plan.next_seq);
+        } else if let FsyncSchedule::Milliseconds(_) = self.fsync_schedule {
+            for path in touched {
+                let _ = self.publisher.send(path);
+            }
+        }
+        debug_print!(
+            "[writer] published: col={}, records={}, next_seq={}",
+            self.col,
+            plan.records.len(),
+            plan.next_seq
+        );
+    }
+
+    /// Invalidates anything of a failed plan that may have reached the file and hands
+    /// its new blocks back to the allocator.
+    fn discard(&self, plan: &WritePlan) {
+        for record in &plan.records {
+            let _ = record
+                .block
+                .zero_range(record.offset, PREFIX_META_SIZE as u64);
+        }
+        for block_id in &plan.allocated_block_ids {
+            self.allocator.tracker().set_block_unlocked(*block_id as usize);
+        }
+    }
+}
+
+/// Entries a group commit planned for one writer. The writer's locks stay held until the
+/// group is committed or aborted, so readers never see a record before it is written.
+pub(super) struct StagedWrites<'a> {
+    writer: &'a Writer,
+    block: MutexGuard<'a, Block>,
+    cur: MutexGuard<'a, u64>,
+    plan: WritePlan,
+}
+
+impl Writer {
+    /// Locks the writer for a group commit.
+    pub(super) fn stage(&self) -> std::io::Result<StagedWrites<'_>> {
+        let (block, cur) = self.lock_active()?;
+        if self.is_batch_writing.load(Ordering::Acquire) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::WouldBlock,
+                "batch write in progress for this topic",
+            ));
+        }
+        Ok(StagedWrites {
+            writer: self,
+            plan: WritePlan::new(&block, *cur, self.next_seq.load(Ordering::Acquire)),
+            block,
+            cur,
+        })
+    }
+}
+
+impl StagedWrites<'_> {
+    /// Plans one record and returns its sequence number. An entry the allocator refuses
+    /// fails on its own and leaves the rest of the group untouched.
+    pub(super) fn push(&mut self, data: &[u8], headers: &[u8]) -> std::io::Result<u64> {
+        self.plan.push(self.writer, data, headers, TxnMark::None)
+    }
+
+    /// Like [`StagedWrites::push`], tagging the record with its part in a transaction.
+    pub(super) fn push_in_txn(
+        &mut self,
+        data: &[u8],
+        headers: &[u8],
+        txn: TxnMark,
+    ) -> std::io::Result<u64> {
+        self.plan.push(self.writer, data, headers, txn)
+    }
+
+    pub(super) fn records(&self) -> &[StagedRecord] {
+        &self.plan.records
+    }
+
+    /// Sequence number the next pushed record would get.
+    pub(super) fn next_seq(&self) -> u64 {
+        self.plan.next_seq
+    }
+
+    /// Blocks this group filled up; like any sealed block they are flushed before
+    /// readers see them.
+    pub(super) fn sealed(&self) -> &[Block] {
+        &self.plan.sealed
+    }
+
+    /// Publishes the group's records once they are written and the sealed blocks are
+    /// flushed. `synced` says whether the group commit also fsynced the records.
+    pub(super) fn commit(self, synced: bool) {
+        let StagedWrites {
+            writer,
+            mut block,
+            mut cur,
+            plan,
+        } = self;
+        writer.publish(plan, &mut block, &mut cur, synced);
+    }
+
+    /// Drops the group's records after a failed write; the writer's block and offset
+    /// were never moved.
+    pub(super) fn abort(self) {
+        self.writer.discard(&self.plan);
+    }
+}
+
+/// Entries planned by an async append. Unlike a group commit it does not keep the
+/// writer's locks while the write is in flight; it sets the writer's async flag instead,
+/// so synchronous writes to the topic wait until it is committed or dropped.
+/// Dropping it without committing discards the records.
+#[cfg(feature = "async")]
+pub(super) struct AsyncAppend {
+    writer: Arc<Writer>,
+    plan: Option<WritePlan>,
+}
+
+#[cfg(feature = "async")]
+impl Writer {
+    pub(super) fn begin_async(self: &Arc<Self>) -> std::io::Result<AsyncAppend> {
+        {
+            let mut in_flight = self
+                .async_append
+                .lock()
+                .map_err(|_| std::io::Error::other("async append lock poisoned"))?;
+            // Async appends to a topic are queued by the caller; a batch write still fails
+            // them rather than blocking the calling task
+            if *in_flight || self.is_batch_writing.load(Ordering::Acquire) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::WouldBlock,
+                    "batch write in progress for this topic",
+                ));
+            }
+            *in_flight = true;
+        }
+        // Clears the flag if locking fails
+        let mut append = AsyncAppend {
+            writer: self.clone(),
+            plan: None,
+        };
+        let block = self
+            .current_block
+            .lock()
+            .map_err(|_| std::io::Error::other("current_block lock poisoned"))?;
+        let cur = self
+            .current_offset
+            .lock()
+            .map_err(|_| std::io::Error::other("current_offset lock poisoned"))?;
+        append.plan = Some(WritePlan::new(
+            &block,
+            *cur,
+            self.next_seq.load(Ordering::Acquire),
+        ));
+        Ok(append)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncAppend {
+    pub(super) fn push(&mut self, data: &[u8], headers: &[u8]) -> std::io::Result<u64> {
+        match self.plan.as_mut() {
+            Some(plan) => plan.push(&self.writer, data, headers, TxnMark::None),
+            None => Err(std::io::Error::other("async append already finished")),
+        }
+    }
+
+    pub(super) fn records(&self) -> &[StagedRecord] {
+        self.plan.as_ref().map_or(&[], |plan| &plan.records)
+    }
+
+    pub(super) fn sealed(&self) -> &[Block] {
+        self.plan.as_ref().map_or(&[], |plan| &plan.sealed)
+    }
+
+    /// Publishes the records once they are written and the sealed blocks are flushed.
+    pub(super) fn commit(mut self, synced: bool) -> std::io::Result<()> {
+        let writer = self.writer.clone();
+        let mut block = writer
+            .current_block
+            .lock()
+            .map_err(|_| std::io::Error::other("current_block lock poisoned"))?;
+        let mut cur = writer
+            .current_offset
+            .lock()
+            .map_err(|_| std::io::Error::other("current_offset lock poisoned"))?;
+        if let Some(plan) = self.plan.take() {
+            writer.publish(plan, &mut block, &mut cur, synced);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for AsyncAppend {
+    fn drop(&mut self) {
+        if let Some(plan) = self.plan.take() {
+            self.writer.discard(&plan);
+        }
+        if let Ok(mut in_flight) = self.writer.async_append.lock() {
+            *in_flight = false;
+        }
+        self.writer.async_done.notify_all();
+    }
 }
@@ -1,9 +1,9 @@
-use crate::wal::config::{FsyncSchedule, USE_FD_BACKEND};
+use crate::wal::config::{FsyncSchedule, StorageBackend};
 use memmap2::MmapMut;
 use std::collections::HashMap;
 use std::fs::OpenOptions;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{Arc, OnceLock, RwLock};
+use std::sync::{Arc, RwLock};
 use std::time::SystemTime;
 
 #[cfg(unix)]
@@ -114,19 +114,31 @@ impl StorageImpl {
     }
 }
 
-static GLOBAL_FSYNC_SCHEDULE: OnceLock<FsyncSchedule> = OnceLock::new();
+/// Per-instance settings for opening data files.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct StorageOptions {
+    pub(crate) backend: StorageBackend,
+    // FD files are opened with O_SYNC when every entry is fsynced on its own
+    pub(crate) use_o_sync: bool,
+}
 
-fn should_use_o_sync() -> bool {
-    GLOBAL_FSYNC_SCHEDULE
-        .get()
-        .map(|s| matches!(s, FsyncSchedule::SyncEach))
-        .unwrap_or(false)
+impl StorageOptions {
+    /// With group commit, `SyncEach` fsyncs once per group instead of using O_SYNC.
+    pub(crate) fn new(
+        backend: StorageBackend,
+        fsync_schedule: FsyncSchedule,
+        group_commit: bool,
+    ) -> Self {
+        Self {
+            backend,
+            use_o_sync: matches!(fsync_schedule, FsyncSchedule::SyncEach) && !group_commit,
+        }
+    }
 }
 
-fn create_storage_impl(path: &str) -> std::io::Result<StorageImpl> {
-    if USE_FD_BACKEND.load(Ordering::Relaxed) {
-        let use_o_sync = should_use_o_sync();
-        Ok(StorageImpl::Fd(FdBackend::new(path, use_o_sync)?))
+fn create_storage_impl(path: &str, options: StorageOptions) -> std::io::Result<StorageImpl> {
+    if options.backend == StorageBackend::Fd {
+        Ok(StorageImpl::Fd(FdBackend::new(path, options.use_o_sync)?))
     } else {
         let file = OpenOptions::new().read(true).write(true).open(path)?;
         // SAFETY: `file` is opened read/write and lives for the duration of this
@@ -151,8 +163,8 @@ unsafe impl Sync for SharedMmap {}
 unsafe impl Send for SharedMmap {}
 
 impl SharedMmap {
-    pub(crate) fn new(path: &str) -> std::io::Result<Arc<Self>> {
-        let storage = create_storage_impl(path)?;
+    pub(crate) fn new(path: &str, options: StorageOptions) -> std::io::Result<Arc<Self>> {
+        let storage = create_storage_impl(path, options)?;
 
         let now_ms = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
@@ -183,7 +195,6 @@ impl SharedMmap {
         self.storage.read(offset, dest);
     }
 
-    #[allow(dead_code)]
     pub(crate) fn len(&self) -> usize {
         self.storage.len()
     }
@@ -198,66 +209,59 @@ impl SharedMmap {
     }
 }
 
+/// The open mappings of an instance's files, shared by every block in them.
 pub(crate) struct SharedMmapKeeper {
-    data: HashMap<String, Arc<SharedMmap>>,
+    data: RwLock<HashMap<String, Arc<SharedMmap>>>,
 }
 
 impl SharedMmapKeeper {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
-            data: HashMap::new(),
+            data: RwLock::new(HashMap::new()),
         }
     }
 
     // Fast path: many readers concurrently
-    fn get_mmap_arc_read(path: &str) -> Option<Arc<SharedMmap>> {
-        static MMAP_KEEPER: OnceLock<RwLock<SharedMmapKeeper>> = OnceLock::new();
-        let keeper_lock = MMAP_KEEPER.get_or_init(|| RwLock::new(SharedMmapKeeper::new()));
-        let keeper = keeper_lock.read().ok()?;
-        keeper.data.get(path).cloned()
+    fn get_mmap_arc_read(&self, path: &str) -> Option<Arc<SharedMmap>> {
+        let data = self.data.read().ok()?;
+        data.get(path).cloned()
     }
 
-    // Read-mostly accessor that escalates to write lock only on miss
-    pub(crate) fn get_mmap_arc(path: &str) -> std::io::Result<Arc<SharedMmap>> {
-        if let Some(existing) = Self::get_mmap_arc_read(path) {
+    // Read-mostly accessor that escalates to write lock only on miss. `options` only
+    // applies when the file is not open yet.
+    pub(crate) fn get_mmap_arc(
+        &self,
+        path: &str,
+        options: StorageOptions,
+    ) -> std::io::Result<Arc<SharedMmap>> {
+        if let Some(existing) = self.get_mmap_arc_read(path) {
             return Ok(existing);
         }
 
-        static MMAP_KEEPER: OnceLock<RwLock<SharedMmapKeeper>> = OnceLock::new();
-        let keeper_lock = MMAP_KEEPER.get_or_init(|| RwLock::new(SharedMmapKeeper::new()));
-
-        // Double-check with a fresh read lock to avoid unnecessary write lock
-        {
-            let keeper = keeper_lock.read().map_err(|_| {
-                std::io::Error::new(std::io::ErrorKind::Other, "mmap keeper read lock poisoned")
-            })?;
-            if let Some(existing) = keeper.data.get(path) {
-                return Ok(existing.clone());
-            }
-        }
-
-        let mut keeper = keeper_lock.write().map_err(|_| {
+        let mut data = self.data.write().map_err(|_| {
             std::io::Error::new(std::io::ErrorKind::Other, "mmap keeper write lock poisoned")
         })?;
-        if let Some(existing) = keeper.data.get(path) {
+        if let Some(existing) = data.get(path) {
             return Ok(existing.clone());
         }
 
-        let arc = SharedMmap::new(path)?;
-        keeper.data.insert(path.to_string(), arc.clone());
+        let arc = SharedMmap::new(path, options)?;
+        data.insert(path.to_string(), arc.clone());
         Ok(arc)
     }
-}
 
-pub(crate) fn set_fsync_schedule(schedule: FsyncSchedule) {
-    let _ = GLOBAL_FSYNC_SCHEDULE.set(schedule);
-}
-
-#[allow(dead_code)]
-pub(crate) fn fsync_schedule() -> Option<FsyncSchedule> {
-    GLOBAL_FSYNC_SCHEDULE.get().copied()
+    /// Forgets the mapping of a file about to be deleted. It is released once the
+    /// blocks still holding it are dropped.
+    pub(crate) fn evict(&self, path: &str) {
+        if let Ok(mut data) = self.data.write() {
+            data.remove(path);
+        }
+    }
 }
 
-pub(crate) fn open_storage_for_path(path: &str) -> std::io::Result<StorageImpl> {
-    create_storage_impl(path)
+pub(crate) fn open_storage_for_path(
+    path: &str,
+    options: StorageOptions,
+) -> std::io::Result<StorageImpl> {
+    create_storage_impl(path, options)
 }
@@ -0,0 +1,251 @@
+#![cfg(feature = "async")]
+
+mod common;
+
+use common::TestEnv;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use walrus_rust::{
+    AsyncWalrus, CheckpointPolicy, FsyncSchedule, StorageBackend, SubscribeOptions, Walrus,
+};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+fn open_async(backend: StorageBackend, fsync: FsyncSchedule) -> AsyncWalrus {
+    let wal = Walrus::builder()
+        .backend(backend)
+        .fsync(fsync)
+        .block_size(64 * 1024)
+        .blocks_per_file(16)
+        .open()
+        .unwrap();
+    AsyncWalrus::new(wal)
+}
+
+async fn round_trip(wal: &AsyncWalrus) {
+    assert_eq!(wal.append("events", b"first").await.unwrap(), 0);
+    let seqs = wal
+        .batch_append("events", &[b"second", b"third"])
+        .await
+        .unwrap();
+    assert_eq!(seqs, 1..3);
+    assert_eq!(wal.append("events", b"fourth").await.unwrap(), 3);
+    assert_eq!(wal.wal().get_topic_entry_count("events"), 4);
+
+    let first = wal.read_next("events", true).await.unwrap().unwrap();
+    assert_eq!((first.seq, first.data), (0, b"first".to_vec()));
+    let rest = wal
+        .batch_read("events", 1024 * 1024, true, None)
+        .await
+        .unwrap();
+    let rest: Vec<(u64, Vec<u8>)> = rest.into_iter().map(|e| (e.seq, e.data)).collect();
+    assert_eq!(
+        rest,
+        vec![
+            (1, b"second".to_vec()),
+            (2, b"third".to_vec()),
+            (3, b"fourth".to_vec())
+        ]
+    );
+    assert!(wal.read_next("events", true).await.unwrap().is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn async_round_trip_fd_backend() {
+    let _env = setup_test_env();
+    let wal = open_async(StorageBackend::Fd, FsyncSchedule::SyncEach);
+    round_trip(&wal).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn async_round_trip_mmap_backend() {
+    let _env = setup_test_env();
+    let wal = open_async(StorageBackend::Mmap, FsyncSchedule::Milliseconds(10));
+    round_trip(&wal).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn async_concurrent_appends_survive_reopen() {
+    let _env = setup_test_env();
+    let mut written = Vec::new();
+    {
+        let wal = Arc::new(open_async(StorageBackend::Fd, FsyncSchedule::NoFsync));
+        // 10KB entries seal a 64KB block every 6 entries
+        let tasks: Vec<_> = (0..8u8)
+            .map(|t| {
+                let wal = wal.clone();
+                tokio::spawn(async move {
+                    let mut seqs = Vec::new();
+                    for i in 0..10u8 {
+                        let payload = vec![t * 16 + i; 10 * 1024];
+                        seqs.push((wal.append("bulk", &payload).await.unwrap(), payload));
+                    }
+                    seqs
+                })
+            })
+            .collect();
+        for task in tasks {
+            written.extend(task.await.unwrap());
+        }
+        wal.sync("bulk").await.unwrap();
+        // Nothing appended here, so there is nothing to sync
+        wal.sync("untouched").await.unwrap();
+    }
+    written.sort();
+    let seqs: Vec<u64> = written.iter().map(|(seq, _)| *seq).collect();
+    assert_eq!(seqs, (0..80).collect::<Vec<u64>>());
+
+    let wal = Walrus::builder()
+        .block_size(64 * 1024)
+        .blocks_per_file(16)
+        .open()
+        .unwrap();
+    for (seq, payload) in &written {
+        let entry = wal.read_next("bulk", true).unwrap().unwrap();
+        assert_eq!(entry.seq, *seq);
+        assert_eq!(&entry.data, payload);
+    }
+    assert!(wal.read_next("bulk", true).unwrap().is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn sync_appends_wait_for_async_appends_in_flight() {
+    let _env = setup_test_env();
+    let wal = Arc::new(open_async(StorageBackend::Fd, FsyncSchedule::SyncEach));
+    let sync_wal = wal.wal().clone();
+    let sync_writer = std::thread::spawn(move || {
+        for i in 0..100u8 {
+            sync_wal.append_for_topic("shared", &[1, i]).unwrap();
+        }
+    });
+    for i in 0..50u8 {
+        wal.append("shared", &[3, i]).await.unwrap();
+    }
+    sync_writer.join().unwrap();
+
+    assert_eq!(wal.wal().get_topic_entry_count("shared"), 150);
+    let entries = wal
+        .batch_read("shared", 1024 * 1024, true, None)
+        .await
+        .unwrap();
+    let seqs: Vec<u64> = entries.iter().map(|e| e.seq).collect();
+    assert_eq!(seqs, (0..150).collect::<Vec<u64>>());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn async_batch_limits_and_rejected_entries() {
+    let _env = setup_test_env();
+    let wal = AsyncWalrus::new(
+        Walrus::builder()
+            .block_size(16 * 1024)
+            .blocks_per_file(8)
+            .max_alloc(64 * 1024)
+            .open()
+            .unwrap(),
+    );
+
+    let too_many: Vec<&[u8]> = vec![b"x"; 2001];
+    let err = wal.batch_append("limits", &too_many).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(wal.batch_append("limits", &[]).await.unwrap(), 0..0);
+
+    wal.append("limits", b"before").await.unwrap();
+    let err = wal
+        .append("limits", &vec![0u8; 100 * 1024])
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    // The synchronous API keeps working once no async append is in flight
+    assert_eq!(wal.wal().append_for_topic("limits", b"after").unwrap(), 1);
+
+    let entries = wal
+        .batch_read("limits", 1024 * 1024, true, None)
+        .await
+        .unwrap();
+    let data: Vec<Vec<u8>> = entries.into_iter().map(|e| e.data).collect();
+    assert_eq!(data, vec![b"before".to_vec(), b"after".to_vec()]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn async_waiting_reads_wake_on_append() {
+    let _env = setup_test_env();
+    let wal = Arc::new(open_async(StorageBackend::Fd, FsyncSchedule::NoFsync));
+
+    let started = Instant::now();
+    let nothing = wal
+        .read_next_wait("waited", true, Duration::from_millis(100))
+        .await
+        .unwrap();
+    assert!(nothing.is_none());
+    assert!(started.elapsed() >= Duration::from_millis(100));
+
+    // A synchronous writer on another thread wakes the waiting task
+    let sync_wal = wal.wal().clone();
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        sync_wal.append_for_topic("waited", b"one").unwrap();
+    });
+    let entry = wal
+        .read_next_wait("waited", true, Duration::from_secs(30))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(entry.data, b"one");
+    writer.join().unwrap();
+
+    let appender = {
+        let wal = wal.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            wal.batch_append("waited", &[b"two", b"three"])
+                .await
+                .unwrap();
+        })
+    };
+    let entries = wal
+        .batch_read_wait("waited", 1024 * 1024, true, Duration::from_secs(30))
+        .await
+        .unwrap();
+    let data: Vec<Vec<u8>> = entries.into_iter().map(|e| e.data).collect();
+    assert_eq!(data, vec![b"two".to_vec(), b"three".to_vec()]);
+    appender.await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn async_subscription_streams_live_entries_and_acks() {
+    let _env = setup_test_env();
+    {
+        let wal = Arc::new(open_async(StorageBackend::Fd, FsyncSchedule::NoFsync));
+        wal.batch_append("stream", &[b"a", b"b"]).await.unwrap();
+        let appender = {
+            let wal = wal.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                wal.append("stream", b"c").await.unwrap();
+                wal.append("stream", b"d").await.unwrap();
+            })
+        };
+
+        let options = SubscribeOptions::new()
+            .checkpoint(CheckpointPolicy::Manual)
+            .idle_timeout(Duration::from_secs(30));
+        let mut sub = wal.subscribe("stream", options).await.unwrap();
+        let mut data = Vec::new();
+        for _ in 0..3 {
+            data.push(sub.next().await.unwrap().unwrap().data);
+        }
+        assert_eq!(data, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+        sub.ack().await.unwrap();
+        assert_eq!(sub.next().await.unwrap().unwrap().data, b"d");
+        appender.await.unwrap();
+    }
+
+    // "d" was never acknowledged, so it is delivered again
+    let wal = open_async(StorageBackend::Fd, FsyncSchedule::NoFsync);
+    let options = SubscribeOptions::new().idle_timeout(Duration::from_millis(100));
+    let mut sub = wal.subscribe("stream", options).await.unwrap();
+    assert_eq!(sub.next().await.unwrap().unwrap().data, b"d");
+    assert!(sub.next().await.is_none());
+}
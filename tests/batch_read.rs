@@ -5,6 +5,7 @@ use std::sync::{Arc, Barrier};
 use std::thread;
 use std::time::Duration;
 use walrus_rust::{FsyncSchedule, ReadConsistency, Walrus, enable_fd_backend};
+use walrus_rust::wal::FILE_HEADER_SIZE;
 
 fn setup_test_env() -> TestEnv {
     TestEnv::new()
@@ -128,6 +129,38 @@ fn test_batch_read_crosses_sealed_to_tail() {
     cleanup_test_env();
 }
 
+#[test]
+fn test_batch_read_budget_ending_in_sealed_block_keeps_its_remaining_entries() {
+    let _guard = setup_test_env();
+
+    // Four 3KB entries per 16KB block; the last block is the writer's active one
+    let wal = Walrus::builder()
+        .fsync(FsyncSchedule::NoFsync)
+        .block_size(16 * 1024)
+        .blocks_per_file(8)
+        .open()
+        .unwrap();
+    for i in 0..12u8 {
+        wal.append_for_topic("budget_cut", &vec![i; 3 * 1024])
+            .unwrap();
+    }
+
+    let mut seen = Vec::new();
+    loop {
+        // The budget runs out partway through the second sealed block on the second read
+        let chunk = wal
+            .batch_read_for_topic("budget_cut", 12 * 1024, true, None)
+            .unwrap();
+        if chunk.is_empty() {
+            break;
+        }
+        seen.extend(chunk.iter().map(|e| e.data[0]));
+    }
+    assert_eq!(seen, (0..12).collect::<Vec<u8>>());
+
+    cleanup_test_env();
+}
+
 #[test]
 fn test_batch_read_tail_only() {
     let _guard = setup_test_env();
@@ -717,7 +750,8 @@ fn test_batch_read_with_zeroed_headers() {
             wal.append_for_topic("zeroed", data.as_bytes()).unwrap();
         }
 
-        drop(wal);
+        // A crash: a clean shutdown would record the block in the manifest
+        std::mem::forget(wal);
 
         thread::sleep(Duration::from_millis(50));
     }
@@ -738,7 +772,7 @@ fn test_batch_read_with_zeroed_headers() {
                 .open(&file_path)
                 .unwrap();
 
-            let approx_offset = 10 * (256 + 12);
+            let approx_offset = FILE_HEADER_SIZE as usize + 10 * (256 + 12);
             let zeros = vec![0u8; 64 * 6];
             file.write_at(&zeros, approx_offset as u64).unwrap();
             file.sync_all().unwrap();
@@ -0,0 +1,130 @@
+mod common;
+
+use common::TestEnv;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use walrus_rust::{FsyncSchedule, Walrus};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+fn open_wal() -> Arc<Walrus> {
+    Arc::new(
+        Walrus::builder()
+            .fsync(FsyncSchedule::NoFsync)
+            .open()
+            .unwrap(),
+    )
+}
+
+#[test]
+fn read_next_blocking_wakes_on_append() {
+    let _env = setup_test_env();
+    let wal = open_wal();
+
+    let writer = {
+        let wal = wal.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            wal.append_for_topic("live", b"hello").unwrap();
+        })
+    };
+
+    let started = Instant::now();
+    let entry = wal
+        .read_next_blocking("live", true, Duration::from_secs(30))
+        .unwrap()
+        .unwrap();
+    assert_eq!(entry.data, b"hello");
+    assert!(started.elapsed() < Duration::from_secs(10));
+    writer.join().unwrap();
+}
+
+#[test]
+fn read_next_blocking_times_out_and_returns_ready_entries_immediately() {
+    let _env = setup_test_env();
+    let wal = open_wal();
+
+    let started = Instant::now();
+    let entry = wal
+        .read_next_blocking("quiet", true, Duration::from_millis(150))
+        .unwrap();
+    assert!(entry.is_none());
+    assert!(started.elapsed() >= Duration::from_millis(150));
+
+    // Appends to other topics do not satisfy the wait
+    let other = {
+        let wal = wal.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            wal.append_for_topic("noisy", b"x").unwrap();
+        })
+    };
+    assert!(
+        wal.read_next_blocking("quiet", true, Duration::from_millis(150))
+            .unwrap()
+            .is_none()
+    );
+    other.join().unwrap();
+
+    wal.append_for_topic("quiet", b"ready").unwrap();
+    let started = Instant::now();
+    let entry = wal
+        .read_next_blocking("quiet", true, Duration::from_secs(30))
+        .unwrap()
+        .unwrap();
+    assert_eq!(entry.data, b"ready");
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn batch_read_blocking_wakes_on_batch_and_block_seal() {
+    let _env = setup_test_env();
+    let wal = Arc::new(
+        Walrus::builder()
+            .fsync(FsyncSchedule::NoFsync)
+            .block_size(64 * 1024)
+            .blocks_per_file(16)
+            .open()
+            .unwrap(),
+    );
+
+    let writer = {
+        let wal = wal.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            wal.batch_append_for_topic("batched", &[b"a", b"b", b"c"])
+                .unwrap();
+            thread::sleep(Duration::from_millis(100));
+            // 40KB entries seal the 64KB block on every other append
+            for _ in 0..3 {
+                wal.append_for_topic("batched", &[7u8; 40 * 1024]).unwrap();
+            }
+        })
+    };
+
+    let entries = wal
+        .batch_read_for_topic_blocking("batched", 1024 * 1024, true, Duration::from_secs(30))
+        .unwrap();
+    let data: Vec<Vec<u8>> = entries.into_iter().map(|e| e.data).collect();
+    assert_eq!(data, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+
+    let mut large = 0;
+    while large < 3 {
+        let entries = wal
+            .batch_read_for_topic_blocking("batched", 1024 * 1024, true, Duration::from_secs(30))
+            .unwrap();
+        assert!(!entries.is_empty());
+        large += entries.len();
+    }
+    assert_eq!(large, 3);
+    writer.join().unwrap();
+
+    assert!(
+        wal.batch_read_for_topic_blocking("batched", 1024, true, Duration::from_millis(50))
+            .unwrap()
+            .is_empty()
+    );
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use common::{TestEnv, payload};
+use walrus_rust::{FsyncSchedule, ReadConsistency, SeekTarget, Walrus};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+fn open_wal(consistency: ReadConsistency) -> Walrus {
+    Walrus::builder()
+        .consistency(consistency)
+        .fsync(FsyncSchedule::NoFsync)
+        .block_size(16 * 1024)
+        .blocks_per_file(8)
+        .open()
+        .unwrap()
+}
+
+fn seqs(entries: &[walrus_rust::Entry]) -> Vec<u64> {
+    entries.iter().map(|e| e.seq).collect()
+}
+
+#[test]
+fn uncommitted_leases_replay_after_restart() {
+    let _env = setup_test_env();
+    {
+        let wal = open_wal(ReadConsistency::StrictlyAtOnce);
+        for i in 0..12 {
+            wal.append_for_topic("tasks", &payload(i)).unwrap();
+        }
+        // Leases advance through the topic without consuming it
+        let first = wal.read_leased("tasks", 7 * 1024).unwrap().unwrap();
+        assert_eq!(seqs(&first.entries), vec![0, 1]);
+        assert_eq!(first.token.next_seq(), 2);
+        let second = wal.read_leased("tasks", 12 * 1024).unwrap().unwrap();
+        assert_eq!(seqs(&second.entries), vec![2, 3, 4]);
+        let third = wal.read_leased("tasks", 1024 * 1024).unwrap().unwrap();
+        assert_eq!(seqs(&third.entries), (5..12).collect::<Vec<u64>>());
+        assert!(wal.read_leased("tasks", 1024 * 1024).unwrap().is_none());
+
+        // Only the first two batches finished processing
+        wal.commit("tasks", first.token).unwrap();
+        wal.commit("tasks", second.token).unwrap();
+    }
+
+    let wal = open_wal(ReadConsistency::StrictlyAtOnce);
+    let replay = wal.read_leased("tasks", 1024 * 1024).unwrap().unwrap();
+    assert_eq!(seqs(&replay.entries), (5..12).collect::<Vec<u64>>());
+    wal.commit("tasks", replay.token).unwrap();
+    drop(wal);
+
+    let wal = open_wal(ReadConsistency::StrictlyAtOnce);
+    assert!(wal.read_next("tasks", true).unwrap().is_none());
+}
+
+#[test]
+fn commit_persists_regardless_of_persist_every() {
+    let _env = setup_test_env();
+    {
+        let wal = open_wal(ReadConsistency::AtLeastOnce {
+            persist_every: 1000,
+        });
+        for i in 0..4 {
+            wal.append_for_topic("metrics", &payload(i)).unwrap();
+        }
+        let lease = wal.read_leased("metrics", 4 * 1024).unwrap().unwrap();
+        assert_eq!(seqs(&lease.entries), vec![0]);
+        wal.commit("metrics", lease.token).unwrap();
+        // A checkpointing batch read only moves the cursor in memory under this mode
+        let rest = wal
+            .batch_read_for_topic("metrics", 1024 * 1024, true, None)
+            .unwrap();
+        assert_eq!(seqs(&rest), vec![1, 2, 3]);
+    }
+
+    let wal = open_wal(ReadConsistency::AtLeastOnce {
+        persist_every: 1000,
+    });
+    assert_eq!(wal.read_next("metrics", true).unwrap().unwrap().seq, 1);
+}
+
+#[test]
+fn stale_foreign_and_group_tokens() {
+    let _env = setup_test_env();
+    {
+        let wal = open_wal(ReadConsistency::StrictlyAtOnce);
+        for i in 0..6 {
+            wal.append_for_topic("events", &payload(i)).unwrap();
+        }
+        wal.append_for_topic("other", b"x").unwrap();
+
+        let early = wal.read_leased("events", 4 * 1024).unwrap().unwrap();
+        let late = wal.read_leased("events", 4 * 1024).unwrap().unwrap();
+        assert_eq!((early.token.next_seq(), late.token.next_seq()), (1, 2));
+
+        let err = wal.commit("other", late.token.clone()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+        // Committing out of order keeps the newer position
+        wal.commit("events", late.token).unwrap();
+        wal.commit("events", early.token).unwrap();
+
+        // Group leases carry their cursor and leave the default one alone
+        let audit = wal
+            .read_leased_as("audit", "events", 1024 * 1024)
+            .unwrap()
+            .unwrap();
+        assert_eq!(audit.token.group(), Some("audit"));
+        assert_eq!(seqs(&audit.entries), (0..6).collect::<Vec<u64>>());
+        wal.commit("events", audit.token).unwrap();
+    }
+
+    let wal = open_wal(ReadConsistency::StrictlyAtOnce);
+    assert_eq!(wal.read_next("events", true).unwrap().unwrap().seq, 2);
+    assert!(wal.read_next_as("audit", "events", true).unwrap().is_none());
+
+    // A seek resets the committed position, so earlier entries can be committed again
+    wal.seek_topic("events", SeekTarget::Beginning).unwrap();
+    let lease = wal.read_leased("events", 4 * 1024).unwrap().unwrap();
+    assert_eq!(seqs(&lease.entries), vec![0]);
+    wal.commit("events", lease.token).unwrap();
+    drop(wal);
+
+    let wal = open_wal(ReadConsistency::StrictlyAtOnce);
+    assert_eq!(wal.read_next("events", true).unwrap().unwrap().seq, 1);
+}
@@ -1,6 +1,6 @@
 use std::cell::RefCell;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -91,7 +91,7 @@ pub fn sanitize_key(key: &str) -> String {
 }
 
 #[allow(dead_code)]
-fn checksum64(data: &[u8]) -> u64 {
+pub fn checksum64(data: &[u8]) -> u64 {
     const FNV_OFFSET: u64 = 0xcbf29ce484222325;
     const FNV_PRIME: u64 = 0x00000100000001B3;
     let mut hash = FNV_OFFSET;
@@ -168,3 +168,26 @@ pub fn current_wal_dir() -> PathBuf {
 pub fn wal_root_dir() -> PathBuf {
     ensure_base_dir()
 }
+
+// Payload plus the 256 byte metadata prefix
+#[allow(dead_code)]
+pub const ENTRY_BYTES: u64 = 3 * 1024 + 256;
+
+#[allow(dead_code)]
+pub fn payload(i: u64) -> Vec<u8> {
+    // 3KB entries fill a 16KB block every 4 entries
+    vec![i as u8; 3 * 1024]
+}
+
+// Names of the data files in `dir`, oldest first
+#[allow(dead_code)]
+pub fn data_files(dir: &Path) -> Vec<String> {
+    let mut files: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.chars().all(|c| c.is_ascii_digit()))
+        .collect();
+    files.sort();
+    files
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use common::{TestEnv, current_wal_dir};
+use std::fs;
+use walrus_rust::{FsyncSchedule, ReadConsistency, Walrus, WalrusBuilder};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+const BLOCK_SIZE: u64 = 16 * 1024;
+
+fn builder() -> WalrusBuilder {
+    Walrus::builder()
+        .consistency(ReadConsistency::StrictlyAtOnce)
+        .fsync(FsyncSchedule::NoFsync)
+        .block_size(BLOCK_SIZE)
+        .blocks_per_file(4)
+}
+
+fn payload(topic: &str, i: u64) -> Vec<u8> {
+    let mut data = common::payload(i);
+    data[0] = topic.as_bytes()[0];
+    data
+}
+
+// Data files with their sizes, oldest first
+fn data_files() -> Vec<(String, u64)> {
+    let dir = current_wal_dir();
+    common::data_files(&dir)
+        .into_iter()
+        .map(|name| {
+            let size = fs::metadata(dir.join(&name)).unwrap().len();
+            (name, size)
+        })
+        .collect()
+}
+
+// Two topics share every file block for block; "fast" is read to the end
+fn write_interleaved() {
+    let wal = builder().open().unwrap();
+    for i in 0..17 {
+        wal.append_for_topic("fast", &payload("fast", i)).unwrap();
+        wal.append_for_topic("slow", &payload("slow", i)).unwrap();
+    }
+    for i in 0..17 {
+        let entry = wal.read_next("fast", true).unwrap().unwrap();
+        assert_eq!(entry.seq, i);
+    }
+}
+
+#[test]
+fn compaction_moves_live_blocks_out_of_sparse_files() {
+    let _env = setup_test_env();
+    write_interleaved();
+    let before = data_files();
+
+    let wal = builder().compact_on_open(0.5).open().unwrap();
+    // "slow" moved as a whole, so every old file is left with consumed blocks only
+    wal.reclaim_now().unwrap();
+    let after = data_files();
+    for (name, _) in &before {
+        assert!(
+            after.iter().all(|(n, _)| n != name),
+            "{} was not reclaimed",
+            name
+        );
+    }
+
+    assert!(wal.read_next("fast", true).unwrap().is_none());
+    for i in 0..5 {
+        let entry = wal.read_next("slow", true).unwrap().unwrap();
+        assert_eq!((entry.seq, entry.data), (i, payload("slow", i)));
+    }
+    assert_eq!(wal.get_topic_entry_count("slow"), 12);
+    assert_eq!(
+        wal.append_for_topic("fast", &payload("fast", 17)).unwrap(),
+        17
+    );
+
+    // Positions and sequence numbers carry over to the next restart
+    drop(wal);
+    let wal = builder().open().unwrap();
+    assert_eq!(wal.read_next("fast", true).unwrap().unwrap().seq, 17);
+    for i in 5..17 {
+        let entry = wal.read_next("slow", true).unwrap().unwrap();
+        assert_eq!((entry.seq, entry.data), (i, payload("slow", i)));
+    }
+    assert!(wal.read_next("slow", true).unwrap().is_none());
+    assert_eq!(
+        wal.append_for_topic("slow", &payload("slow", 17)).unwrap(),
+        17
+    );
+}
+
+#[test]
+fn moved_blocks_are_not_recovered_twice() {
+    let _env = setup_test_env();
+    write_interleaved();
+    // Links keep the old files around after the reclaimer deletes them, as a crash
+    // right after compaction would
+    let dir = current_wal_dir();
+    let kept = dir.join("kept");
+    fs::create_dir(&kept).unwrap();
+    let before = data_files();
+    for (name, _) in &before {
+        fs::hard_link(dir.join(name), kept.join(name)).unwrap();
+    }
+
+    let wal = builder().compact_on_open(0.5).open().unwrap();
+    wal.reclaim_now().unwrap();
+    drop(wal);
+    for (name, _) in &before {
+        fs::rename(kept.join(name), dir.join(name)).unwrap();
+    }
+
+    let wal = builder().open().unwrap();
+    assert!(wal.read_next("fast", true).unwrap().is_none());
+    for i in 0..17 {
+        let entry = wal.read_next("slow", true).unwrap().unwrap();
+        assert_eq!((entry.seq, entry.data), (i, payload("slow", i)));
+    }
+    assert!(wal.read_next("slow", true).unwrap().is_none());
+}
+
+#[test]
+fn files_above_the_live_ratio_are_left_alone() {
+    let _env = setup_test_env();
+    write_interleaved();
+    let before = data_files();
+
+    let wal = builder().compact_on_open(0.25).open().unwrap();
+    let after = data_files();
+    for file in &before {
+        assert!(after.contains(file));
+    }
+    assert_eq!(wal.read_next("slow", true).unwrap().unwrap().seq, 0);
+}
+
+#[test]
+fn lagging_consumer_groups_keep_blocks_live() {
+    let _env = setup_test_env();
+    {
+        let wal = builder().open().unwrap();
+        for i in 0..17 {
+            wal.append_for_topic("fast", &payload("fast", i)).unwrap();
+            wal.append_for_topic("slow", &payload("slow", i)).unwrap();
+        }
+        wal.read_next_as("audit", "fast", true).unwrap().unwrap();
+        for _ in 0..17 {
+            wal.read_next("fast", true).unwrap().unwrap();
+        }
+    }
+    let before = data_files();
+
+    let wal = builder().compact_on_open(0.5).open().unwrap();
+    for file in &before {
+        assert!(data_files().contains(file));
+    }
+    let entry = wal.read_next_as("audit", "fast", true).unwrap().unwrap();
+    assert_eq!((entry.seq, entry.data), (1, payload("fast", 1)));
+}
+
+#[test]
+fn out_of_range_ratio_is_rejected() {
+    let _env = setup_test_env();
+    let err = builder().compact_on_open(1.5).open().err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
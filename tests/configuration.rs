@@ -6,7 +6,7 @@ use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 use std::time::Instant;
-use walrus_rust::wal::{FsyncSchedule, ReadConsistency, Walrus};
+use walrus_rust::wal::{FsyncSchedule, ReadConsistency, StorageBackend, Walrus};
 
 fn setup_env() -> TestEnv {
     let env = TestEnv::new();
@@ -616,3 +616,186 @@ fn key_names_are_sanitized_for_directories() {
         expected_dir
     );
 }
+
+fn data_file_count() -> usize {
+    fs::read_dir(current_wal_dir())
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().parse::<u64>().is_ok())
+        .count()
+}
+
+#[test]
+fn builder_small_blocks_span_several_files() {
+    let _env = setup_env();
+    // 16KB blocks, 8 blocks per file => 128KB files
+    let wal = Walrus::builder()
+        .block_size(16 * 1024)
+        .blocks_per_file(8)
+        .open()
+        .unwrap();
+
+    let payload = vec![7u8; 1024];
+    for _ in 0..300 {
+        wal.append_for_topic("small", &payload).unwrap();
+    }
+    assert!(data_file_count() >= 3);
+    for seq in 0..300 {
+        let entry = wal.read_next("small", true).unwrap().unwrap();
+        assert_eq!(entry.seq, seq);
+        assert_eq!(entry.data, payload);
+    }
+    assert!(wal.read_next("small", true).unwrap().is_none());
+}
+
+#[test]
+fn builder_geometry_is_recovered_from_file_headers() {
+    let _env = setup_env();
+    {
+        let wal = Walrus::builder()
+            .block_size(16 * 1024)
+            .blocks_per_file(8)
+            .open()
+            .unwrap();
+        for i in 0..200u32 {
+            wal.append_for_topic("geo", &i.to_le_bytes()).unwrap();
+            wal.append_for_topic("geo", &vec![0u8; 512]).unwrap();
+        }
+    }
+
+    thread::sleep(Duration::from_millis(50));
+
+    // Reopening with the default geometry still reads the small-block files
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+    for i in 0..200u32 {
+        let entry = wal.read_next("geo", true).unwrap().unwrap();
+        assert_eq!(entry.data, i.to_le_bytes());
+        assert_eq!(wal.read_next("geo", true).unwrap().unwrap().data.len(), 512);
+    }
+    assert!(wal.read_next("geo", true).unwrap().is_none());
+
+    wal.append_for_topic("geo", b"after").unwrap();
+    let entry = wal.read_next("geo", true).unwrap().unwrap();
+    assert_eq!(entry.data, b"after");
+    assert_eq!(entry.seq, 400);
+}
+
+#[test]
+fn builder_max_alloc_bounds_single_entries() {
+    let _env = setup_env();
+    let wal = Walrus::builder()
+        .block_size(16 * 1024)
+        .blocks_per_file(64)
+        .max_alloc(512 * 1024)
+        .open()
+        .unwrap();
+
+    let big = vec![9u8; 300 * 1024];
+    wal.append_for_topic("blobs", &big).unwrap();
+    let err = wal
+        .append_for_topic("blobs", &vec![9u8; 600 * 1024])
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    assert_eq!(wal.read_next("blobs", true).unwrap().unwrap().data, big);
+    assert!(wal.read_next("blobs", true).unwrap().is_none());
+}
+
+#[test]
+fn builder_rejects_invalid_geometry() {
+    let _env = setup_env();
+    let attempts = [
+        Walrus::builder().block_size(0).open(),
+        Walrus::builder().block_size(1000).open(),
+        Walrus::builder().blocks_per_file(0).open(),
+        Walrus::builder().block_size(64 * 1024).max_alloc(4096).open(),
+        Walrus::builder()
+            .block_size(64 * 1024)
+            .blocks_per_file(4)
+            .max_alloc(1024 * 1024)
+            .open(),
+    ];
+    for attempt in attempts {
+        assert_eq!(
+            attempt.err().unwrap().kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+    }
+}
+
+#[test]
+fn builder_data_dir_and_namespace_are_per_instance() {
+    let env = setup_env();
+    let dir = wal_root_dir().join(env.unique_key("explicit-dir"));
+    let key = "builder-ns";
+
+    {
+        let wal = Walrus::builder()
+            .data_dir(&dir)
+            .namespace(key)
+            .open()
+            .unwrap();
+        wal.append_for_topic("topic", b"in explicit dir").unwrap();
+    }
+    assert!(dir.join(sanitize_key(key)).is_dir());
+
+    thread::sleep(Duration::from_millis(50));
+
+    // The same settings find the data again; the default directory does not have it
+    let wal = Walrus::builder()
+        .data_dir(&dir)
+        .namespace(key)
+        .open()
+        .unwrap();
+    assert_eq!(
+        wal.read_next("topic", true).unwrap().unwrap().data,
+        b"in explicit dir"
+    );
+    let default_wal = Walrus::new().unwrap();
+    assert!(default_wal.read_next("topic", true).unwrap().is_none());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn instances_with_different_backends_and_fsync_coexist() {
+    let env = setup_env();
+    let fd_key = env.unique_key("fd");
+    let mmap_key = env.unique_key("mmap");
+    let open = |key: &str, backend, fsync| {
+        Walrus::builder()
+            .namespace(key)
+            .backend(backend)
+            .fsync(fsync)
+            .open()
+            .unwrap()
+    };
+
+    {
+        let fd = open(&fd_key, StorageBackend::Fd, FsyncSchedule::SyncEach);
+        let mmap = open(&mmap_key, StorageBackend::Mmap, FsyncSchedule::NoFsync);
+        fd.batch_append_for_topic("t", &[b"f1", b"f2"]).unwrap();
+        mmap.batch_append_for_topic("t", &[b"m1", b"m2"]).unwrap();
+        fd.append_for_topic("t", b"f3").unwrap();
+        mmap.append_for_topic("t", b"m3").unwrap();
+
+        let fd_batch = fd.batch_read_for_topic("t", 1024, false, None).unwrap();
+        let mmap_batch = mmap.batch_read_for_topic("t", 1024, false, None).unwrap();
+        assert_eq!(fd_batch.len(), 3);
+        assert_eq!(mmap_batch.len(), 3);
+        assert_eq!(fd_batch[0].data, b"f1");
+        assert_eq!(mmap_batch[2].data, b"m3");
+    }
+
+    thread::sleep(Duration::from_millis(50));
+
+    // Files are readable by either backend after a restart
+    let fd = open(&mmap_key, StorageBackend::Fd, FsyncSchedule::Milliseconds(50));
+    let mmap = open(&fd_key, StorageBackend::Mmap, FsyncSchedule::Milliseconds(50));
+    for expected in [b"m1", b"m2", b"m3"] {
+        assert_eq!(fd.read_next("t", true).unwrap().unwrap().data, expected);
+    }
+    for expected in [b"f1", b"f2", b"f3"] {
+        assert_eq!(mmap.read_next("t", true).unwrap().unwrap().data, expected);
+    }
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use common::TestEnv;
+use std::thread;
+use std::time::Duration;
+use walrus_rust::{ReadConsistency, SeekTarget, Walrus};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+fn drain_as(wal: &Walrus, group: &str, topic: &str) -> Vec<u64> {
+    let mut seqs = Vec::new();
+    while let Some(entry) = wal.read_next_as(group, topic, true).unwrap() {
+        seqs.push(entry.seq);
+    }
+    seqs
+}
+
+#[test]
+fn groups_read_independently() {
+    let _env = setup_test_env();
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+
+    for i in 0..5u8 {
+        wal.append_for_topic("orders", &[i]).unwrap();
+    }
+
+    // The default cursor consuming everything does not affect named groups
+    for i in 0..5u8 {
+        assert_eq!(wal.read_next("orders", true).unwrap().unwrap().data, vec![i]);
+    }
+    assert!(wal.read_next("orders", true).unwrap().is_none());
+
+    assert_eq!(drain_as(&wal, "billing", "orders"), vec![0, 1, 2, 3, 4]);
+    for i in 0..2u8 {
+        let entry = wal.read_next_as("audit", "orders", true).unwrap().unwrap();
+        assert_eq!(entry.data, vec![i]);
+    }
+
+    wal.append_for_topic("orders", &[5]).unwrap();
+    assert_eq!(wal.read_next("orders", true).unwrap().unwrap().seq, 5);
+    assert_eq!(drain_as(&wal, "billing", "orders"), vec![5]);
+    assert_eq!(drain_as(&wal, "audit", "orders"), vec![2, 3, 4, 5]);
+}
+
+#[test]
+fn group_batch_reads_and_seeks_are_independent() {
+    let _env = setup_test_env();
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+
+    for i in 0..6u8 {
+        wal.append_for_topic("events", &[i]).unwrap();
+    }
+
+    let batch = wal
+        .batch_read_for_topic_as("a", "events", 1024 * 1024, true, None)
+        .unwrap();
+    assert_eq!(batch.len(), 6);
+    assert!(wal
+        .batch_read_for_topic_as("a", "events", 1024 * 1024, true, None)
+        .unwrap()
+        .is_empty());
+
+    let batch = wal
+        .batch_read_for_topic_as("b", "events", 1024 * 1024, true, None)
+        .unwrap();
+    assert_eq!(batch.len(), 6);
+
+    assert_eq!(
+        wal.seek_topic_as("a", "events", SeekTarget::Sequence(4))
+            .unwrap(),
+        4
+    );
+    assert_eq!(drain_as(&wal, "a", "events"), vec![4, 5]);
+    assert!(wal.read_next_as("b", "events", true).unwrap().is_none());
+    assert_eq!(wal.read_next("events", true).unwrap().unwrap().seq, 0);
+}
+
+#[test]
+fn group_cursors_survive_restart() {
+    let _env = setup_test_env();
+    {
+        let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+        for i in 0..6u8 {
+            wal.append_for_topic("durable", &[i]).unwrap();
+        }
+        for _ in 0..3 {
+            wal.read_next_as("fast", "durable", true).unwrap().unwrap();
+        }
+        wal.read_next("durable", true).unwrap().unwrap();
+    }
+
+    thread::sleep(Duration::from_millis(50));
+
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+    assert_eq!(wal.get_topic_entry_count("durable"), 5);
+    assert_eq!(wal.get_topic_entry_count_as("fast", "durable"), 3);
+    assert_eq!(wal.get_topic_entry_count_as("fresh", "durable"), 6);
+
+    assert_eq!(drain_as(&wal, "fast", "durable"), vec![3, 4, 5]);
+    assert_eq!(wal.read_next("durable", true).unwrap().unwrap().seq, 1);
+    assert_eq!(drain_as(&wal, "fresh", "durable"), vec![0, 1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn slow_group_keeps_sealed_blocks_readable() {
+    let _env = setup_test_env();
+    {
+        let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+        // ~1MB entries spill over several 10MB blocks
+        let payload = vec![3u8; 1024 * 1024];
+        for _ in 0..25 {
+            wal.append_for_topic("bulk", &payload).unwrap();
+        }
+        wal.read_next_as("slow", "bulk", true).unwrap().unwrap();
+        for _ in 0..20 {
+            wal.read_next("bulk", true).unwrap().unwrap();
+        }
+    }
+
+    thread::sleep(Duration::from_millis(50));
+
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+    assert_eq!(wal.get_topic_entry_count("bulk"), 5);
+    assert_eq!(wal.get_topic_entry_count_as("slow", "bulk"), 24);
+    assert_eq!(drain_as(&wal, "slow", "bulk"), (1..25).collect::<Vec<u64>>());
+    assert_eq!(wal.read_next("bulk", true).unwrap().unwrap().seq, 20);
+}
+
+#[test]
+fn group_entry_counts_track_each_group() {
+    let _env = setup_test_env();
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+
+    wal.append_for_topic("counted", b"a").unwrap();
+    wal.append_for_topic("counted", b"b").unwrap();
+    wal.read_next_as("g1", "counted", true).unwrap().unwrap();
+    assert_eq!(wal.get_topic_entry_count_as("g1", "counted"), 1);
+    assert_eq!(wal.get_topic_entry_count_as("g2", "counted"), 2);
+    assert_eq!(wal.get_topic_entry_count("counted"), 2);
+
+    wal.batch_append_for_topic("counted", &[b"c", b"d"]).unwrap();
+    assert_eq!(wal.get_topic_entry_count_as("g1", "counted"), 3);
+    assert_eq!(wal.get_topic_entry_count("counted"), 4);
+
+    // Unchecked reads leave the count alone
+    wal.read_next_as("g1", "counted", false).unwrap().unwrap();
+    assert_eq!(wal.get_topic_entry_count_as("g1", "counted"), 3);
+}
+
+#[test]
+fn invalid_group_names_are_rejected() {
+    let _env = setup_test_env();
+    let wal = Walrus::new().unwrap();
+
+    let err = wal.read_next_as("", "topic", true).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let err = wal
+        .batch_read_for_topic_as("bad\0group", "topic", 1024, true, None)
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use common::TestEnv;
+use std::collections::HashMap;
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::Duration;
+use walrus_rust::{FsyncSchedule, ReadConsistency, StorageBackend, Walrus};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+fn open_group_commit(backend: StorageBackend, fsync: FsyncSchedule) -> Walrus {
+    Walrus::builder()
+        .backend(backend)
+        .fsync(fsync)
+        .group_commit(true)
+        .open()
+        .unwrap()
+}
+
+// 8 threads x 4 topics x 50 entries, all released at once so appends overlap
+fn concurrent_appends(wal: &Arc<Walrus>) -> HashMap<String, Vec<(u64, Vec<u8>)>> {
+    let barrier = Arc::new(Barrier::new(8));
+    let handles: Vec<_> = (0..8u8)
+        .map(|t| {
+            let wal = wal.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                let mut written = Vec::new();
+                for i in 0..50u8 {
+                    let topic = format!("topic-{}", (t + i) % 4);
+                    let payload = vec![t, i];
+                    let seq = wal.append_for_topic(&topic, &payload).unwrap();
+                    written.push((topic, seq, payload));
+                }
+                written
+            })
+        })
+        .collect();
+
+    let mut by_topic: HashMap<String, Vec<(u64, Vec<u8>)>> = HashMap::new();
+    for handle in handles {
+        for (topic, seq, payload) in handle.join().unwrap() {
+            by_topic.entry(topic).or_default().push((seq, payload));
+        }
+    }
+    for entries in by_topic.values_mut() {
+        entries.sort();
+    }
+    by_topic
+}
+
+fn assert_reads_back(wal: &Walrus, expected: &HashMap<String, Vec<(u64, Vec<u8>)>>) {
+    for (topic, entries) in expected {
+        for (seq, payload) in entries {
+            let entry = wal.read_next(topic, true).unwrap().unwrap();
+            assert_eq!(entry.seq, *seq);
+            assert_eq!(&entry.data, payload);
+        }
+        assert!(wal.read_next(topic, true).unwrap().is_none());
+    }
+}
+
+#[test]
+fn group_commit_concurrent_appends_across_topics() {
+    let _env = setup_test_env();
+    let expected = {
+        let wal = Arc::new(open_group_commit(
+            StorageBackend::Fd,
+            FsyncSchedule::SyncEach,
+        ));
+        let expected = concurrent_appends(&wal);
+        for (topic, entries) in &expected {
+            // Every topic got a gapless run of sequence numbers
+            let seqs: Vec<u64> = entries.iter().map(|(seq, _)| *seq).collect();
+            assert_eq!(seqs, (0..entries.len() as u64).collect::<Vec<u64>>());
+            assert_eq!(wal.get_topic_entry_count(topic), entries.len() as u64);
+        }
+        expected
+    };
+
+    thread::sleep(Duration::from_millis(50));
+
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+    assert_reads_back(&wal, &expected);
+}
+
+#[test]
+fn group_commit_with_mmap_backend_and_small_blocks() {
+    let _env = setup_test_env();
+    let wal = Arc::new(
+        Walrus::builder()
+            .backend(StorageBackend::Mmap)
+            .fsync(FsyncSchedule::Milliseconds(10))
+            .group_commit(true)
+            .block_size(4096)
+            .blocks_per_file(16)
+            .open()
+            .unwrap(),
+    );
+    // ~258 byte records seal a 4KB block every 15 entries
+    let expected = concurrent_appends(&wal);
+    assert_reads_back(&wal, &expected);
+}
+
+#[test]
+fn group_commit_rejects_entries_individually() {
+    let _env = setup_test_env();
+    let wal = Walrus::builder()
+        .fsync(FsyncSchedule::SyncEach)
+        .group_commit(true)
+        .block_size(16 * 1024)
+        .blocks_per_file(8)
+        .max_alloc(64 * 1024)
+        .open()
+        .unwrap();
+
+    wal.append_for_topic("mixed", b"before").unwrap();
+    let err = wal
+        .append_for_topic("mixed", &vec![0u8; 100 * 1024])
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    wal.append_for_topic("mixed", b"after").unwrap();
+
+    let first = wal.read_next("mixed", true).unwrap().unwrap();
+    let second = wal.read_next("mixed", true).unwrap().unwrap();
+    assert_eq!((first.seq, first.data), (0, b"before".to_vec()));
+    assert_eq!((second.seq, second.data), (1, b"after".to_vec()));
+    assert!(wal.read_next("mixed", true).unwrap().is_none());
+}
+
+#[test]
+fn group_commit_mixes_with_headers_batches_and_durable_appends() {
+    let _env = setup_test_env();
+    let wal = open_group_commit(StorageBackend::Fd, FsyncSchedule::NoFsync);
+
+    wal.append_for_topic_with_headers("mixed", b"one", &[("k", b"v")])
+        .unwrap();
+    wal.batch_append_for_topic("mixed", &[b"two", b"three"])
+        .unwrap();
+    assert_eq!(wal.append_for_topic_durable("mixed", b"four").unwrap(), 3);
+    wal.sync_all().unwrap();
+
+    let first = wal.read_next("mixed", true).unwrap().unwrap();
+    assert_eq!(first.headers, vec![("k".to_string(), b"v".to_vec())]);
+    for expected in [&b"two"[..], b"three", b"four"] {
+        assert_eq!(
+            wal.read_next("mixed", true).unwrap().unwrap().data,
+            expected
+        );
+    }
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use common::{TestEnv, current_wal_dir, data_files, payload};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use walrus_rust::{FsyncSchedule, ReadConsistency, Walrus};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+fn open_wal(namespace: &str) -> Walrus {
+    // NoFsync wakes the background thread every 10s; reclamation must not wait for it
+    Walrus::builder()
+        .data_dir(current_wal_dir())
+        .namespace(namespace)
+        .consistency(ReadConsistency::StrictlyAtOnce)
+        .fsync(FsyncSchedule::NoFsync)
+        .block_size(16 * 1024)
+        // 3KB entries fill a file every 8 entries
+        .blocks_per_file(2)
+        .open()
+        .unwrap()
+}
+
+fn wait_for_files(dir: &Path, count: usize) -> Vec<String> {
+    let deadline = Instant::now() + Duration::from_secs(3);
+    loop {
+        let files = data_files(dir);
+        if files.len() == count || Instant::now() > deadline {
+            return files;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn namespace_dir(namespace: &str) -> PathBuf {
+    current_wal_dir().join(namespace)
+}
+
+#[test]
+fn consuming_one_instance_leaves_the_others_files_alone() {
+    let _env = setup_test_env();
+    // Both instances number their blocks from 1 and lay out the same topic the same way
+    let a = open_wal("a");
+    let b = open_wal("b");
+    for i in 0..20 {
+        a.append_for_topic("events", &payload(i)).unwrap();
+        b.append_for_topic("events", &payload(i)).unwrap();
+    }
+    let b_files = data_files(&namespace_dir("b"));
+    assert_eq!(b_files.len(), 3);
+
+    // Consuming the first file of `a` frees it, and only it
+    for _ in 0..9 {
+        a.read_next("events", true).unwrap().unwrap();
+    }
+    assert_eq!(wait_for_files(&namespace_dir("a"), 2).len(), 2);
+    assert_eq!(a.reclaim_now().unwrap().reclaimable_files, 0);
+
+    let stats = b.reclaim_now().unwrap();
+    assert_eq!((stats.reclaimable_files, stats.reclaimed_files), (0, 0));
+    assert_eq!(data_files(&namespace_dir("b")), b_files);
+    for i in 0..20 {
+        let entry = b.read_next("events", true).unwrap().unwrap();
+        assert_eq!((entry.seq, entry.data), (i, payload(i)));
+    }
+}
+
+#[test]
+fn every_instance_reclaims_its_own_files() {
+    let _env = setup_test_env();
+    // Opened first, so it would have owned the reclaimer of the whole process
+    let idle = open_wal("idle");
+    idle.append_for_topic("events", b"kept").unwrap();
+
+    let busy = open_wal("busy");
+    for i in 0..20 {
+        busy.append_for_topic("events", &payload(i)).unwrap();
+    }
+    let before = data_files(&namespace_dir("busy"));
+    for _ in 0..9 {
+        busy.read_next("events", true).unwrap().unwrap();
+    }
+    assert_eq!(
+        wait_for_files(&namespace_dir("busy"), 2),
+        before[1..].to_vec()
+    );
+
+    assert!(busy.reclaim_stats().reclaimed_files >= 1);
+    assert_eq!(idle.reclaim_stats().reclaimed_files, 0);
+    assert_eq!(
+        idle.read_next("events", true).unwrap().unwrap().data,
+        b"kept"
+    );
+}
+
+#[test]
+fn instances_in_parallel_threads_stay_independent() {
+    let _env = setup_test_env();
+    let root = current_wal_dir();
+    let namespaces: Vec<String> = (0..4).map(|i| format!("tenant-{}", i)).collect();
+
+    thread::scope(|s| {
+        for (n, namespace) in namespaces.iter().enumerate() {
+            let root = root.clone();
+            s.spawn(move || {
+                let wal = Walrus::builder()
+                    .data_dir(&root)
+                    .namespace(namespace)
+                    .fsync(FsyncSchedule::NoFsync)
+                    .block_size(16 * 1024)
+                    .blocks_per_file(2)
+                    .open()
+                    .unwrap();
+                // Tenants consume different amounts, so their blocks are checkpointed
+                // at different times
+                let total = 16 + 8 * n as u64;
+                for i in 0..total {
+                    wal.append_for_topic("events", &payload(i)).unwrap();
+                }
+                for i in 0..total {
+                    let entry = wal.read_next("events", true).unwrap().unwrap();
+                    assert_eq!((entry.seq, entry.data), (i, payload(i)));
+                }
+                assert!(wal.read_next("events", true).unwrap().is_none());
+                // Every full file is consumed; the one still being written to stays
+                wal.reclaim_now().unwrap();
+                assert_eq!(data_files(&root.join(namespace)).len(), 1);
+            });
+        }
+    });
+}
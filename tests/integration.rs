@@ -743,3 +743,210 @@ fn exactly_once_delivery_guarantee() {
         );
     }
 }
+
+#[test]
+fn integration_sequence_numbers_per_topic() {
+    let _env = setup_test_env();
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+
+    assert_eq!(wal.append_for_topic("seq_a", b"a0").unwrap(), 0);
+    assert_eq!(wal.append_for_topic("seq_a", b"a1").unwrap(), 1);
+    assert_eq!(wal.append_for_topic("seq_b", b"b0").unwrap(), 0);
+    assert_eq!(
+        wal.batch_append_for_topic("seq_a", &[b"a2", b"a3", b"a4"])
+            .unwrap(),
+        2..5
+    );
+    assert_eq!(wal.batch_append_for_topic("seq_a", &[]).unwrap(), 5..5);
+
+    let first = wal.read_next("seq_a", true).unwrap().unwrap();
+    assert_eq!(first.seq, 0);
+    assert_eq!(first.offset, 0);
+    let second = wal.read_next("seq_a", true).unwrap().unwrap();
+    assert_eq!(second.seq, 1);
+    assert_eq!(second.block_id, first.block_id);
+    assert!(second.offset > first.offset);
+
+    let rest = wal.batch_read_for_topic("seq_a", 1024 * 1024, true, None).unwrap();
+    let seqs: Vec<u64> = rest.iter().map(|e| e.seq).collect();
+    assert_eq!(seqs, vec![2, 3, 4]);
+    assert_eq!(rest[0].data, b"a2");
+
+    assert_eq!(wal.read_next("seq_b", true).unwrap().unwrap().seq, 0);
+}
+
+#[test]
+fn integration_sequence_numbers_survive_restart() {
+    let _env = setup_test_env();
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+    for i in 0..3u8 {
+        assert_eq!(wal.append_for_topic("seq_restart", &[i]).unwrap(), i as u64);
+    }
+    drop(wal);
+
+    thread::sleep(Duration::from_millis(50));
+
+    let wal2 = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+    assert_eq!(wal2.append_for_topic("seq_restart", &[3]).unwrap(), 3);
+
+    for i in 0..4u64 {
+        let entry = wal2.read_next("seq_restart", true).unwrap().unwrap();
+        assert_eq!(entry.seq, i);
+        assert_eq!(entry.data, vec![i as u8]);
+    }
+}
+
+#[test]
+fn integration_headers_and_timestamps_round_trip() {
+    let _env = setup_test_env();
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+
+    let before = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    wal.append_for_topic_with_headers(
+        "hdr",
+        b"payload",
+        &[("trace-id", b"abc123"), ("route", b"eu")],
+    )
+    .unwrap();
+    wal.append_for_topic("hdr", b"plain").unwrap();
+    wal.append_for_topic_with_headers("hdr", b"", &[("empty-body", b"")])
+        .unwrap();
+
+    let first = wal.read_next("hdr", true).unwrap().unwrap();
+    assert_eq!(first.data, b"payload");
+    assert_eq!(
+        first.headers,
+        vec![
+            ("trace-id".to_string(), b"abc123".to_vec()),
+            ("route".to_string(), b"eu".to_vec()),
+        ]
+    );
+    assert!(first.timestamp_ms >= before);
+
+    let rest = wal
+        .batch_read_for_topic("hdr", 1024 * 1024, false, None)
+        .unwrap();
+    assert_eq!(rest[0].data, b"plain");
+    assert!(rest[0].headers.is_empty());
+    assert!(rest[0].timestamp_ms >= first.timestamp_ms);
+    assert_eq!(
+        wal.read_next("hdr", true).unwrap().unwrap().headers,
+        Vec::<(String, Vec<u8>)>::new()
+    );
+    let last = wal.read_next("hdr", true).unwrap().unwrap();
+    assert!(last.data.is_empty());
+    assert_eq!(last.headers, vec![("empty-body".to_string(), Vec::new())]);
+}
+
+#[test]
+fn integration_headers_survive_restart() {
+    let _env = setup_test_env();
+    let stamped = {
+        let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+        wal.append_for_topic_with_headers("hdr_restart", b"data", &[("k", b"v")])
+            .unwrap();
+        wal.read_next("hdr_restart", false)
+            .unwrap()
+            .unwrap()
+            .timestamp_ms
+    };
+
+    thread::sleep(Duration::from_millis(50));
+
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+    let entry = wal.read_next("hdr_restart", true).unwrap().unwrap();
+    assert_eq!(entry.data, b"data");
+    assert_eq!(entry.headers, vec![("k".to_string(), b"v".to_vec())]);
+    assert_eq!(entry.timestamp_ms, stamped);
+}
+
+#[test]
+fn integration_oversized_headers_are_rejected() {
+    let _env = setup_test_env();
+    let wal = Walrus::new().unwrap();
+
+    let big = vec![0u8; 70 * 1024];
+    let err = wal
+        .append_for_topic_with_headers("hdr_big", b"x", &[("big", &big)])
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(wal.read_next("hdr_big", true).unwrap().is_none());
+}
+
+#[test]
+fn integration_durable_appends_from_many_threads() {
+    let _env = setup_test_env();
+    {
+        let wal = Arc::new(
+            Walrus::with_consistency_and_schedule(
+                ReadConsistency::StrictlyAtOnce,
+                FsyncSchedule::NoFsync,
+            )
+            .unwrap(),
+        );
+        let handles: Vec<_> = (0..8u8)
+            .map(|t| {
+                let wal = wal.clone();
+                thread::spawn(move || {
+                    (0..25u8)
+                        .map(|i| wal.append_for_topic_durable("durable", &[t, i]).unwrap())
+                        .collect::<Vec<u64>>()
+                })
+            })
+            .collect();
+        let mut seqs: Vec<u64> = handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect();
+        seqs.sort_unstable();
+        assert_eq!(seqs, (0..200).collect::<Vec<u64>>());
+    }
+
+    thread::sleep(Duration::from_millis(50));
+
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+    let mut seen = 0;
+    while let Some(entry) = wal.read_next("durable", true).unwrap() {
+        assert_eq!(entry.seq, seen);
+        seen += 1;
+    }
+    assert_eq!(seen, 200);
+}
+
+#[test]
+fn integration_sync_topic_and_sync_all() {
+    let _env = setup_test_env();
+    {
+        let wal = Walrus::with_consistency_and_schedule(
+            ReadConsistency::StrictlyAtOnce,
+            FsyncSchedule::NoFsync,
+        )
+        .unwrap();
+        // Topics that were never written have nothing to sync
+        wal.sync_topic("untouched").unwrap();
+        wal.sync_all().unwrap();
+
+        wal.append_for_topic("a", b"a1").unwrap();
+        wal.batch_append_for_topic("a", &[b"a2", b"a3"]).unwrap();
+        wal.sync_topic("a").unwrap();
+
+        wal.append_for_topic("b", b"b1").unwrap();
+        wal.append_for_topic("c", b"c1").unwrap();
+        wal.sync_all().unwrap();
+        // Syncing again with nothing new is a no-op
+        wal.sync_topic("b").unwrap();
+        wal.sync_all().unwrap();
+    }
+
+    thread::sleep(Duration::from_millis(50));
+
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+    for expected in [b"a1", b"a2", b"a3"] {
+        assert_eq!(wal.read_next("a", true).unwrap().unwrap().data, expected);
+    }
+    assert_eq!(wal.read_next("b", true).unwrap().unwrap().data, b"b1");
+    assert_eq!(wal.read_next("c", true).unwrap().unwrap().data, b"c1");
+}
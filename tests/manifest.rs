@@ -0,0 +1,190 @@
+mod common;
+
+use common::{TestEnv, current_wal_dir};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use walrus_rust::{FsyncSchedule, ReadConsistency, TopicInfo, Walrus};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+fn open_wal() -> Walrus {
+    Walrus::builder()
+        .data_dir(current_wal_dir())
+        .namespace("manifest")
+        .consistency(ReadConsistency::StrictlyAtOnce)
+        .fsync(FsyncSchedule::NoFsync)
+        .block_size(16 * 1024)
+        .blocks_per_file(4)
+        .open()
+        .unwrap()
+}
+
+fn wal_dir() -> PathBuf {
+    current_wal_dir().join("manifest")
+}
+
+fn manifest_path() -> PathBuf {
+    wal_dir().join("block_manifest_index.db")
+}
+
+// 1KB entries, so a 16KB block holds 15 of them
+fn entry(i: u64) -> Vec<u8> {
+    let mut data = format!("entry-{:04}-", i).into_bytes();
+    data.resize(1024, b'.');
+    data
+}
+
+fn append_entries(wal: &Walrus, topic: &str, range: std::ops::Range<u64>) {
+    for i in range {
+        wal.append_for_topic(topic, &entry(i)).unwrap();
+    }
+}
+
+// Sequence number and leading text of each entry, short enough to show in a failure
+fn read_all(wal: &Walrus, group: &str, topic: &str) -> Vec<(u64, String)> {
+    let mut entries = Vec::new();
+    while let Some(entry) = wal.read_next_as(group, topic, true).unwrap() {
+        let text = String::from_utf8_lossy(&entry.data[..entry.data.len().min(10)]);
+        entries.push((entry.seq, text.into_owned()));
+    }
+    entries
+}
+
+fn expected(range: std::ops::Range<u64>) -> Vec<(u64, String)> {
+    range.map(|i| (i, format!("entry-{:04}", i))).collect()
+}
+
+#[test]
+fn restart_after_close_takes_sealed_blocks_from_the_manifest() {
+    let _env = setup_test_env();
+    let wal = open_wal();
+    append_entries(&wal, "events", 0..40);
+    wal.close().unwrap();
+    assert!(manifest_path().exists());
+
+    // Recovery reading the block would stop at the damaged record and lose the rest
+    let first = entry(0);
+    let needle = &first[..11];
+    let damaged = fs::read_dir(wal_dir())
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| !p.to_string_lossy().ends_with("_index.db"))
+        .find_map(|path| {
+            let mut bytes = fs::read(&path).unwrap();
+            let at = bytes.windows(needle.len()).position(|w| w == needle)?;
+            bytes[at + 20] ^= 0xff;
+            fs::write(&path, bytes).unwrap();
+            Some(path)
+        });
+    assert!(damaged.is_some());
+
+    let wal = open_wal();
+    assert_eq!(wal.get_topic_entry_count("events"), 40);
+    assert_eq!(wal.topic_info("events").unwrap().next_seq, 40);
+    assert_eq!(wal.append_for_topic("events", b"next").unwrap(), 40);
+}
+
+#[test]
+fn crash_recovers_blocks_missing_from_the_manifest() {
+    let _env = setup_test_env();
+    let wal = open_wal();
+    append_entries(&wal, "events", 0..40);
+    append_entries(&wal, "audit", 0..3);
+    // The process dies without shutting down: only the sealed blocks were recorded
+    std::mem::forget(wal);
+
+    let wal = open_wal();
+    assert_eq!(wal.get_topic_entry_count("events"), 40);
+    assert_eq!(wal.get_topic_entry_count("audit"), 3);
+    append_entries(&wal, "events", 40..45);
+    wal.close().unwrap();
+
+    let wal = open_wal();
+    assert_eq!(read_all(&wal, "readers", "events"), expected(0..45));
+    assert_eq!(read_all(&wal, "readers", "audit"), expected(0..3));
+}
+
+#[test]
+fn recovery_from_the_manifest_matches_a_full_scan() {
+    let _env = setup_test_env();
+    let wal = open_wal();
+    append_entries(&wal, "events", 0..20);
+    let mut tx = wal.begin_transaction();
+    for i in 0..20 {
+        tx.append("orders", &entry(i)).unwrap();
+    }
+    tx.commit().unwrap();
+    let mut tx = wal.begin_transaction();
+    tx.append("orders", b"aborted").unwrap();
+    tx.abort().unwrap();
+    append_entries(&wal, "events", 20..35);
+    append_entries(&wal, "orders", 20..25);
+    for _ in 0..5 {
+        wal.read_next("events", true).unwrap().unwrap();
+    }
+    wal.close().unwrap();
+
+    let snapshot = |group: &str| -> Vec<(TopicInfo, Vec<(u64, String)>)> {
+        let wal = open_wal();
+        let state = ["events", "orders"]
+            .iter()
+            .map(|topic| {
+                let info = wal.topic_info(topic).unwrap();
+                (info, read_all(&wal, group, topic))
+            })
+            .collect();
+        wal.close().unwrap();
+        state
+    };
+    let from_manifest = snapshot("manifest");
+    fs::remove_file(manifest_path()).unwrap();
+    let from_scan = snapshot("scan");
+
+    assert_eq!(from_manifest[0].1.len(), 35);
+    assert_eq!(from_manifest[1].1.len(), 25);
+    assert!(from_manifest[1].1.iter().all(|(_, text)| text != "aborted"));
+    // Block ids and files differ since every open starts a new file
+    for ((a, a_entries), (b, b_entries)) in from_manifest.iter().zip(&from_scan) {
+        let used = |info: &TopicInfo| info.blocks.iter().map(|b| b.used).collect::<Vec<_>>();
+        assert_eq!(used(a), used(b));
+        assert_eq!(
+            (a.unread_entries, a.unread_bytes, a.next_seq),
+            (b.unread_entries, b.unread_bytes, b.next_seq)
+        );
+        assert_eq!(a_entries, b_entries);
+    }
+}
+
+#[test]
+fn torn_manifest_falls_back_to_scanning() {
+    let _env = setup_test_env();
+    let wal = open_wal();
+    append_entries(&wal, "events", 0..40);
+    wal.close().unwrap();
+
+    let len = fs::metadata(manifest_path()).unwrap().len();
+    OpenOptions::new()
+        .write(true)
+        .open(manifest_path())
+        .unwrap()
+        .set_len(len - 7)
+        .unwrap();
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(manifest_path())
+        .unwrap();
+    file.write_all(&[0xab; 32]).unwrap();
+    drop(file);
+
+    // The first open rewrites the manifest from what it scanned
+    for _ in 0..2 {
+        let wal = open_wal();
+        assert_eq!(wal.get_topic_entry_count("events"), 40);
+        wal.close().unwrap();
+    }
+    let wal = open_wal();
+    assert_eq!(read_all(&wal, "readers", "events"), expected(0..40));
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::TestEnv;
+use walrus_rust::{FsyncSchedule, ReadConsistency, Walrus, WalrusBuilder};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+fn builder() -> WalrusBuilder {
+    Walrus::builder()
+        .consistency(ReadConsistency::StrictlyAtOnce)
+        .fsync(FsyncSchedule::NoFsync)
+        .block_size(16 * 1024)
+        .blocks_per_file(4)
+}
+
+fn read_all(wal: &Walrus, topic: &str) -> Vec<(u64, Vec<u8>)> {
+    let mut entries = Vec::new();
+    while let Some(entry) = wal.read_next(topic, true).unwrap() {
+        entries.push((entry.seq, entry.data));
+    }
+    entries
+}
+
+#[test]
+fn every_batch_is_appended_in_order() {
+    let _env = setup_test_env();
+    let wal = builder().open().unwrap();
+    wal.append_for_topic("orders", b"earlier").unwrap();
+
+    let command: &[&[u8]] = &[b"create", b"confirm"];
+    let event: &[&[u8]] = &[b"created"];
+    let ranges = wal
+        .batch_append_multi(&[("orders", command), ("outbox", event), ("idle", &[])])
+        .unwrap();
+    assert_eq!(ranges, vec![1..3, 0..1, 0..0]);
+
+    assert_eq!(
+        read_all(&wal, "orders"),
+        vec![
+            (0, b"earlier".to_vec()),
+            (1, b"create".to_vec()),
+            (2, b"confirm".to_vec())
+        ]
+    );
+    assert_eq!(read_all(&wal, "outbox"), vec![(0, b"created".to_vec())]);
+    assert_eq!(wal.get_topic_entry_count("orders"), 0);
+    assert!(wal.read_next("idle", true).unwrap().is_none());
+}
+
+#[test]
+fn batches_spanning_blocks_survive_a_restart() {
+    let _env = setup_test_env();
+    let payloads: Vec<Vec<u8>> = (0..10u8).map(|i| vec![i; 3 * 1024]).collect();
+    let entries: Vec<&[u8]> = payloads.iter().map(Vec::as_slice).collect();
+    {
+        let wal = builder().open().unwrap();
+        let ranges = wal
+            .batch_append_multi(&[("a", &entries), ("b", &entries[..3])])
+            .unwrap();
+        assert_eq!(ranges, vec![0..10, 0..3]);
+    }
+
+    let wal = builder().open().unwrap();
+    let expected: Vec<(u64, Vec<u8>)> = payloads
+        .iter()
+        .cloned()
+        .enumerate()
+        .map(|(i, p)| (i as u64, p))
+        .collect();
+    assert_eq!(read_all(&wal, "a"), expected);
+    assert_eq!(read_all(&wal, "b"), expected[..3].to_vec());
+    assert_eq!(wal.append_for_topic("b", b"next").unwrap(), 3);
+}
+
+#[test]
+fn a_failing_batch_appends_to_no_topic() {
+    let _env = setup_test_env();
+    let wal = builder().open().unwrap();
+    wal.append_for_topic("outbox", b"earlier").unwrap();
+
+    // Larger than a 64KB file, so the allocator refuses it
+    let too_big = vec![0u8; 100 * 1024];
+    let command: &[&[u8]] = &[b"create"];
+    let event: &[&[u8]] = &[b"created", &too_big];
+    assert!(
+        wal.batch_append_multi(&[("orders", command), ("outbox", event)])
+            .is_err()
+    );
+
+    assert!(wal.read_next("orders", true).unwrap().is_none());
+    assert_eq!(read_all(&wal, "outbox"), vec![(0, b"earlier".to_vec())]);
+    assert_eq!(wal.append_for_topic("orders", b"retry").unwrap(), 0);
+    assert_eq!(wal.append_for_topic("outbox", b"retry").unwrap(), 1);
+    assert_eq!(read_all(&wal, "orders"), vec![(0, b"retry".to_vec())]);
+}
+
+#[test]
+fn repeated_topics_are_rejected() {
+    let _env = setup_test_env();
+    let wal = builder().open().unwrap();
+    let entries: &[&[u8]] = &[b"x"];
+    let err = wal
+        .batch_append_multi(&[("orders", entries), ("orders", entries)])
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(wal.read_next("orders", true).unwrap().is_none());
+}
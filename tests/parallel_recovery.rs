@@ -0,0 +1,90 @@
+mod common;
+
+use common::{TestEnv, current_wal_dir};
+use std::fs;
+use walrus_rust::{FsyncSchedule, ReadConsistency, Walrus};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+// Two 16KB blocks per file, so a few hundred entries spread over many files
+fn open_wal() -> Walrus {
+    Walrus::builder()
+        .data_dir(current_wal_dir())
+        .namespace("parallel")
+        .consistency(ReadConsistency::StrictlyAtOnce)
+        .fsync(FsyncSchedule::NoFsync)
+        .block_size(16 * 1024)
+        .blocks_per_file(2)
+        .open()
+        .unwrap()
+}
+
+fn data_files() -> usize {
+    common::data_files(&current_wal_dir().join("parallel")).len()
+}
+
+fn entry(topic: &str, i: u64) -> Vec<u8> {
+    let mut data = format!("{}-{:04}-", topic, i).into_bytes();
+    data.resize(1024, b'.');
+    data
+}
+
+fn check_topic(wal: &Walrus, topic: &str, count: u64) {
+    for i in 0..count {
+        let read = wal
+            .read_next_as("readers", topic, true)
+            .unwrap()
+            .unwrap_or_else(|| panic!("{} ends before entry {}", topic, i));
+        assert_eq!(read.seq, i);
+        assert!(read.data == entry(topic, i), "{} entry {} differs", topic, i);
+    }
+    assert!(wal.read_next_as("readers", topic, true).unwrap().is_none());
+}
+
+#[test]
+fn interleaved_topics_across_many_files_recover_in_order() {
+    let _env = setup_test_env();
+    let topics = ["alpha", "beta", "gamma"];
+    let wal = open_wal();
+    for i in 0..100 {
+        for topic in topics {
+            wal.append_for_topic(topic, &entry(topic, i)).unwrap();
+        }
+    }
+    // Nothing recorded beyond the sealed blocks, and the manifest gone too
+    std::mem::forget(wal);
+    fs::remove_file(current_wal_dir().join("parallel/block_manifest_index.db")).unwrap();
+    assert!(data_files() >= 8);
+
+    let wal = open_wal();
+    for topic in topics {
+        assert_eq!(wal.get_topic_entry_count(topic), 100);
+        assert_eq!(wal.topic_info(topic).unwrap().next_seq, 100);
+        check_topic(&wal, topic, 100);
+    }
+}
+
+#[test]
+fn files_in_and_out_of_the_manifest_recover_together() {
+    let _env = setup_test_env();
+    let wal = open_wal();
+    for i in 0..60 {
+        wal.append_for_topic("events", &entry("events", i)).unwrap();
+    }
+    wal.close().unwrap();
+
+    let wal = open_wal();
+    for i in 60..120 {
+        wal.append_for_topic("events", &entry("events", i)).unwrap();
+        wal.append_for_topic("audit", &entry("audit", i - 60)).unwrap();
+    }
+    std::mem::forget(wal);
+
+    let wal = open_wal();
+    assert_eq!(wal.get_topic_entry_count("events"), 120);
+    assert_eq!(wal.get_topic_entry_count("audit"), 60);
+    check_topic(&wal, "events", 120);
+    check_topic(&wal, "audit", 60);
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use common::{TestEnv, current_wal_dir, payload};
+use std::thread;
+use std::time::{Duration, Instant};
+use walrus_rust::{FsyncSchedule, ReadConsistency, SeekTarget, Walrus};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+fn open_wal() -> Walrus {
+    // NoFsync wakes the background thread every 10s; reclamation must not wait for it
+    Walrus::builder()
+        .consistency(ReadConsistency::StrictlyAtOnce)
+        .fsync(FsyncSchedule::NoFsync)
+        .block_size(16 * 1024)
+        // 3KB entries fill a file every 8 entries
+        .blocks_per_file(2)
+        .open()
+        .unwrap()
+}
+
+fn data_files() -> Vec<String> {
+    common::data_files(&current_wal_dir())
+}
+
+fn wait_for_files(count: usize) -> Vec<String> {
+    let deadline = Instant::now() + Duration::from_secs(3);
+    loop {
+        let files = data_files();
+        if files.len() == count || Instant::now() > deadline {
+            return files;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn consumed_files_are_deleted_promptly() {
+    let _env = setup_test_env();
+    let wal = open_wal();
+    for i in 0..20 {
+        wal.append_for_topic("events", &payload(i)).unwrap();
+    }
+    let before = data_files();
+    assert_eq!(before.len(), 3);
+
+    // Consuming the first file's two blocks frees it, long before the next 10s tick
+    for _ in 0..9 {
+        wal.read_next("events", true).unwrap().unwrap();
+    }
+    let after = wait_for_files(2);
+    assert_eq!(after, before[1..].to_vec());
+
+    let stats = wal.reclaim_stats();
+    assert_eq!((stats.reclaimable_files, stats.reclaimable_bytes), (0, 0));
+    assert!(stats.reclaimed_files >= 1);
+}
+
+#[test]
+fn partially_consumed_files_are_kept() {
+    let _env = setup_test_env();
+    let wal = open_wal();
+    for i in 0..20 {
+        wal.append_for_topic("events", &payload(i)).unwrap();
+    }
+    // Only the first block of the first file is consumed
+    for _ in 0..5 {
+        wal.read_next("events", true).unwrap().unwrap();
+    }
+    let stats = wal.reclaim_now().unwrap();
+    assert_eq!(stats.reclaimable_files, 0);
+    assert_eq!(data_files().len(), 3);
+}
+
+#[test]
+fn reclaim_now_deletes_truncated_files() {
+    let _env = setup_test_env();
+    let wal = open_wal();
+    for i in 0..20 {
+        wal.append_for_topic("events", &payload(i)).unwrap();
+    }
+    let before = data_files();
+
+    wal.truncate_topic_before("events", SeekTarget::Sequence(16))
+        .unwrap();
+    let stats = wal.reclaim_now().unwrap();
+    assert_eq!((stats.reclaimable_files, stats.reclaimable_bytes), (0, 0));
+    assert_eq!(data_files(), before[2..].to_vec());
+    assert_eq!(wal.read_next("events", true).unwrap().unwrap().seq, 16);
+}
+
+#[test]
+fn reclaimed_blocks_leave_the_seek_index() {
+    let _env = setup_test_env();
+    let wal = open_wal();
+    for i in 0..20 {
+        wal.append_for_topic("events", &payload(i)).unwrap();
+    }
+    for _ in 0..9 {
+        wal.read_next("events", true).unwrap().unwrap();
+    }
+    wal.reclaim_now().unwrap();
+    assert_eq!(data_files().len(), 2);
+
+    // A group that has not read the topic only has the entries still on disk ahead of it
+    assert_eq!(wal.get_topic_entry_count_as("late", "events"), 12);
+    assert_eq!(
+        wal.seek_topic_as("late", "events", SeekTarget::Beginning)
+            .unwrap(),
+        8
+    );
+}
+
+#[test]
+fn sequence_numbers_survive_reclaiming_every_block() {
+    let _env = setup_test_env();
+    {
+        let wal = open_wal();
+        for i in 0..8 {
+            wal.append_for_topic("events", &payload(i)).unwrap();
+        }
+    }
+    let written = data_files();
+    {
+        let wal = open_wal();
+        for i in 0..8 {
+            assert_eq!(wal.read_next("events", true).unwrap().unwrap().seq, i);
+        }
+        assert!(wal.read_next("events", true).unwrap().is_none());
+        wal.reclaim_now().unwrap();
+        assert!(!data_files().contains(&written[0]));
+    }
+
+    // Nothing of the topic is left on disk, yet its sequence numbers carry on
+    let wal = open_wal();
+    assert_eq!(wal.append_for_topic("events", &payload(8)).unwrap(), 8);
+    assert_eq!(wal.read_next("events", true).unwrap().unwrap().seq, 8);
+}
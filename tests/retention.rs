@@ -0,0 +1,150 @@
+mod common;
+
+use common::{ENTRY_BYTES, TestEnv, payload};
+use std::thread;
+use std::time::{Duration, Instant};
+use walrus_rust::{FsyncSchedule, ReadConsistency, RetentionPolicy, Walrus, WalrusBuilder};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+fn builder() -> WalrusBuilder {
+    Walrus::builder()
+        .consistency(ReadConsistency::StrictlyAtOnce)
+        .fsync(FsyncSchedule::NoFsync)
+        .block_size(16 * 1024)
+        .blocks_per_file(8)
+}
+
+// Retention runs on the background thread, shortly after a block is sealed
+fn wait_for(mut done: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(3);
+    loop {
+        if done() || Instant::now() > deadline {
+            return done();
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn sealed_blocks(wal: &Walrus, topic: &str) -> usize {
+    wal.topic_info(topic).unwrap().blocks.len()
+}
+
+#[test]
+fn max_entries_expires_unread_blocks() {
+    let _env = setup_test_env();
+    let wal = builder()
+        .retention(RetentionPolicy::new().max_entries(6))
+        .open()
+        .unwrap();
+    for i in 0..17 {
+        wal.append_for_topic("events", &payload(i)).unwrap();
+    }
+
+    // Entry 16 sealed the fourth block: entries from 11 on have to stay, and 8..12
+    // share a block with 11
+    assert!(wait_for(|| sealed_blocks(&wal, "events") == 2));
+    let entry = wal.read_next("events", true).unwrap().unwrap();
+    assert_eq!((entry.seq, entry.data), (8, payload(8)));
+}
+
+#[test]
+fn max_bytes_keeps_the_newest_blocks() {
+    let _env = setup_test_env();
+    let wal = builder().open().unwrap();
+    wal.set_topic_retention("events", RetentionPolicy::new().max_bytes(8 * ENTRY_BYTES))
+        .unwrap();
+    for i in 0..20 {
+        wal.append_for_topic("events", &payload(i)).unwrap();
+    }
+
+    assert!(wait_for(|| sealed_blocks(&wal, "events") == 1));
+    assert_eq!(
+        wal.topic_info("events").unwrap().unread_bytes,
+        8 * ENTRY_BYTES
+    );
+    assert_eq!(wal.read_next("events", true).unwrap().unwrap().seq, 12);
+}
+
+#[test]
+fn max_age_expires_idle_topics_in_the_background() {
+    let _env = setup_test_env();
+    let wal = builder()
+        .retention(RetentionPolicy::new().max_age(Duration::from_millis(100)))
+        .open()
+        .unwrap();
+    for i in 0..10 {
+        wal.append_for_topic("events", &payload(i)).unwrap();
+    }
+    assert_eq!(wal.read_next("events", true).unwrap().unwrap().seq, 0);
+    assert_eq!(
+        wal.read_next_as("audit", "events", true)
+            .unwrap()
+            .unwrap()
+            .seq,
+        0
+    );
+
+    // Both sealed blocks expire without further appends; the writer's active block stays
+    assert!(wait_for(|| sealed_blocks(&wal, "events") == 0));
+    assert_eq!(wal.enforce_retention().unwrap(), 0);
+    assert_eq!(wal.read_next("events", true).unwrap().unwrap().seq, 8);
+    assert_eq!(
+        wal.read_next_as("audit", "events", true)
+            .unwrap()
+            .unwrap()
+            .seq,
+        8
+    );
+    assert_eq!(wal.get_topic_entry_count("events"), 1);
+}
+
+#[test]
+fn topic_policies_override_the_default() {
+    let _env = setup_test_env();
+    let wal = builder()
+        .retention(RetentionPolicy::new().max_entries(4))
+        .open()
+        .unwrap();
+    wal.set_topic_retention("kept", RetentionPolicy::new())
+        .unwrap();
+    for i in 0..12 {
+        wal.append_for_topic("kept", &payload(i)).unwrap();
+    }
+    // Entry 8 sealed the second block, when entries from 5 on had to stay
+    for i in 0..9 {
+        wal.append_for_topic("trimmed", &payload(i)).unwrap();
+    }
+    assert!(wait_for(|| sealed_blocks(&wal, "trimmed") == 1));
+    assert_eq!(wal.read_next("kept", true).unwrap().unwrap().seq, 0);
+    assert_eq!(wal.read_next("trimmed", true).unwrap().unwrap().seq, 4);
+
+    // Back under the default, applied right away
+    wal.clear_topic_retention("kept").unwrap();
+    assert_eq!(wal.read_next("kept", true).unwrap().unwrap().seq, 8);
+}
+
+#[test]
+fn recovered_topics_are_trimmed_on_open() {
+    let _env = setup_test_env();
+    {
+        let wal = builder().open().unwrap();
+        for i in 0..20 {
+            wal.append_for_topic("events", &payload(i)).unwrap();
+        }
+    }
+
+    let wal = builder()
+        .retention(RetentionPolicy::new().max_entries(4))
+        .open()
+        .unwrap();
+    assert_eq!(wal.read_next("events", true).unwrap().unwrap().seq, 16);
+
+    // The expired blocks stay gone after another restart
+    drop(wal);
+    let wal = builder().open().unwrap();
+    assert_eq!(wal.read_next("events", true).unwrap().unwrap().seq, 17);
+    assert_eq!(wal.append_for_topic("events", &payload(20)).unwrap(), 20);
+}
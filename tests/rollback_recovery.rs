@@ -5,7 +5,7 @@ use std::os::unix::fs::FileExt;
 use std::sync::{Arc, Barrier};
 use std::thread;
 use std::time::Duration;
-use walrus_rust::wal::PREFIX_META_SIZE;
+use walrus_rust::wal::{FILE_HEADER_SIZE, PREFIX_META_SIZE};
 use walrus_rust::{FsyncSchedule, ReadConsistency, Walrus, enable_fd_backend};
 
 fn setup_test_env() -> TestEnv {
@@ -37,7 +37,8 @@ fn test_zeroed_header_stops_block_scanning() {
             wal.append_for_topic("zero_test", data.as_bytes()).unwrap();
         }
 
-        drop(wal);
+        // A crash: a clean shutdown would record the block in the manifest
+        std::mem::forget(wal);
 
         thread::sleep(Duration::from_millis(50));
     }
@@ -51,8 +52,8 @@ fn test_zeroed_header_stops_block_scanning() {
 
         assert_eq!(wal_files.len(), 1, "Should have exactly one WAL file");
 
-        let offset_0 = 0;
-        let offset_1 = entry_offset("entry_0".len());
+        let offset_0 = FILE_HEADER_SIZE as usize;
+        let offset_1 = offset_0 + entry_offset("entry_0".len());
         let offset_2 = offset_1 + entry_offset("entry_1".len());
 
         let file_path = wal_files[0].path();
@@ -276,7 +277,8 @@ fn test_recovery_preserves_data_before_zeroed_headers() {
 
         wal.append_for_topic("preserve_test", b"small_2").unwrap();
 
-        drop(wal);
+        // A crash: a clean shutdown would record the block in the manifest
+        std::mem::forget(wal);
 
         thread::sleep(Duration::from_millis(50));
     }
@@ -296,7 +298,7 @@ fn test_recovery_preserves_data_before_zeroed_headers() {
             .open(&file_path)
             .expect("Failed to open WAL file");
 
-        let offset_large = entry_offset("small_1".len());
+        let offset_large = FILE_HEADER_SIZE as usize + entry_offset("small_1".len());
 
         let zeros = vec![0u8; PREFIX_META_SIZE];
         file.write_at(&zeros, offset_large as u64)
@@ -0,0 +1,202 @@
+mod common;
+
+use common::TestEnv;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use walrus_rust::{ReadConsistency, SeekTarget, Walrus};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[test]
+fn seek_sequence_replays_consumed_entries() {
+    let _env = setup_test_env();
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+
+    for i in 0..10u8 {
+        wal.append_for_topic("replay", &[i]).unwrap();
+    }
+    for _ in 0..10 {
+        wal.read_next("replay", true).unwrap().unwrap();
+    }
+    assert!(wal.read_next("replay", true).unwrap().is_none());
+
+    assert_eq!(
+        wal.seek_topic("replay", SeekTarget::Sequence(3)).unwrap(),
+        3
+    );
+    assert_eq!(wal.get_topic_entry_count("replay"), 7);
+    for i in 3..10u8 {
+        let entry = wal.read_next("replay", true).unwrap().unwrap();
+        assert_eq!(entry.seq, i as u64);
+        assert_eq!(entry.data, vec![i]);
+    }
+    assert!(wal.read_next("replay", true).unwrap().is_none());
+}
+
+#[test]
+fn seek_beginning_and_end() {
+    let _env = setup_test_env();
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+
+    for i in 0..5u8 {
+        wal.append_for_topic("ends", &[i]).unwrap();
+    }
+
+    assert_eq!(wal.seek_topic("ends", SeekTarget::End).unwrap(), 5);
+    assert_eq!(wal.get_topic_entry_count("ends"), 0);
+    assert!(wal.read_next("ends", true).unwrap().is_none());
+
+    wal.append_for_topic("ends", &[5]).unwrap();
+    assert_eq!(wal.read_next("ends", true).unwrap().unwrap().seq, 5);
+
+    assert_eq!(wal.seek_topic("ends", SeekTarget::Beginning).unwrap(), 0);
+    let entries = wal
+        .batch_read_for_topic("ends", 1024 * 1024, true, None)
+        .unwrap();
+    let seqs: Vec<u64> = entries.iter().map(|e| e.seq).collect();
+    assert_eq!(seqs, vec![0, 1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn seek_past_last_entry_lands_at_end() {
+    let _env = setup_test_env();
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+
+    for i in 0..3u8 {
+        wal.append_for_topic("past_end", &[i]).unwrap();
+    }
+    assert_eq!(
+        wal.seek_topic("past_end", SeekTarget::Sequence(100))
+            .unwrap(),
+        3
+    );
+    assert!(wal.read_next("past_end", true).unwrap().is_none());
+
+    assert_eq!(wal.seek_topic("missing", SeekTarget::Beginning).unwrap(), 0);
+    assert!(wal.read_next("missing", true).unwrap().is_none());
+}
+
+#[test]
+fn seek_sequence_within_dense_block() {
+    let _env = setup_test_env();
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+
+    // More entries than the sparse index stride, so the seek has to scan forward
+    for i in 0..300u32 {
+        wal.append_for_topic("dense", &i.to_le_bytes()).unwrap();
+    }
+
+    assert_eq!(
+        wal.seek_topic("dense", SeekTarget::Sequence(201)).unwrap(),
+        201
+    );
+    let entry = wal.read_next("dense", true).unwrap().unwrap();
+    assert_eq!(entry.seq, 201);
+    assert_eq!(entry.data, 201u32.to_le_bytes());
+}
+
+#[test]
+fn seek_sequence_into_sealed_blocks() {
+    let _env = setup_test_env();
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+
+    // ~1MB entries spill over several 10MB blocks
+    let payload = vec![7u8; 1024 * 1024];
+    for _ in 0..25 {
+        wal.append_for_topic("sealed", &payload).unwrap();
+    }
+    for _ in 0..25 {
+        wal.read_next("sealed", true).unwrap().unwrap();
+    }
+
+    assert_eq!(
+        wal.seek_topic("sealed", SeekTarget::Sequence(12)).unwrap(),
+        12
+    );
+    let first = wal.read_next("sealed", true).unwrap().unwrap();
+    assert_eq!(first.seq, 12);
+    assert_eq!(first.data.len(), payload.len());
+
+    let mut last = first.seq;
+    while let Some(entry) = wal.read_next("sealed", true).unwrap() {
+        assert_eq!(entry.seq, last + 1);
+        last = entry.seq;
+    }
+    assert_eq!(last, 24);
+}
+
+#[test]
+fn seek_timestamp_skips_older_entries() {
+    let _env = setup_test_env();
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+
+    for i in 0..3u8 {
+        wal.append_for_topic("by_time", &[i]).unwrap();
+    }
+    thread::sleep(Duration::from_millis(20));
+    let cutoff = now_ms();
+    for i in 3..6u8 {
+        wal.append_for_topic("by_time", &[i]).unwrap();
+    }
+
+    assert_eq!(
+        wal.seek_topic("by_time", SeekTarget::Timestamp(cutoff))
+            .unwrap(),
+        3
+    );
+    assert_eq!(
+        wal.read_next("by_time", true).unwrap().unwrap().data,
+        vec![3]
+    );
+
+    assert_eq!(
+        wal.seek_topic("by_time", SeekTarget::Timestamp(now_ms() + 60_000))
+            .unwrap(),
+        6
+    );
+    assert!(wal.read_next("by_time", true).unwrap().is_none());
+}
+
+#[test]
+fn seek_position_survives_restart() {
+    let _env = setup_test_env();
+    {
+        let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+        for i in 0..10u8 {
+            wal.append_for_topic("persisted", &[i]).unwrap();
+        }
+        for _ in 0..10 {
+            wal.read_next("persisted", true).unwrap().unwrap();
+        }
+        assert_eq!(
+            wal.seek_topic("persisted", SeekTarget::Sequence(4))
+                .unwrap(),
+            4
+        );
+    }
+
+    thread::sleep(Duration::from_millis(50));
+
+    let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+    assert_eq!(wal.get_topic_entry_count("persisted"), 6);
+    let entry = wal.read_next("persisted", true).unwrap().unwrap();
+    assert_eq!(entry.seq, 4);
+    assert_eq!(entry.data, vec![4]);
+
+    // Recovery rebuilds the seek index, so seeking works on recovered blocks too
+    assert_eq!(
+        wal.seek_topic("persisted", SeekTarget::Sequence(1))
+            .unwrap(),
+        1
+    );
+    assert_eq!(wal.read_next("persisted", true).unwrap().unwrap().seq, 1);
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use common::{TestEnv, current_wal_dir};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use walrus_rust::{FsyncSchedule, ReadConsistency, Walrus};
+
+// Threads are counted for the whole process, so tests must not open instances side by side
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn setup_test_env() -> (MutexGuard<'static, ()>, TestEnv) {
+    let guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    (guard, TestEnv::new())
+}
+
+fn open_wal(fsync: FsyncSchedule) -> Walrus {
+    Walrus::builder()
+        .data_dir(current_wal_dir())
+        .namespace("service")
+        .consistency(ReadConsistency::StrictlyAtOnce)
+        .fsync(fsync)
+        .block_size(16 * 1024)
+        .blocks_per_file(2)
+        .open()
+        .unwrap()
+}
+
+fn wal_dir() -> PathBuf {
+    current_wal_dir().join("service")
+}
+
+fn thread_count() -> usize {
+    fs::read_dir("/proc/self/task").unwrap().count()
+}
+
+// Descriptors and mappings the process still holds on files of `dir`
+fn open_handles(dir: &Path) -> (usize, usize) {
+    let fds = fs::read_dir("/proc/self/fd")
+        .unwrap()
+        .filter_map(|entry| fs::read_link(entry.ok()?.path()).ok())
+        .filter(|target| target.starts_with(dir))
+        .count();
+    let prefix = format!("{}/", dir.display());
+    let maps = fs::read_to_string("/proc/self/maps")
+        .unwrap()
+        .lines()
+        .filter(|line| line.contains(&prefix))
+        .count();
+    (fds, maps)
+}
+
+#[test]
+fn close_stops_threads_and_releases_files() {
+    let _env = setup_test_env();
+    let before = thread_count();
+
+    for round in 0..20u64 {
+        let wal = open_wal(FsyncSchedule::Milliseconds(200));
+        // 3KB entries roll over to a new file every 8 entries
+        for _ in 0..3 {
+            wal.append_for_topic("events", &[round as u8; 3 * 1024])
+                .unwrap();
+        }
+        assert_ne!(open_handles(&wal_dir()), (0, 0));
+        wal.close().unwrap();
+        assert_eq!(open_handles(&wal_dir()), (0, 0));
+    }
+
+    // The harness itself may start or finish a thread meanwhile, but not forty
+    assert!(thread_count() < before + 5);
+    let wal = open_wal(FsyncSchedule::Milliseconds(200));
+    for seq in 0..60 {
+        let entry = wal.read_next("events", true).unwrap().unwrap();
+        assert_eq!((entry.seq, entry.data[0]), (seq, (seq / 3) as u8));
+    }
+    assert!(wal.read_next("events", true).unwrap().is_none());
+}
+
+#[test]
+fn dropping_an_instance_shuts_it_down() {
+    let _env = setup_test_env();
+    let before = thread_count();
+
+    for _ in 0..10 {
+        let wal = open_wal(FsyncSchedule::SyncEach);
+        wal.append_for_topic("events", b"entry").unwrap();
+        drop(wal);
+        assert_eq!(open_handles(&wal_dir()), (0, 0));
+    }
+
+    assert!(thread_count() < before + 5);
+    let wal = open_wal(FsyncSchedule::SyncEach);
+    assert_eq!(wal.get_topic_entry_count("events"), 10);
+}
+
+#[test]
+fn checkpoints_and_clean_markers_survive_close() {
+    let _env = setup_test_env();
+    let wal = open_wal(FsyncSchedule::NoFsync);
+    for i in 0..6 {
+        wal.append_for_topic("orders", &[i]).unwrap();
+    }
+    for _ in 0..4 {
+        wal.read_next("orders", true).unwrap().unwrap();
+    }
+    wal.mark_topic_dirty("orders");
+    wal.mark_topic_dirty("audit");
+    wal.mark_topic_clean("audit");
+    wal.close().unwrap();
+
+    let wal = open_wal(FsyncSchedule::NoFsync);
+    assert_eq!(wal.read_next("orders", true).unwrap().unwrap().data, [4]);
+    assert!(!wal.topic_is_clean("orders"));
+    assert!(wal.topic_is_clean("audit"));
+}
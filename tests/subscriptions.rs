@@ -0,0 +1,136 @@
+mod common;
+
+use common::{TestEnv, payload};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use walrus_rust::{
+    CheckpointPolicy, FsyncSchedule, ReadConsistency, SeekTarget, SubscribeOptions, Walrus,
+};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+fn open_wal(consistency: ReadConsistency) -> Walrus {
+    Walrus::builder()
+        .consistency(consistency)
+        .fsync(FsyncSchedule::NoFsync)
+        .block_size(16 * 1024)
+        .blocks_per_file(8)
+        .open()
+        .unwrap()
+}
+
+fn idle(policy: CheckpointPolicy) -> SubscribeOptions {
+    SubscribeOptions::new()
+        .checkpoint(policy)
+        .max_batch_bytes(8 * 1024)
+        .idle_timeout(Duration::from_millis(100))
+}
+
+#[test]
+fn auto_checkpoint_resumes_after_last_processed_entry() {
+    let _env = setup_test_env();
+    {
+        let wal = open_wal(ReadConsistency::StrictlyAtOnce);
+        for i in 0..12 {
+            wal.append_for_topic("orders", &payload(i)).unwrap();
+        }
+        let mut sub = wal
+            .subscribe("orders", idle(CheckpointPolicy::Auto))
+            .unwrap();
+        for i in 0..8 {
+            let entry = sub.next().unwrap().unwrap();
+            assert_eq!((entry.seq, entry.data), (i, payload(i)));
+        }
+        // Entry 7 was handed out but never followed by another request
+    }
+
+    let wal = open_wal(ReadConsistency::StrictlyAtOnce);
+    let seqs: Vec<u64> = wal
+        .subscribe("orders", idle(CheckpointPolicy::Auto))
+        .unwrap()
+        .map(|entry| entry.unwrap().seq)
+        .collect();
+    assert_eq!(seqs, (7..12).collect::<Vec<u64>>());
+}
+
+#[test]
+fn manual_ack_replays_unacked_entries_after_restart() {
+    let _env = setup_test_env();
+    {
+        let wal = open_wal(ReadConsistency::StrictlyAtOnce);
+        for i in 0..10 {
+            wal.append_for_topic("jobs", &payload(i)).unwrap();
+        }
+        let mut sub = wal
+            .subscribe("jobs", idle(CheckpointPolicy::Manual))
+            .unwrap();
+        // Acknowledge right at a block boundary, then read on without acknowledging
+        for i in 0..4 {
+            assert_eq!(sub.next().unwrap().unwrap().seq, i);
+        }
+        sub.ack().unwrap();
+        for i in 4..9 {
+            assert_eq!(sub.next().unwrap().unwrap().seq, i);
+        }
+    }
+
+    let wal = open_wal(ReadConsistency::StrictlyAtOnce);
+    let mut sub = wal
+        .subscribe("jobs", idle(CheckpointPolicy::Manual))
+        .unwrap();
+    let seqs: Vec<u64> = sub.by_ref().map(|entry| entry.unwrap().seq).collect();
+    assert_eq!(seqs, (4..10).collect::<Vec<u64>>());
+    sub.ack().unwrap();
+    drop(sub);
+    assert!(wal.read_next("jobs", true).unwrap().is_none());
+}
+
+#[test]
+fn every_n_group_subscription_from_start_position_follows_live_appends() {
+    let _env = setup_test_env();
+    let wal = Arc::new(open_wal(ReadConsistency::AtLeastOnce { persist_every: 2 }));
+    for i in 0..6 {
+        wal.append_for_topic("feed", &payload(i)).unwrap();
+    }
+
+    let writer = {
+        let wal = wal.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            for i in 6..9 {
+                wal.append_for_topic("feed", &payload(i)).unwrap();
+            }
+        })
+    };
+    let options = SubscribeOptions::new()
+        .group("audit")
+        .start(SeekTarget::Sequence(3))
+        .checkpoint(CheckpointPolicy::EveryN(3))
+        .idle_timeout(Duration::from_secs(5));
+    let mut sub = wal.subscribe("feed", options).unwrap();
+    let seqs: Vec<u64> = (0..6).map(|_| sub.next().unwrap().unwrap().seq).collect();
+    assert_eq!(seqs, (3..9).collect::<Vec<u64>>());
+    writer.join().unwrap();
+    // The second group of three is acknowledged without waiting for another request
+    sub.ack().unwrap();
+    drop(sub);
+
+    // The default cursor is untouched by the group's subscription
+    let all: Vec<u64> = wal
+        .subscribe(
+            "feed",
+            SubscribeOptions::new().idle_timeout(Duration::from_millis(100)),
+        )
+        .unwrap()
+        .map(|entry| entry.unwrap().seq)
+        .collect();
+    assert_eq!(all, (0..9).collect::<Vec<u64>>());
+    drop(wal);
+
+    // Six acknowledged entries with persist_every = 2 leave the group after entry 8
+    let wal = open_wal(ReadConsistency::AtLeastOnce { persist_every: 2 });
+    assert!(wal.read_next_as("audit", "feed", true).unwrap().is_none());
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use common::{TestEnv, payload};
+use walrus_rust::{FsyncSchedule, ReadConsistency, SeekTarget, Walrus};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+fn open_wal() -> Walrus {
+    Walrus::builder()
+        .consistency(ReadConsistency::StrictlyAtOnce)
+        .fsync(FsyncSchedule::NoFsync)
+        .block_size(16 * 1024)
+        .blocks_per_file(8)
+        .open()
+        .unwrap()
+}
+
+#[test]
+fn deleted_topic_stays_deleted_across_restart() {
+    let _env = setup_test_env();
+    {
+        let wal = open_wal();
+        for i in 0..10 {
+            wal.append_for_topic("doomed", &payload(i)).unwrap();
+            wal.append_for_topic("kept", &payload(i)).unwrap();
+        }
+        assert_eq!(wal.read_next("doomed", true).unwrap().unwrap().seq, 0);
+        assert_eq!(
+            wal.read_next_as("audit", "doomed", true)
+                .unwrap()
+                .unwrap()
+                .seq,
+            0
+        );
+
+        wal.delete_topic("doomed").unwrap();
+        assert!(wal.read_next("doomed", true).unwrap().is_none());
+        assert!(wal.read_next_as("audit", "doomed", true).unwrap().is_none());
+        assert_eq!(wal.get_topic_entry_count("doomed"), 0);
+        assert_eq!(wal.get_topic_size("doomed"), 0);
+        assert!(wal.topic_is_clean("doomed"));
+
+        // The topic starts over, numbering after the deleted entries
+        assert_eq!(wal.append_for_topic("doomed", &payload(10)).unwrap(), 10);
+        assert_eq!(wal.get_topic_entry_count("doomed"), 1);
+    }
+
+    let wal = open_wal();
+    let entry = wal.read_next("doomed", true).unwrap().unwrap();
+    assert_eq!((entry.seq, entry.data), (10, payload(10)));
+    assert!(wal.read_next("doomed", true).unwrap().is_none());
+    // The group's cursor went with the topic; a new one starts at the surviving entry
+    assert_eq!(
+        wal.read_next_as("audit", "doomed", true)
+            .unwrap()
+            .unwrap()
+            .seq,
+        10
+    );
+    let kept: Vec<u64> = (0..10)
+        .map(|_| wal.read_next("kept", true).unwrap().unwrap().seq)
+        .collect();
+    assert_eq!(kept, (0..10).collect::<Vec<u64>>());
+    assert_eq!(wal.append_for_topic("doomed", b"again").unwrap(), 11);
+}
+
+#[test]
+fn deleting_a_recovered_topic_keeps_its_numbering() {
+    let _env = setup_test_env();
+    {
+        let wal = open_wal();
+        for i in 0..6 {
+            wal.append_for_topic("logs", &payload(i)).unwrap();
+        }
+    }
+    {
+        let wal = open_wal();
+        assert_eq!(wal.get_topic_entry_count("logs"), 6);
+        wal.delete_topic("logs").unwrap();
+        assert!(wal.read_next("logs", true).unwrap().is_none());
+    }
+
+    // Nothing was appended since the deletion, yet numbering still continues
+    let wal = open_wal();
+    assert!(wal.read_next("logs", true).unwrap().is_none());
+    assert_eq!(wal.get_topic_entry_count("logs"), 0);
+    assert_eq!(wal.append_for_topic("logs", b"fresh").unwrap(), 6);
+    assert_eq!(wal.read_next("logs", true).unwrap().unwrap().seq, 6);
+}
+
+#[test]
+fn truncation_moves_lagging_cursors_and_survives_restart() {
+    let _env = setup_test_env();
+    {
+        let wal = open_wal();
+        for i in 0..12 {
+            wal.append_for_topic("events", &payload(i)).unwrap();
+        }
+        assert_eq!(wal.read_next("events", true).unwrap().unwrap().seq, 0);
+        for i in 0..6 {
+            let entry = wal.read_next_as("audit", "events", true).unwrap().unwrap();
+            assert_eq!(entry.seq, i);
+        }
+
+        // Entries 4..8 share a block with entry 6, so only the first block goes
+        assert_eq!(
+            wal.truncate_topic_before("events", SeekTarget::Sequence(6))
+                .unwrap(),
+            4
+        );
+        assert_eq!(wal.get_topic_entry_count("events"), 8);
+        assert_eq!(wal.get_topic_entry_count_as("audit", "events"), 6);
+        // Nothing before the floor is reachable, even by seeking back
+        assert_eq!(
+            wal.seek_topic("events", SeekTarget::Beginning).unwrap(),
+            4
+        );
+        assert_eq!(
+            wal.truncate_topic_before("events", SeekTarget::Timestamp(0))
+                .unwrap(),
+            4
+        );
+    }
+
+    {
+        let wal = open_wal();
+        assert_eq!(wal.get_topic_entry_count("events"), 8);
+        assert_eq!(wal.read_next("events", true).unwrap().unwrap().seq, 4);
+        assert_eq!(
+            wal.read_next_as("audit", "events", true)
+                .unwrap()
+                .unwrap()
+                .seq,
+            6
+        );
+        assert_eq!(
+            wal.read_next_as("fresh", "events", true)
+                .unwrap()
+                .unwrap()
+                .seq,
+            4
+        );
+
+        assert_eq!(
+            wal.truncate_topic_before("events", SeekTarget::End).unwrap(),
+            12
+        );
+        assert!(wal.read_next("events", true).unwrap().is_none());
+        assert!(wal.read_next_as("audit", "events", true).unwrap().is_none());
+    }
+
+    let wal = open_wal();
+    assert!(wal.read_next("events", true).unwrap().is_none());
+    assert_eq!(wal.append_for_topic("events", b"next").unwrap(), 12);
+    assert_eq!(wal.read_next("events", true).unwrap().unwrap().seq, 12);
+}
+
+#[test]
+fn commits_behind_a_truncation_are_ignored() {
+    let _env = setup_test_env();
+    {
+        let wal = open_wal();
+        for i in 0..12 {
+            wal.append_for_topic("jobs", &payload(i)).unwrap();
+        }
+        let lease = wal.read_leased("jobs", 4 * 1024).unwrap().unwrap();
+        assert_eq!(lease.token.next_seq(), 1);
+        assert_eq!(
+            wal.truncate_topic_before("jobs", SeekTarget::Sequence(8))
+                .unwrap(),
+            8
+        );
+        wal.commit("jobs", lease.token).unwrap();
+    }
+
+    let wal = open_wal();
+    assert_eq!(wal.read_next("jobs", true).unwrap().unwrap().seq, 8);
+}
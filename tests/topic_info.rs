@@ -0,0 +1,183 @@
+mod common;
+
+use common::{ENTRY_BYTES, TestEnv, payload};
+use walrus_rust::{FsyncSchedule, ReadConsistency, ReaderPosition, SeekTarget, Walrus};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+fn open_wal() -> Walrus {
+    Walrus::builder()
+        .consistency(ReadConsistency::StrictlyAtOnce)
+        .fsync(FsyncSchedule::NoFsync)
+        .block_size(16 * 1024)
+        .blocks_per_file(8)
+        .open()
+        .unwrap()
+}
+
+#[test]
+fn list_topics_tracks_appends_and_deletion() {
+    let _env = setup_test_env();
+    let wal = open_wal();
+    assert!(wal.list_topics().is_empty());
+
+    wal.append_for_topic("zeta", b"z").unwrap();
+    wal.append_for_topic("alpha", b"a").unwrap();
+    wal.append_for_topic("mid", b"m").unwrap();
+    assert_eq!(wal.list_topics(), vec!["alpha", "mid", "zeta"]);
+
+    wal.delete_topic("mid").unwrap();
+    assert_eq!(wal.list_topics(), vec!["alpha", "zeta"]);
+}
+
+#[test]
+fn topic_info_reports_blocks_and_tail() {
+    let _env = setup_test_env();
+    let wal = open_wal();
+    for i in 0..10 {
+        wal.append_for_topic("events", &payload(i)).unwrap();
+    }
+
+    let info = wal.topic_info("events").unwrap();
+    assert_eq!(info.topic, "events");
+    assert_eq!(info.blocks.len(), 2);
+    assert!(info.blocks.iter().all(|b| b.used == 4 * ENTRY_BYTES));
+    assert_ne!(info.blocks[0].id, info.blocks[1].id);
+    assert!(info.blocks.iter().all(|b| !b.file.is_empty()));
+
+    let active = info.active_block.unwrap();
+    assert_eq!(active.used, 2 * ENTRY_BYTES);
+    assert_eq!(
+        info.reader_position,
+        Some(ReaderPosition {
+            block_id: info.blocks[0].id,
+            offset: 0,
+        })
+    );
+    assert_eq!(info.unread_entries, 10);
+    assert_eq!(info.unread_bytes, 10 * ENTRY_BYTES);
+    assert_eq!(info.next_seq, 10);
+    assert!(!info.batch_write_pending);
+}
+
+#[test]
+fn topic_info_follows_reads() {
+    let _env = setup_test_env();
+    let wal = open_wal();
+    for i in 0..10 {
+        wal.append_for_topic("events", &payload(i)).unwrap();
+    }
+    for _ in 0..5 {
+        wal.read_next("events", true).unwrap().unwrap();
+    }
+
+    let info = wal.topic_info("events").unwrap();
+    assert_eq!(
+        info.reader_position,
+        Some(ReaderPosition {
+            block_id: info.blocks[1].id,
+            offset: ENTRY_BYTES,
+        })
+    );
+    assert_eq!(info.unread_entries, 5);
+    assert_eq!(info.unread_bytes, 5 * ENTRY_BYTES);
+
+    // Caught up: the cursor sits at the writer's tail
+    for _ in 0..5 {
+        wal.read_next("events", true).unwrap().unwrap();
+    }
+    let info = wal.topic_info("events").unwrap();
+    let active = info.active_block.unwrap();
+    assert_eq!(
+        info.reader_position,
+        Some(ReaderPosition {
+            block_id: active.id,
+            offset: active.used,
+        })
+    );
+    assert_eq!(info.unread_entries, 0);
+    assert_eq!(info.unread_bytes, 0);
+}
+
+#[test]
+fn topic_info_is_per_consumer_group() {
+    let _env = setup_test_env();
+    let wal = open_wal();
+    for i in 0..6 {
+        wal.append_for_topic("events", &payload(i)).unwrap();
+    }
+    for _ in 0..5 {
+        wal.read_next("events", true).unwrap().unwrap();
+    }
+
+    // A group that has not read yet sees the whole topic ahead of it
+    let fresh = wal.topic_info_as("audit", "events").unwrap();
+    assert_eq!(fresh.unread_entries, 6);
+    assert_eq!(fresh.unread_bytes, 6 * ENTRY_BYTES);
+    assert_eq!(fresh.reader_position.unwrap().block_id, fresh.blocks[0].id);
+
+    wal.read_next_as("audit", "events", true).unwrap().unwrap();
+    let audit = wal.topic_info_as("audit", "events").unwrap();
+    assert_eq!(audit.unread_entries, 5);
+    assert_eq!(audit.unread_bytes, 5 * ENTRY_BYTES);
+    assert_eq!(wal.topic_info("events").unwrap().unread_entries, 1);
+
+    assert!(wal.topic_info_as("", "events").is_err());
+}
+
+#[test]
+fn topic_info_after_restart_and_truncation() {
+    let _env = setup_test_env();
+    {
+        let wal = open_wal();
+        for i in 0..10 {
+            wal.append_for_topic("events", &payload(i)).unwrap();
+        }
+        for _ in 0..2 {
+            wal.read_next("events", true).unwrap().unwrap();
+        }
+    }
+
+    let wal = open_wal();
+    assert_eq!(wal.list_topics(), vec!["events"]);
+    let info = wal.topic_info("events").unwrap();
+    // Recovered blocks are all sealed until the topic is appended to again
+    assert_eq!(info.blocks.len(), 3);
+    assert!(info.active_block.is_none());
+    assert_eq!(info.next_seq, 10);
+    assert_eq!(
+        info.reader_position,
+        Some(ReaderPosition {
+            block_id: info.blocks[0].id,
+            offset: 2 * ENTRY_BYTES,
+        })
+    );
+    assert_eq!(info.unread_bytes, 8 * ENTRY_BYTES);
+
+    assert_eq!(
+        wal.truncate_topic_before("events", SeekTarget::Sequence(4))
+            .unwrap(),
+        4
+    );
+    let info = wal.topic_info("events").unwrap();
+    assert_eq!(info.blocks.len(), 2);
+    assert_eq!(info.reader_position.unwrap().block_id, info.blocks[0].id);
+    assert_eq!(info.unread_entries, 6);
+    assert_eq!(info.unread_bytes, 6 * ENTRY_BYTES);
+}
+
+#[test]
+fn unknown_topic_is_empty() {
+    let _env = setup_test_env();
+    let wal = open_wal();
+    let info = wal.topic_info("missing").unwrap();
+    assert!(info.blocks.is_empty());
+    assert!(info.active_block.is_none());
+    assert!(info.reader_position.is_none());
+    assert_eq!(
+        (info.unread_entries, info.unread_bytes, info.next_seq),
+        (0, 0, 0)
+    );
+}
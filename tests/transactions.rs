@@ -0,0 +1,176 @@
+mod common;
+
+use common::TestEnv;
+use std::time::Duration;
+use walrus_rust::{FsyncSchedule, ReadConsistency, Walrus, WalrusBuilder};
+
+fn setup_test_env() -> TestEnv {
+    TestEnv::new()
+}
+
+fn builder() -> WalrusBuilder {
+    Walrus::builder()
+        .consistency(ReadConsistency::StrictlyAtOnce)
+        .fsync(FsyncSchedule::NoFsync)
+        .block_size(16 * 1024)
+        .blocks_per_file(4)
+}
+
+fn read_all(wal: &Walrus, topic: &str) -> Vec<(u64, Vec<u8>)> {
+    let mut entries = Vec::new();
+    while let Some(entry) = wal.read_next(topic, true).unwrap() {
+        entries.push((entry.seq, entry.data));
+    }
+    entries
+}
+
+#[test]
+fn committed_entries_become_readable_together() {
+    let _env = setup_test_env();
+    let wal = builder().open().unwrap();
+
+    let mut tx = wal.begin_transaction();
+    assert_eq!(tx.append("orders", b"create").unwrap(), 0);
+    assert_eq!(tx.append("outbox", b"created").unwrap(), 0);
+    assert_eq!(tx.append("orders", b"confirm").unwrap(), 1);
+    assert!(wal.read_next("orders", true).unwrap().is_none());
+    assert!(
+        wal.batch_read_for_topic("outbox", 1024, true, None)
+            .unwrap()
+            .is_empty()
+    );
+
+    tx.commit().unwrap();
+    assert_eq!(
+        read_all(&wal, "orders"),
+        vec![(0, b"create".to_vec()), (1, b"confirm".to_vec())]
+    );
+    let outbox = wal
+        .batch_read_for_topic("outbox", 1024, true, None)
+        .unwrap();
+    assert_eq!(outbox.len(), 1);
+    assert_eq!(outbox[0].data, b"created");
+    // The commit marker took the next sequence number
+    assert_eq!(wal.append_for_topic("orders", b"after").unwrap(), 3);
+    assert_eq!(read_all(&wal, "orders"), vec![(3, b"after".to_vec())]);
+}
+
+#[test]
+fn aborted_entries_are_skipped_by_readers() {
+    let _env = setup_test_env();
+    let wal = builder().open().unwrap();
+    wal.append_for_topic("orders", b"before").unwrap();
+
+    let mut tx = wal.begin_transaction();
+    tx.append("orders", b"discarded").unwrap();
+    wal.append_for_topic("orders", b"behind").unwrap();
+    // Readers stop in front of the undecided entry, whatever follows it
+    assert_eq!(read_all(&wal, "orders"), vec![(0, b"before".to_vec())]);
+
+    tx.abort().unwrap();
+    assert_eq!(read_all(&wal, "orders"), vec![(2, b"behind".to_vec())]);
+
+    let mut tx = wal.begin_transaction();
+    tx.append("orders", b"dropped").unwrap();
+    wal.append_for_topic("orders", b"last").unwrap();
+    drop(tx);
+    let batch = wal
+        .batch_read_for_topic("orders", 1024, true, None)
+        .unwrap();
+    let data: Vec<&[u8]> = batch.iter().map(|e| e.data.as_slice()).collect();
+    assert_eq!(data, vec![b"last".as_slice()]);
+}
+
+#[test]
+fn batch_reads_get_past_runs_of_markers() {
+    let _env = setup_test_env();
+    let wal = builder().open().unwrap();
+    for _ in 0..20 {
+        let mut tx = wal.begin_transaction();
+        tx.append("events", &[0u8; 512]).unwrap();
+        tx.abort().unwrap();
+    }
+    wal.append_for_topic("events", b"visible").unwrap();
+
+    // The budget is used up by aborted entries many times over
+    let batch = wal.batch_read_for_topic("events", 1, true, None).unwrap();
+    assert_eq!(batch.len(), 1);
+    assert_eq!(
+        (batch[0].seq, batch[0].data.as_slice()),
+        (40, b"visible".as_slice())
+    );
+}
+
+#[test]
+fn blocked_readers_wake_up_on_commit() {
+    let _env = setup_test_env();
+    let wal = builder().open().unwrap();
+    let mut tx = wal.begin_transaction();
+    tx.append("orders", b"create").unwrap();
+
+    std::thread::scope(|s| {
+        let reader = s.spawn(|| {
+            wal.read_next_blocking("orders", true, Duration::from_secs(10))
+                .unwrap()
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        tx.commit().unwrap();
+        let entry = reader.join().unwrap().unwrap();
+        assert_eq!(entry.data, b"create");
+    });
+}
+
+#[test]
+fn recovery_aborts_unfinished_transactions() {
+    let _env = setup_test_env();
+    let (committed_id, open_id) = {
+        let wal = builder().open().unwrap();
+        let mut tx = wal.begin_transaction();
+        tx.append("orders", b"kept").unwrap();
+        tx.append("outbox", b"kept").unwrap();
+        let committed_id = tx.id();
+        tx.commit().unwrap();
+
+        let mut aborted = wal.begin_transaction();
+        aborted.append("orders", b"aborted").unwrap();
+        aborted.abort().unwrap();
+
+        let mut open = wal.begin_transaction();
+        open.append("orders", b"in flight").unwrap();
+        open.append("outbox", b"in flight").unwrap();
+        wal.append_for_topic("orders", b"plain").unwrap();
+        let open_id = open.id();
+        // The process dies with the transaction undecided
+        std::mem::forget(open);
+        (committed_id, open_id)
+    };
+
+    let wal = builder().open().unwrap();
+    assert_eq!(
+        read_all(&wal, "orders"),
+        vec![(0, b"kept".to_vec()), (5, b"plain".to_vec())]
+    );
+    assert_eq!(read_all(&wal, "outbox"), vec![(0, b"kept".to_vec())]);
+
+    let mut tx = wal.begin_transaction();
+    assert!(tx.id() > committed_id.max(open_id));
+    tx.append("outbox", b"next").unwrap();
+    tx.commit().unwrap();
+    assert_eq!(read_all(&wal, "outbox"), vec![(3, b"next".to_vec())]);
+}
+
+#[test]
+fn aborted_entries_stay_hidden_while_on_disk() {
+    let _env = setup_test_env();
+    let wal = builder().open().unwrap();
+
+    let mut tx = wal.begin_transaction();
+    tx.append("orders", b"dropped").unwrap();
+    tx.append("audit", b"dropped").unwrap();
+    drop(tx);
+    wal.append_for_topic("orders", b"kept").unwrap();
+
+    // Deleting a topic forgets aborted transactions, but not one still holding entries
+    wal.delete_topic("audit").unwrap();
+    assert_eq!(read_all(&wal, "orders"), vec![(2, b"kept".to_vec())]);
+}
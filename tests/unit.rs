@@ -6,7 +6,7 @@ use std::io::{Read, Seek, SeekFrom, Write};
 use std::thread;
 use std::time::Duration;
 use walrus_rust::ReadConsistency;
-use walrus_rust::wal::{Entry, WalIndex, Walrus};
+use walrus_rust::wal::{BlockPos, Entry, WalIndex, Walrus};
 
 fn setup_wal_env() -> TestEnv {
     TestEnv::new()
@@ -499,6 +499,7 @@ mod entry_tests {
         let test_data = vec![1, 2, 3, 4, 5];
         let entry = Entry {
             data: test_data.clone(),
+            ..Default::default()
         };
 
         assert_eq!(entry.data, test_data);
@@ -507,7 +508,7 @@ mod entry_tests {
 
     #[test]
     fn entry_with_empty_data() {
-        let entry = Entry { data: Vec::new() };
+        let entry = Entry::default();
         assert!(entry.data.is_empty());
     }
 
@@ -516,6 +517,7 @@ mod entry_tests {
         let large_data = vec![42u8; 1024 * 1024];
         let entry = Entry {
             data: large_data.clone(),
+            ..Default::default()
         };
         assert_eq!(entry.data.len(), 1024 * 1024);
         assert_eq!(entry.data[0], 42);
@@ -597,6 +599,87 @@ mod wal_index_tests {
         assert_eq!(idx.get("key2").unwrap().cur_block_idx, 30);
         assert_eq!(idx.get("key3").unwrap().cur_block_idx, 50);
     }
+
+    #[test]
+    fn wal_index_stays_small_under_frequent_checkpoints() {
+        let _guard = setup_wal_env();
+        let path = current_wal_dir().join("test_compaction_index.db");
+        {
+            let mut idx = WalIndex::new("test_compaction").unwrap();
+            for i in 0..5000u64 {
+                idx.set(format!("topic-{}", i % 3), i, i * 10).unwrap();
+                if i % 100 == 0 {
+                    idx.update(&["topic-2".to_string()], vec![]).unwrap();
+                }
+            }
+            idx.remove("topic-0").unwrap();
+            // Records of the three keys are folded together every so often
+            assert!(std::fs::metadata(&path).unwrap().len() < 64 * 1024);
+        }
+
+        let idx = WalIndex::new("test_compaction").unwrap();
+        assert!(idx.get("topic-0").is_none());
+        assert_eq!(idx.get("topic-1").unwrap().cur_block_idx, 4999);
+        assert_eq!(idx.get("topic-2").unwrap().cur_block_offset, 49970);
+    }
+
+    #[test]
+    fn wal_index_ignores_a_torn_last_record() {
+        let _guard = setup_wal_env();
+        let path = current_wal_dir().join("test_torn_index.db");
+        {
+            let mut idx = WalIndex::new("test_torn").unwrap();
+            idx.set("kept".to_string(), 1, 10).unwrap();
+            idx.update(&[], vec![("a".to_string(), 2, 20), ("b".to_string(), 3, 30)])
+                .unwrap();
+        }
+        // A crash in the middle of appending the next record
+        let len = std::fs::metadata(&path).unwrap().len();
+        {
+            let mut idx = WalIndex::new("test_torn").unwrap();
+            idx.update(&["kept".to_string()], vec![("a".to_string(), 9, 90)])
+                .unwrap();
+        }
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len + 20).unwrap();
+
+        let mut idx = WalIndex::new("test_torn").unwrap();
+        assert_eq!(idx.get("kept").unwrap().cur_block_idx, 1);
+        assert_eq!(idx.get("a").unwrap().cur_block_idx, 2);
+        assert_eq!(idx.get("b").unwrap().cur_block_idx, 3);
+
+        // Later changes are not lost behind the torn record
+        idx.set("b".to_string(), 4, 40).unwrap();
+        drop(idx);
+        let idx = WalIndex::new("test_torn").unwrap();
+        assert_eq!(idx.get("b").unwrap().cur_block_idx, 4);
+        assert_eq!(idx.get("kept").unwrap().cur_block_idx, 1);
+    }
+
+    #[test]
+    fn wal_index_reads_files_written_as_a_single_map() {
+        let _guard = setup_wal_env();
+        let mut map = std::collections::HashMap::new();
+        map.insert(
+            "orders".to_string(),
+            BlockPos {
+                cur_block_idx: 5,
+                cur_block_offset: 512,
+            },
+        );
+        let bytes = rkyv::to_bytes::<_, 256>(&map).unwrap();
+        std::fs::create_dir_all(current_wal_dir()).unwrap();
+        std::fs::write(current_wal_dir().join("test_legacy_index.db"), &bytes).unwrap();
+
+        let mut idx = WalIndex::new("test_legacy").unwrap();
+        assert_eq!(idx.get("orders").unwrap().cur_block_offset, 512);
+        idx.set("events".to_string(), 1, 64).unwrap();
+        drop(idx);
+
+        let idx = WalIndex::new("test_legacy").unwrap();
+        assert_eq!(idx.get("orders").unwrap().cur_block_offset, 512);
+        assert_eq!(idx.get("events").unwrap().cur_block_offset, 64);
+    }
 }
 
 mod walrus_integration_tests {
@@ -833,6 +916,90 @@ mod error_handling_tests {
     }
 }
 
+mod file_header_tests {
+    use super::*;
+    use walrus_rust::wal::FILE_HEADER_SIZE;
+
+    fn read_file(path: &str) -> Vec<u8> {
+        std::fs::read(path).unwrap()
+    }
+
+    #[test]
+    fn data_files_start_with_versioned_header() {
+        let _guard = setup_wal_env();
+        let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+        wal.append_for_topic("hdr", b"after_the_header").unwrap();
+
+        let bytes = read_file(&first_data_file());
+        assert_eq!(&bytes[0..8], b"WALRUSDF");
+        assert_eq!(u32::from_le_bytes(bytes[8..12].try_into().unwrap()), 1);
+        assert_eq!(
+            u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as u64,
+            FILE_HEADER_SIZE
+        );
+        // default geometry: 10MB blocks, 100 blocks per file
+        assert_eq!(
+            u64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+            10 * 1024 * 1024
+        );
+        assert_eq!(u64::from_le_bytes(bytes[24..32].try_into().unwrap()), 100);
+        let pos = bytes
+            .windows(16)
+            .position(|w| w == b"after_the_header")
+            .unwrap();
+        assert!(pos as u64 >= FILE_HEADER_SIZE);
+    }
+
+    #[test]
+    fn newer_format_version_is_refused() {
+        let _guard = setup_wal_env();
+        {
+            let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+            wal.append_for_topic("hdr", b"x").unwrap();
+        }
+
+        let path = first_data_file();
+        let mut header = read_file(&path)[..40].to_vec();
+        header[8..12].copy_from_slice(&99u32.to_le_bytes());
+        let checksum = common::checksum64(&header[..32]);
+        header[32..40].copy_from_slice(&checksum.to_le_bytes());
+        {
+            let mut f = OpenOptions::new().write(true).open(&path).unwrap();
+            f.write_all(&header).unwrap();
+        }
+
+        let err = match Walrus::with_consistency(ReadConsistency::StrictlyAtOnce) {
+            Ok(_) => panic!("file with a newer format version was accepted"),
+            Err(e) => e,
+        };
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("format version 99"));
+    }
+
+    #[test]
+    fn headerless_files_still_recover() {
+        let _guard = setup_wal_env();
+        {
+            let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+            wal.append_for_topic("legacy", b"one").unwrap();
+            wal.append_for_topic("legacy", b"two").unwrap();
+        }
+        thread::sleep(Duration::from_millis(50));
+
+        // Rewrite the file in the original layout, with blocks starting at offset 0
+        let path = first_data_file();
+        let bytes = read_file(&path);
+        let mut legacy = bytes[FILE_HEADER_SIZE as usize..].to_vec();
+        legacy.resize(bytes.len(), 0);
+        std::fs::write(&path, &legacy).unwrap();
+
+        let wal = Walrus::with_consistency(ReadConsistency::StrictlyAtOnce).unwrap();
+        assert_eq!(wal.read_next("legacy", true).unwrap().unwrap().data, b"one");
+        assert_eq!(wal.read_next("legacy", true).unwrap().unwrap().data, b"two");
+        assert!(wal.read_next("legacy", true).unwrap().is_none());
+    }
+}
+
 mod stress_tests {
     use super::*;
 